@@ -0,0 +1,327 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::download::{SerDesArxiv, paper_id_without_version};
+
+/// Collapse the newlines and repeated spaces found in arXiv titles
+pub fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Leading title words skipped when building citation keys
+const SKIPPED_TITLE_WORDS: [&str; 5] = ["a", "an", "the", "on", "of"];
+
+/// Lowercased alphanumeric characters of `text`
+fn key_part(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Citation key made of the first author's surname, the year and the first
+/// word of the title, skipping articles (e.g. vaswani2017attention)
+pub fn cite_key(paper: &SerDesArxiv) -> String {
+    let surname = paper
+        .authors
+        .first()
+        .and_then(|author| author.split_whitespace().last())
+        .map(key_part)
+        .filter(|surname| !surname.is_empty())
+        .unwrap_or_else(|| "anonymous".to_string());
+    let year = paper.published.get(..4).unwrap_or("");
+    let word = paper
+        .title
+        .split_whitespace()
+        .map(key_part)
+        .find(|word| !word.is_empty() && !SKIPPED_TITLE_WORDS.contains(&word.as_str()))
+        .unwrap_or_default();
+    format!("{}{}{}", surname, year, word)
+}
+
+/// Citation keys of the papers, where keys shared by several papers, or
+/// with one of the `taken` keys, get an a, b, c, ... suffix in the order of
+/// the papers, as in vaswani2017attentiona, skipping the suffixed keys that
+/// are taken too
+pub fn cite_keys<'a>(
+    papers: impl IntoIterator<Item = &'a SerDesArxiv>,
+    taken: &HashSet<String>,
+) -> Vec<String> {
+    let keys: Vec<String> = papers.into_iter().map(cite_key).collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for key in &keys {
+        *counts.entry(key.as_str()).or_default() += 1;
+    }
+    let mut used = taken.clone();
+    let mut next: HashMap<&str, usize> = HashMap::new();
+    keys.iter()
+        .map(|key| {
+            if counts[key.as_str()] == 1 && used.insert(key.clone()) {
+                return key.clone();
+            }
+            let index = next.entry(key.as_str()).or_default();
+            loop {
+                let candidate = format!("{}{}", key, letter_suffix(*index));
+                *index += 1;
+                if used.insert(candidate.clone()) {
+                    return candidate;
+                }
+            }
+        })
+        .collect()
+}
+
+/// Citation keys and arXiv ids of the entries of a BibTeX file
+#[derive(Debug, Default)]
+pub struct BibtexEntries {
+    pub keys: HashSet<String>,
+    /// Values of the eprint fields, without version
+    pub eprints: HashSet<String>,
+}
+
+/// Read the citation keys and arXiv ids of the entries already in a BibTeX
+/// file, e.g. one written by a previous run, which new entries are appended to
+pub fn bibtex_entries(content: &str) -> BibtexEntries {
+    let mut entries = BibtexEntries::default();
+    for line in content.lines().map(str::trim) {
+        if let Some(header) = line.strip_prefix('@') {
+            if let Some((_, rest)) = header.split_once('{') {
+                let key = rest.split(',').next().unwrap_or_default().trim();
+                if !key.is_empty() {
+                    entries.keys.insert(key.to_string());
+                }
+            }
+        } else if let Some((name, value)) = line.split_once('=')
+            && name.trim().eq_ignore_ascii_case("eprint")
+        {
+            let value = value
+                .trim()
+                .trim_end_matches(',')
+                .trim_matches(|c| matches!(c, '{' | '}' | '"' | ' '));
+            entries
+                .eprints
+                .insert(paper_id_without_version(value).to_string());
+        }
+    }
+    entries
+}
+
+/// a, b, ..., z, aa, ab, ... for 0, 1, ..., 25, 26, 27, ...
+fn letter_suffix(mut index: usize) -> String {
+    let mut suffix = vec![];
+    loop {
+        suffix.push(b'a' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    suffix.reverse();
+    String::from_utf8(suffix).expect("letters are valid UTF-8")
+}
+
+/// Escape the characters that have a special meaning in BibTeX values
+pub fn escape_bibtex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in normalize_whitespace(text).chars() {
+        if matches!(c, '{' | '}' | '%' | '&') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Render the paper as a BibTeX entry with the given citation key: an
+/// @article when it has a journal reference, a @misc otherwise
+pub fn bibtex_entry(paper: &SerDesArxiv, key: &str) -> String {
+    let id = paper_id_without_version(&paper.id);
+    let authors: Vec<String> = paper
+        .authors
+        .iter()
+        .map(|author| escape_bibtex(author))
+        .collect();
+    let mut fields = vec![
+        ("title", escape_bibtex(&paper.title)),
+        ("author", authors.join(" and ")),
+        ("year", paper.published.get(..4).unwrap_or("").to_string()),
+    ];
+    if let Some(journal_ref) = &paper.journal_ref {
+        fields.push(("journal", escape_bibtex(journal_ref)));
+    }
+    fields.extend([
+        ("eprint", id.to_string()),
+        ("archivePrefix", "arXiv".to_string()),
+        ("primaryClass", paper.primary_category.clone()),
+    ]);
+    if let Some(doi) = &paper.doi {
+        fields.push(("doi", doi.clone()));
+    }
+    let url = match &paper.doi {
+        Some(doi) => format!("https://doi.org/{}", doi),
+        None => format!("https://arxiv.org/abs/{}", id),
+    };
+    fields.push(("url", url));
+    if !paper.summary.trim().is_empty() {
+        fields.push(("abstract", escape_bibtex(&paper.summary)));
+    }
+    let entry_type = if paper.journal_ref.is_some() {
+        "article"
+    } else {
+        "misc"
+    };
+    let mut entry = format!("@{}{{{},\n", entry_type, key);
+    for (name, value) in fields {
+        entry.push_str(&format!("  {} = {{{}}},\n", name, value));
+    }
+    entry.push_str("}\n");
+    entry
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn paper() -> SerDesArxiv {
+        SerDesArxiv {
+            id: "http://arxiv.org/abs/1706.03762v7".to_string(),
+            updated: "2023-08-02T00:41:18Z".to_string(),
+            published: "2017-06-12T17:57:34Z".to_string(),
+            title: "Attention Is All\n  You Need".to_string(),
+            authors: vec![
+                "Ashish Vaswani".to_string(),
+                "Noam Shazeer".to_string(),
+                "Niki Parmar".to_string(),
+            ],
+            primary_category: "cs.CL".to_string(),
+            categories: vec!["cs.CL".to_string(), "cs.LG".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cite_key() {
+        assert_eq!(cite_key(&paper()), "vaswani2017attention");
+        let mut old_style = paper();
+        old_style.id = "http://arxiv.org/abs/cs/0112017v1".to_string();
+        old_style.authors = vec![];
+        old_style.published = "2001-12-14T00:00:00Z".to_string();
+        old_style.title = "The Theory of Everything".to_string();
+        assert_eq!(cite_key(&old_style), "anonymous2001theory");
+        let mut accented = paper();
+        accented.authors = vec!["Gérard O'Brien-Dupont".to_string()];
+        accented.title = "{GPT}-4: a report".to_string();
+        assert_eq!(cite_key(&accented), "obriendupont2017gpt4");
+    }
+
+    #[test]
+    fn test_cite_keys_collisions() {
+        let mut other = paper();
+        other.title = "Attention, again".to_string();
+        let mut distinct = paper();
+        distinct.title = "Transformers".to_string();
+        let papers = [paper(), distinct, other, paper()];
+        assert_eq!(
+            cite_keys(&papers, &HashSet::new()),
+            vec![
+                "vaswani2017attentiona",
+                "vaswani2017transformers",
+                "vaswani2017attentionb",
+                "vaswani2017attentionc",
+            ]
+        );
+        // Keys already in the file are not reused, suffixed or not
+        let taken: HashSet<String> = ["vaswani2017transformers", "vaswani2017attentiona"]
+            .map(String::from)
+            .into();
+        assert_eq!(
+            cite_keys(&papers[..2], &taken),
+            vec!["vaswani2017attention", "vaswani2017transformersa"]
+        );
+        assert_eq!(
+            cite_keys(&papers, &taken),
+            vec![
+                "vaswani2017attentionb",
+                "vaswani2017transformersa",
+                "vaswani2017attentionc",
+                "vaswani2017attentiond",
+            ]
+        );
+        assert_eq!(letter_suffix(0), "a");
+        assert_eq!(letter_suffix(25), "z");
+        assert_eq!(letter_suffix(26), "aa");
+        assert_eq!(letter_suffix(27), "ab");
+        assert_eq!(letter_suffix(26 + 26 * 26), "aaa");
+    }
+
+    #[test]
+    fn test_bibtex_entries() {
+        let mut content = bibtex_entry(&paper(), "vaswani2017attention");
+        content.push_str("\n@misc{ doe2020graphs ,\n  EPRINT = \"cs/0112017v1\",\n}\n");
+        let entries = bibtex_entries(&content);
+        assert_eq!(
+            entries.keys,
+            ["vaswani2017attention", "doe2020graphs"]
+                .map(String::from)
+                .into()
+        );
+        assert_eq!(
+            entries.eprints,
+            ["1706.03762", "cs/0112017"].map(String::from).into()
+        );
+    }
+
+    #[test]
+    fn test_escape_bibtex() {
+        assert_eq!(
+            escape_bibtex("Rates of 50% & more in {LLMs}"),
+            "Rates of 50\\% \\& more in \\{LLMs\\}"
+        );
+        assert_eq!(escape_bibtex("Line\n  break"), "Line break");
+        assert_eq!(escape_bibtex("$\\alpha$-decay"), "$\\alpha$-decay");
+    }
+
+    #[test]
+    fn test_bibtex_entry() {
+        let entry = bibtex_entry(&paper(), "vaswani2017attention");
+        assert_eq!(
+            entry,
+            "@misc{vaswani2017attention,
+  title = {Attention Is All You Need},
+  author = {Ashish Vaswani and Noam Shazeer and Niki Parmar},
+  year = {2017},
+  eprint = {1706.03762},
+  archivePrefix = {arXiv},
+  primaryClass = {cs.CL},
+  url = {https://arxiv.org/abs/1706.03762},
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_bibtex_entry_published() {
+        let mut published = paper();
+        published.summary =
+            "We propose the Transformer, 100% attention & no {recurrence}.".to_string();
+        published.journal_ref = Some("NeurIPS 30 (2017) 5998-6008".to_string());
+        published.doi = Some("10.48550/arXiv.1706.03762".to_string());
+        let entry = bibtex_entry(&published, "vaswani2017attention");
+        assert_eq!(
+            entry,
+            "@article{vaswani2017attention,
+  title = {Attention Is All You Need},
+  author = {Ashish Vaswani and Noam Shazeer and Niki Parmar},
+  year = {2017},
+  journal = {NeurIPS 30 (2017) 5998-6008},
+  eprint = {1706.03762},
+  archivePrefix = {arXiv},
+  primaryClass = {cs.CL},
+  doi = {10.48550/arXiv.1706.03762},
+  url = {https://doi.org/10.48550/arXiv.1706.03762},
+  abstract = {We propose the Transformer, 100\\% attention \\& no \\{recurrence\\}.},
+}
+"
+        );
+        assert_eq!(published.to_bibtex(), entry);
+    }
+}
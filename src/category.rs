@@ -0,0 +1,268 @@
+/// Category codes of the arXiv taxonomy, see https://arxiv.org/category_taxonomy
+pub const CATEGORIES: [&str; 157] = [
+    "astro-ph",
+    "astro-ph.CO",
+    "astro-ph.EP",
+    "astro-ph.GA",
+    "astro-ph.HE",
+    "astro-ph.IM",
+    "astro-ph.SR",
+    "cond-mat",
+    "cond-mat.dis-nn",
+    "cond-mat.mes-hall",
+    "cond-mat.mtrl-sci",
+    "cond-mat.other",
+    "cond-mat.quant-gas",
+    "cond-mat.soft",
+    "cond-mat.stat-mech",
+    "cond-mat.str-el",
+    "cond-mat.supr-con",
+    "cs.AI",
+    "cs.AR",
+    "cs.CC",
+    "cs.CE",
+    "cs.CG",
+    "cs.CL",
+    "cs.CR",
+    "cs.CV",
+    "cs.CY",
+    "cs.DB",
+    "cs.DC",
+    "cs.DL",
+    "cs.DM",
+    "cs.DS",
+    "cs.ET",
+    "cs.FL",
+    "cs.GL",
+    "cs.GR",
+    "cs.GT",
+    "cs.HC",
+    "cs.IR",
+    "cs.IT",
+    "cs.LG",
+    "cs.LO",
+    "cs.MA",
+    "cs.MM",
+    "cs.MS",
+    "cs.NA",
+    "cs.NE",
+    "cs.NI",
+    "cs.OH",
+    "cs.OS",
+    "cs.PF",
+    "cs.PL",
+    "cs.RO",
+    "cs.SC",
+    "cs.SD",
+    "cs.SE",
+    "cs.SI",
+    "cs.SY",
+    "econ.EM",
+    "econ.GN",
+    "econ.TH",
+    "eess.AS",
+    "eess.IV",
+    "eess.SP",
+    "eess.SY",
+    "gr-qc",
+    "hep-ex",
+    "hep-lat",
+    "hep-ph",
+    "hep-th",
+    "math-ph",
+    "math.AC",
+    "math.AG",
+    "math.AP",
+    "math.AT",
+    "math.CA",
+    "math.CO",
+    "math.CT",
+    "math.CV",
+    "math.DG",
+    "math.DS",
+    "math.FA",
+    "math.GM",
+    "math.GN",
+    "math.GR",
+    "math.GT",
+    "math.HO",
+    "math.IT",
+    "math.KT",
+    "math.LO",
+    "math.MG",
+    "math.MP",
+    "math.NA",
+    "math.NT",
+    "math.OA",
+    "math.OC",
+    "math.PR",
+    "math.QA",
+    "math.RA",
+    "math.RT",
+    "math.SG",
+    "math.SP",
+    "math.ST",
+    "nlin.AO",
+    "nlin.CD",
+    "nlin.CG",
+    "nlin.PS",
+    "nlin.SI",
+    "nucl-ex",
+    "nucl-th",
+    "physics.acc-ph",
+    "physics.ao-ph",
+    "physics.app-ph",
+    "physics.atm-clus",
+    "physics.atom-ph",
+    "physics.bio-ph",
+    "physics.chem-ph",
+    "physics.class-ph",
+    "physics.comp-ph",
+    "physics.data-an",
+    "physics.ed-ph",
+    "physics.flu-dyn",
+    "physics.gen-ph",
+    "physics.geo-ph",
+    "physics.hist-ph",
+    "physics.ins-det",
+    "physics.med-ph",
+    "physics.optics",
+    "physics.plasm-ph",
+    "physics.pop-ph",
+    "physics.soc-ph",
+    "physics.space-ph",
+    "q-bio.BM",
+    "q-bio.CB",
+    "q-bio.GN",
+    "q-bio.MN",
+    "q-bio.NC",
+    "q-bio.OT",
+    "q-bio.PE",
+    "q-bio.QM",
+    "q-bio.SC",
+    "q-bio.TO",
+    "q-fin.CP",
+    "q-fin.EC",
+    "q-fin.GN",
+    "q-fin.MF",
+    "q-fin.PM",
+    "q-fin.PR",
+    "q-fin.RM",
+    "q-fin.ST",
+    "q-fin.TR",
+    "quant-ph",
+    "stat.AP",
+    "stat.CO",
+    "stat.ME",
+    "stat.ML",
+    "stat.OT",
+    "stat.TH",
+];
+
+/// Farthest a category can be from a known one and still get it suggested
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Number of single-character insertions, deletions and substitutions
+/// turning `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Known category closest to `category`, ignoring case, if any is close enough.
+/// Ties go to the category sharing the longest prefix, as typos tend to be at
+/// the end, e.g. "cs.clg" is taken for "cs.CL" rather than "cs.CG".
+pub fn suggest_category(category: &str) -> Option<&'static str> {
+    let lowercase = category.to_lowercase();
+    CATEGORIES
+        .iter()
+        .map(|known| {
+            let known_lowercase = known.to_lowercase();
+            let distance = edit_distance(&lowercase, &known_lowercase);
+            let prefix = lowercase
+                .chars()
+                .zip(known_lowercase.chars())
+                .take_while(|(a, b)| a == b)
+                .count();
+            (distance, std::cmp::Reverse(prefix), *known)
+        })
+        .filter(|(distance, _, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min()
+        .map(|(_, _, known)| known)
+}
+
+/// Check that `category` is in the arXiv taxonomy, or a wildcard over a known
+/// archive such as `cs.*`, returning an error suggesting the closest known
+/// category otherwise
+pub fn validate_category(category: &str) -> Result<(), String> {
+    if CATEGORIES.contains(&category) {
+        return Ok(());
+    }
+    if let Some(archive) = category.strip_suffix(".*")
+        && CATEGORIES.iter().any(|known| {
+            known
+                .split_once('.')
+                .is_some_and(|(prefix, _)| prefix == archive)
+        })
+    {
+        return Ok(());
+    }
+    let mut message = format!("unknown arXiv category '{}'", category);
+    if let Some(suggestion) = suggest_category(category) {
+        message.push_str(&format!(", did you mean {}?", suggestion));
+    }
+    message.push_str(" (use --no-validate-category to search it anyway)");
+    Err(message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("cs.cl", "cs.cl"), 0);
+        assert_eq!(edit_distance("cs.clg", "cs.cl"), 1);
+        assert_eq!(edit_distance("stat.lm", "stat.ml"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_validate_category() {
+        for category in ["cs.CL", "stat.ML", "hep-th", "cond-mat.str-el", "math.*"] {
+            assert!(validate_category(category).is_ok(), "{}", category);
+        }
+        let message = validate_category("cs.clg").unwrap_err();
+        assert_eq!(
+            message,
+            "unknown arXiv category 'cs.clg', did you mean cs.CL? (use --no-validate-category to search it anyway)"
+        );
+        assert!(
+            validate_category("cs.cl")
+                .unwrap_err()
+                .contains("did you mean cs.CL?")
+        );
+        assert!(
+            validate_category("quantph")
+                .unwrap_err()
+                .contains("did you mean quant-ph?")
+        );
+        let message = validate_category("biology").unwrap_err();
+        assert!(!message.contains("did you mean"));
+        assert!(validate_category("bio.*").is_err());
+    }
+
+    #[test]
+    fn test_categories_sorted() {
+        assert!(CATEGORIES.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
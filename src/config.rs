@@ -0,0 +1,344 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use arxiv_cli::download::{BIBTEX_FILE, MIN_MAX_FILENAME_LENGTH};
+use clap::{ArgMatches, parser::ValueSource};
+use serde::Deserialize;
+
+use crate::Args;
+
+const CONFIG_DIRECTORY: &str = "arxiv-cli";
+const CONFIG_FILE: &str = "config.toml";
+
+/// Defaults for the command-line options, read from a TOML file whose keys are
+/// the long names of the flags (e.g. `output-dir = "papers"`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    category: Option<Vec<String>>,
+    exclude_category: Option<Vec<String>>,
+    primary_only: Option<bool>,
+    limit: Option<i32>,
+    pdf: Option<bool>,
+    source: Option<bool>,
+    concurrency: Option<usize>,
+    retries: Option<u32>,
+    request_delay: Option<f64>,
+    proxy: Option<String>,
+    user_agent: Option<String>,
+    verify: Option<bool>,
+    force: Option<bool>,
+    summary: Option<bool>,
+    fulltext: Option<bool>,
+    no_latex: Option<bool>,
+    filename_template: Option<String>,
+    max_filename_length: Option<usize>,
+    no_metadata: Option<bool>,
+    format: Option<String>,
+    include_summary_in_metadata: Option<bool>,
+    append: Option<bool>,
+    csv: Option<bool>,
+    with_abstracts: Option<bool>,
+    bibtex: Option<bool>,
+    sort_by: Option<String>,
+    sort_order: Option<String>,
+    quiet: Option<bool>,
+    output_dir: Option<PathBuf>,
+    pdf_dir: Option<PathBuf>,
+    text_dir: Option<PathBuf>,
+}
+
+/// Location of the config file read when --config is not given, e.g.
+/// ~/.config/arxiv-cli/config.toml on Linux
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIRECTORY).join(CONFIG_FILE))
+}
+
+impl Config {
+    pub fn parse(content: &str, path: &Path) -> anyhow::Result<Self> {
+        toml::from_str(content)
+            .map_err(|e| anyhow::anyhow!("invalid config file {}: {}", path.display(), e))
+    }
+
+    /// Read the config file given with --config, or the default one if it exists
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Option<Self>> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_config_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(None),
+            },
+        };
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("failed to read config file {}: {}", path.display(), e))?;
+        Self::parse(&content, &path).map(Some)
+    }
+
+    /// Use the values of the config file for the options that were not given
+    /// on the command line
+    pub fn apply(self, args: &mut Args, matches: &ArgMatches) -> anyhow::Result<()> {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        // A raw query replaces every other search flag, including the
+        // categories, and ids are looked up whatever their categories
+        let by_category = unset("raw_query")
+            && unset("ids")
+            && unset("id_file")
+            && matches.subcommand().is_none();
+        if let Some(category) = self.category
+            && unset("category")
+            && by_category
+        {
+            args.category = category;
+        }
+        if let Some(primary_only) = self.primary_only
+            && unset("primary_only")
+            && by_category
+        {
+            args.primary_only = primary_only;
+        }
+        if let Some(exclude_category) = self.exclude_category
+            && unset("exclude_category")
+        {
+            args.exclude_category = exclude_category;
+        }
+        // --all and --count-only fetch every result, whatever the limit
+        if let Some(limit) = self.limit
+            && unset("limit")
+            && unset("all")
+            && unset("count_only")
+        {
+            if limit < 1 {
+                anyhow::bail!("invalid config value limit = {}: must be at least 1", limit);
+            }
+            args.limit = limit;
+        }
+        if let Some(concurrency) = self.concurrency
+            && unset("concurrency")
+        {
+            if concurrency == 0 {
+                anyhow::bail!("invalid config value concurrency = 0: must be at least 1");
+            }
+            args.concurrency = concurrency;
+        }
+        if let Some(retries) = self.retries
+            && unset("retries")
+        {
+            args.retries = retries;
+        }
+        if let Some(request_delay) = self.request_delay
+            && unset("request_delay")
+        {
+            args.request_delay = Duration::try_from_secs_f64(request_delay).map_err(|_| {
+                anyhow::anyhow!(
+                    "invalid config value request-delay = {}: must be a non-negative number of seconds",
+                    request_delay
+                )
+            })?;
+        }
+        if let Some(proxy) = self.proxy
+            && unset("proxy")
+        {
+            args.proxy = Some(proxy);
+        }
+        if let Some(user_agent) = self.user_agent
+            && unset("user_agent")
+        {
+            args.user_agent = Some(user_agent);
+        }
+        // --latex on the command line also overrides no-latex = true
+        if let Some(no_latex) = self.no_latex
+            && unset("no_latex")
+            && unset("latex")
+        {
+            args.no_latex = no_latex;
+        }
+        if let Some(filename_template) = self.filename_template
+            && unset("filename_template")
+        {
+            args.filename_template = filename_template.parse()?;
+        }
+        if let Some(max_filename_length) = self.max_filename_length
+            && unset("max_filename_length")
+        {
+            if max_filename_length < MIN_MAX_FILENAME_LENGTH {
+                anyhow::bail!(
+                    "invalid config value max-filename-length = {}: must be at least {}",
+                    max_filename_length,
+                    MIN_MAX_FILENAME_LENGTH
+                );
+            }
+            args.max_filename_length = max_filename_length;
+        }
+        if let Some(format) = self.format
+            && unset("format")
+            && unset("json_array")
+        {
+            args.format = format.parse()?;
+        }
+        if let Some(sort_by) = self.sort_by
+            && unset("sort_by")
+        {
+            args.sort_by = sort_by.parse()?;
+        }
+        if let Some(sort_order) = self.sort_order
+            && unset("sort_order")
+        {
+            args.sort_order = sort_order.parse()?;
+        }
+        if let Some(output_dir) = self.output_dir
+            && unset("output_dir")
+        {
+            args.output_dir = output_dir;
+        }
+        if let Some(bibtex) = self.bibtex
+            && unset("bibtex")
+        {
+            args.bibtex = bibtex.then(|| PathBuf::from(BIBTEX_FILE));
+        }
+        if let Some(pdf_dir) = self.pdf_dir
+            && unset("pdf_dir")
+        {
+            args.pdf_dir = pdf_dir;
+        }
+        if let Some(text_dir) = self.text_dir
+            && unset("text_dir")
+        {
+            args.text_dir = text_dir;
+        }
+        let flags = [
+            (self.pdf, "pdf", &mut args.pdf),
+            (self.source, "source", &mut args.source),
+            (self.verify, "verify", &mut args.verify),
+            (self.force, "force", &mut args.force),
+            (self.summary, "summary", &mut args.summary),
+            (self.fulltext, "fulltext", &mut args.fulltext),
+            (self.no_metadata, "no_metadata", &mut args.no_metadata),
+            (
+                self.include_summary_in_metadata,
+                "include_summary_in_metadata",
+                &mut args.include_summary_in_metadata,
+            ),
+            (self.append, "append", &mut args.append),
+            (self.csv, "csv", &mut args.csv),
+            (
+                self.with_abstracts,
+                "with_abstracts",
+                &mut args.with_abstracts,
+            ),
+            (self.quiet, "quiet", &mut args.quiet),
+        ];
+        for (value, id, flag) in flags {
+            if let Some(value) = value
+                && unset(id)
+            {
+                *flag = value;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn args_with_config(cli: &[&str], config: &str) -> anyhow::Result<Args> {
+        let matches = Args::command()
+            .mut_group("search", |group| group.required(false))
+            .try_get_matches_from(std::iter::once("arxiv-cli").chain(cli.iter().copied()))?;
+        let mut args = Args::from_arg_matches(&matches)?;
+        Config::parse(config, Path::new("config.toml"))?.apply(&mut args, &matches)?;
+        Ok(args)
+    }
+
+    #[test]
+    fn test_config_provides_defaults() {
+        let config = r#"
+            category = ["cs.CL", "cs.LG"]
+            limit = 20
+            output-dir = "papers"
+            pdf = true
+            sort-by = "relevance"
+            filename-template = "{id}_{title}"
+            request-delay = 5.0
+            max-filename-length = 140
+            pdf-dir = "~/papers/raw"
+            proxy = "http://proxy.example.com:8080"
+        "#;
+        let args = args_with_config(&["-q", "llm"], config).expect("Should apply config");
+        assert_eq!(args.category, vec!["cs.CL", "cs.LG"]);
+        assert_eq!(args.limit, 20);
+        assert_eq!(args.output_dir, PathBuf::from("papers"));
+        assert!(args.pdf);
+        assert_eq!(args.sort_by.as_str(), "relevance");
+        assert_eq!(args.filename_template.to_string(), "{id}_{title}");
+        assert_eq!(args.request_delay, Duration::from_secs(5));
+        assert_eq!(args.max_filename_length, 140);
+        assert_eq!(args.pdf_dir, PathBuf::from("~/papers/raw"));
+        assert_eq!(args.text_dir, PathBuf::from("texts"));
+        assert_eq!(args.proxy.as_deref(), Some("http://proxy.example.com:8080"));
+        assert_eq!(args.user_agent, None);
+        assert_eq!(args.query.as_deref(), Some("llm"));
+    }
+
+    #[test]
+    fn test_command_line_overrides_config() {
+        let config = r#"
+            category = ["cs.CL"]
+            limit = 20
+            output-dir = "papers"
+            summary = true
+        "#;
+        let args = args_with_config(&["-c", "cs.AI", "-l", "3", "-o", "elsewhere"], config)
+            .expect("Should apply config");
+        assert_eq!(args.category, vec!["cs.AI"]);
+        assert_eq!(args.limit, 3);
+        assert_eq!(args.output_dir, PathBuf::from("elsewhere"));
+        assert!(args.summary);
+        let args =
+            args_with_config(&["--raw-query", "au:smith"], config).expect("Should apply config");
+        assert!(args.category.is_empty());
+        let args = args_with_config(&["--id", "1706.03762"], config).expect("Should apply config");
+        assert!(args.category.is_empty());
+        let args =
+            args_with_config(&["--id-file", "ids.txt"], config).expect("Should apply config");
+        assert!(args.category.is_empty());
+        let args = args_with_config(
+            &["get", "1706.03762"],
+            "category = [\"cs.CL\"]\nprimary-only = true",
+        )
+        .expect("Should apply config");
+        assert!(args.category.is_empty());
+        assert!(!args.primary_only);
+        // Flags that conflict with a config value on the command line win over it
+        let args =
+            args_with_config(&["-c", "cs.AI", "--all"], config).expect("Should apply config");
+        assert_eq!(args.limit, 5);
+        let args = args_with_config(&["-c", "cs.AI", "--json-array"], "format = \"yaml\"")
+            .expect("Should apply config");
+        assert_eq!(args.format.as_str(), "jsonl");
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(args_with_config(&["-q", "llm"], "limit = \"ten\"").is_err());
+        assert!(args_with_config(&["-q", "llm"], "unknown-flag = true").is_err());
+        let err = args_with_config(&["-q", "llm"], "sort-by = \"newest\"")
+            .expect_err("Should reject unknown sort field");
+        assert!(err.to_string().contains("invalid sort field 'newest'"));
+        assert!(args_with_config(&["-q", "llm"], "concurrency = 0").is_err());
+        assert!(args_with_config(&["-q", "llm"], "limit = -5").is_err());
+        assert!(args_with_config(&["-q", "llm"], "request-delay = -1.0").is_err());
+        assert!(args_with_config(&["-q", "llm"], "max-filename-length = 10").is_err());
+    }
+
+    #[test]
+    fn test_load_missing_config() {
+        let err = Config::load(Some(Path::new("does-not-exist.toml")))
+            .expect_err("Should fail on missing file");
+        assert!(err.to_string().contains("failed to read config file"));
+    }
+}
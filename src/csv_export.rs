@@ -0,0 +1,235 @@
+use std::{io, ops::Range};
+
+use crate::download::{SerDesArxiv, paper_id_without_version};
+
+const CSV_HEADER: [&str; 8] = [
+    "id",
+    "title",
+    "authors",
+    "published",
+    "updated",
+    "primary_category",
+    "categories",
+    "pdf_url",
+];
+const SUMMARY_COLUMN: &str = "abstract";
+/// Separator of the authors and categories within a cell
+const LIST_SEPARATOR: &str = "; ";
+
+/// Names of the columns, followed by the abstract if `include_summary` is set
+pub fn csv_header(include_summary: bool) -> Vec<&'static str> {
+    let mut header = CSV_HEADER.to_vec();
+    if include_summary {
+        header.push(SUMMARY_COLUMN);
+    }
+    header
+}
+
+/// Cells of the row of `paper`, in the order of [`csv_header`]
+pub fn csv_record(paper: &SerDesArxiv, include_summary: bool) -> Vec<String> {
+    let mut record = vec![
+        paper.id.clone(),
+        paper.title.clone(),
+        paper.authors.join(LIST_SEPARATOR),
+        paper.published.clone(),
+        paper.updated.clone(),
+        paper.primary_category.clone(),
+        paper.categories.join(LIST_SEPARATOR),
+        paper.pdf_url.clone(),
+    ];
+    if include_summary {
+        record.push(paper.summary.clone());
+    }
+    record
+}
+
+/// Write one CSV row per paper, preceded by a header row
+pub fn write_csv<W: io::Write>(
+    writer: W,
+    papers: &[SerDesArxiv],
+    include_summary: bool,
+) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(csv_header(include_summary))?;
+    for paper in papers {
+        writer.write_record(csv_record(paper, include_summary))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Row of a metadata file: its byte range in the file, and the id, without
+/// version, and update time of its paper, `None` for a header or a row that
+/// cannot be parsed
+pub type MetadataEntry = (Range<usize>, Option<(String, String)>);
+
+/// Rows of a CSV metadata file, the header included, in the order of the file
+pub fn csv_entries(content: &str) -> Vec<MetadataEntry> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(false)
+        .from_reader(content.as_bytes());
+    let mut record = csv::StringRecord::new();
+    let mut updated_column = CSV_HEADER.iter().position(|column| *column == "updated");
+    let mut starts_and_keys: Vec<(usize, Option<(String, String)>)> = vec![];
+    loop {
+        let start = reader.position().byte() as usize;
+        let key = match reader.read_record(&mut record) {
+            Ok(false) => break,
+            // The header row, which tells where the update time is
+            Ok(true) if start == 0 => {
+                updated_column = record.iter().position(|column| column == "updated");
+                None
+            }
+            Ok(true) => record
+                .get(0)
+                .filter(|id| !id.is_empty())
+                .zip(updated_column.and_then(|column| record.get(column)))
+                .map(|(id, updated)| {
+                    (
+                        paper_id_without_version(id).to_string(),
+                        updated.to_string(),
+                    )
+                }),
+            Err(_) => None,
+        };
+        if reader.position().byte() as usize == start {
+            break;
+        }
+        starts_and_keys.push((start, key));
+    }
+    let ends: Vec<usize> = starts_and_keys
+        .iter()
+        .skip(1)
+        .map(|(start, _)| *start)
+        .chain([content.len()])
+        .collect();
+    starts_and_keys
+        .into_iter()
+        .zip(ends)
+        .map(|((start, key), end)| (start..end, key))
+        .collect()
+}
+
+/// Parse a CSV metadata file back, returning the number of papers it lists,
+/// or the line number and error of each row that does not parse
+pub fn validate_csv(content: &str) -> Result<usize, Vec<String>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let mut errors: Vec<String> = vec![];
+    match reader.headers() {
+        Ok(header) if header.get(0) == Some("id") => {}
+        Ok(_) => errors.push("line 1: missing id column".to_string()),
+        Err(e) => errors.push(format!("line 1: {}", e)),
+    }
+    let mut count = 0;
+    for record in reader.records() {
+        match record {
+            Ok(record) if record.get(0).is_some_and(|id| !id.is_empty()) => count += 1,
+            Ok(record) => errors.push(format!(
+                "line {}: missing id",
+                record.position().map_or(0, |position| position.line())
+            )),
+            Err(e) => errors.push(format!(
+                "line {}: {}",
+                e.position().map_or(0, |position| position.line()),
+                e
+            )),
+        }
+    }
+    if errors.is_empty() {
+        Ok(count)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn paper() -> SerDesArxiv {
+        SerDesArxiv {
+            id: "http://arxiv.org/abs/2401.00001v1".to_string(),
+            updated: "2024-01-02T00:00:00Z".to_string(),
+            published: "2024-01-01T00:00:00Z".to_string(),
+            title: "Graphs, \"Retrieval\", and Generation".to_string(),
+            summary: "First line, with a comma.\nSecond \"quoted\" line.".to_string(),
+            authors: vec!["Ada Lovelace".to_string(), "Alan Turing".to_string()],
+            primary_category: "cs.CL".to_string(),
+            categories: vec!["cs.CL".to_string(), "cs.AI".to_string()],
+            pdf_url: "https://arxiv.org/pdf/2401.00001v1".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut buffer = vec![];
+        write_csv(&mut buffer, &[paper()], false).expect("Should write CSV");
+        let mut reader = csv::Reader::from_reader(buffer.as_slice());
+        let header = reader.headers().expect("Should read header").clone();
+        assert_eq!(header.iter().collect::<Vec<_>>(), CSV_HEADER.to_vec());
+        let rows: Vec<csv::StringRecord> = reader
+            .records()
+            .collect::<Result<_, _>>()
+            .expect("Should read rows");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(&rows[0][1], "Graphs, \"Retrieval\", and Generation");
+        assert_eq!(&rows[0][2], "Ada Lovelace; Alan Turing");
+        assert_eq!(&rows[0][6], "cs.CL; cs.AI");
+        assert_eq!(&rows[0][7], "https://arxiv.org/pdf/2401.00001v1");
+    }
+
+    #[test]
+    fn test_write_csv_round_trip() {
+        let mut buffer = vec![];
+        write_csv(&mut buffer, &[paper(), paper()], true).expect("Should write CSV");
+        let mut reader = csv::Reader::from_reader(buffer.as_slice());
+        assert_eq!(
+            reader.headers().expect("Should read header"),
+            csv_header(true)
+        );
+        let rows: Vec<csv::StringRecord> = reader
+            .records()
+            .collect::<Result<_, _>>()
+            .expect("Should read rows");
+        assert_eq!(rows.len(), 2);
+        for row in rows {
+            assert_eq!(row, csv_record(&paper(), true));
+            assert_eq!(
+                &row[8],
+                "First line, with a comma.\nSecond \"quoted\" line."
+            );
+        }
+        let content = String::from_utf8(buffer).expect("Should be UTF-8");
+        assert_eq!(validate_csv(&content), Ok(2));
+        let entries = csv_entries(&content);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].1, None);
+        // Each range covers a whole row, the line break within the abstract included
+        assert_eq!(
+            &content[entries[0].0.clone()],
+            "id,title,authors,published,updated,primary_category,categories,pdf_url,abstract\n"
+        );
+        assert_eq!(entries[1].0.end, entries[2].0.start);
+        assert_eq!(entries[2].0.end, content.len());
+        assert_eq!(
+            entries[1].1,
+            Some(("2401.00001".to_string(), "2024-01-02T00:00:00Z".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_validate_csv() {
+        let mut buffer = vec![];
+        write_csv(&mut buffer, &[], false).expect("Should write CSV");
+        let content = String::from_utf8(buffer).expect("Should be UTF-8");
+        assert_eq!(content.lines().count(), 1);
+        assert_eq!(validate_csv(&content), Ok(0));
+        let content = format!("{}2401.00002,Too short\n,{}\n", content, ",".repeat(6));
+        let errors = validate_csv(&content).expect_err("Should reject rows");
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("line 2: "));
+        assert_eq!(errors[1], "line 3: missing id");
+    }
+}
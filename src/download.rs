@@ -1,19 +1,436 @@
-use std::{fs, io::Write};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    fs::{self, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
 
-use arxiv::{Arxiv, ArxivQueryBuilder};
+use crate::bibtex::{
+    BibtexEntries, bibtex_entries, bibtex_entry, cite_key, cite_keys, normalize_whitespace,
+};
+use crate::csv_export::{
+    MetadataEntry, csv_entries, csv_header, csv_record, validate_csv, write_csv,
+};
+use crate::error::ArxivCliError;
+use crate::feed::fetch_feed;
+use crate::filename::FilenameTemplate;
+use crate::filter::PaperFilters;
+use crate::html::render_html;
+use crate::http::client;
+use crate::latex::latex_to_unicode;
+use crate::license::fetch_license;
+use crate::manifest::{Artifact, MANIFEST_FILE, Manifest};
+use crate::markdown::render_markdown;
+use crate::storage::PaperStore;
+use crate::table::{DEFAULT_TABLE_WIDTH, render_table, terminal_width};
+use arxiv::{ArxivQuery, ArxivQueryBuilder};
+use futures::{StreamExt, stream};
+use html2text::from_read;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, info, warn};
+use rand::{SeedableRng, rngs::StdRng, seq::index};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 const JSON_FILE: &str = "metadata.jsonl";
-const PDF_DIRECTORY: &str = "pdfs/";
-const TEXT_DIRECTORY: &str = "texts/";
+const JSON_ARRAY_FILE: &str = "metadata.json";
+pub const BIBTEX_FILE: &str = "references.bib";
+const CSV_FILE: &str = "metadata.csv";
+const YAML_FILE: &str = "metadata.yaml";
+const ABSTRACTS_FILE: &str = "abstracts.jsonl";
+/// Checksums of the PDFs, in their directory
+pub const CHECKSUMS_FILE: &str = "SHA256SUMS";
+const RUN_MANIFEST_FILE: &str = "run.json";
+/// Suffix of the metadata written after each page in --all mode, next to the
+/// metadata file, which only gets the papers once they are filtered
+const CHECKPOINT_SUFFIX: &str = ".partial";
+pub const PDF_DIRECTORY: &str = "pdfs";
+pub const TEXT_DIRECTORY: &str = "texts";
+pub const SOURCE_DIRECTORY: &str = "sources";
+/// Extensions of the e-prints served by arXiv, the longest first so that
+/// .tar.gz is matched before .gz
+const SOURCE_EXTENSIONS: [&str; 5] = [".tar.gz", ".gz", ".tar", ".pdf", ".tex"];
+const FULLTEXT_SUFFIX: &str = ".fulltext";
+const LONGEST_EXTENSION: &str = ".fulltext.txt";
+const TEXT_WIDTH: usize = 80;
+const PROGRESS_TEMPLATE: &str = "{prefix:>12} [{bar:30}] {pos}/{len} {wide_msg}";
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const ID_BATCH_SIZE: usize = 100;
+const PAGE_SIZE: i32 = 100;
+/// How many more papers than requested are fetched when client-side filters are set
+const OVERFETCH_FACTOR: i32 = 2;
+pub const DEFAULT_CONCURRENCY: usize = 4;
+pub const DEFAULT_RETRIES: u32 = 3;
+pub const DEFAULT_MAX_TOTAL: i32 = 10_000;
+pub const DEFAULT_MAX_FILENAME_LENGTH: usize = 200;
+/// Smallest --max-filename-length leaving room for a name besides the extension
+pub const MIN_MAX_FILENAME_LENGTH: usize = 32;
+/// Minimum delay between two requests to the arXiv API, as asked by its terms of use
+pub const DEFAULT_REQUEST_DELAY: Duration = Duration::from_secs(3);
+/// Smallest size of a PDF accepted by --verify; even a one-page PDF is larger
+pub const MIN_PDF_SIZE: usize = 1024;
 
-#[derive(Serialize, Deserialize, Clone)]
+/// Time of the latest request to the arXiv API made by this process
+static LAST_API_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Field used by arXiv to sort the results of a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortBy {
+    Relevance,
+    LastUpdatedDate,
+    #[default]
+    SubmittedDate,
+}
+
+impl SortBy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortBy::Relevance => "relevance",
+            SortBy::LastUpdatedDate => "lastUpdatedDate",
+            SortBy::SubmittedDate => "submittedDate",
+        }
+    }
+}
+
+impl fmt::Display for SortBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SortBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relevance" => Ok(SortBy::Relevance),
+            "lastUpdatedDate" => Ok(SortBy::LastUpdatedDate),
+            "submittedDate" => Ok(SortBy::SubmittedDate),
+            _ => Err(anyhow::anyhow!(
+                "invalid sort field '{}': expected one of relevance, lastUpdatedDate, submittedDate",
+                s
+            )),
+        }
+    }
+}
+
+/// Direction in which arXiv sorts the results of a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    Ascending,
+    #[default]
+    Descending,
+}
+
+impl SortOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Ascending => "ascending",
+            SortOrder::Descending => "descending",
+        }
+    }
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascending" => Ok(SortOrder::Ascending),
+            "descending" => Ok(SortOrder::Descending),
+            _ => Err(anyhow::anyhow!(
+                "invalid sort order '{}': expected one of ascending, descending",
+                s
+            )),
+        }
+    }
+}
+
+/// Format in which the metadata of the papers is saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetadataFormat {
+    /// One JSON object per line, in metadata.jsonl
+    #[default]
+    Jsonl,
+    /// A single JSON array, in metadata.json
+    Json,
+    /// One row per paper after a header row, in metadata.csv
+    Csv,
+    /// A YAML sequence of papers, in metadata.yaml
+    Yaml,
+}
+
+impl MetadataFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetadataFormat::Jsonl => "jsonl",
+            MetadataFormat::Json => "json",
+            MetadataFormat::Csv => "csv",
+            MetadataFormat::Yaml => "yaml",
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            MetadataFormat::Jsonl => JSON_FILE,
+            MetadataFormat::Json => JSON_ARRAY_FILE,
+            MetadataFormat::Csv => CSV_FILE,
+            MetadataFormat::Yaml => YAML_FILE,
+        }
+    }
+
+    /// Name of the metadata file of a run started at `time`, e.g.
+    /// metadata-2024-06-01T12-30-00.jsonl for "2024-06-01T12:30:00", with
+    /// every character of `time` that is not safe in file names replaced
+    pub fn timestamped_file_name(&self, time: &str) -> String {
+        let (stem, extension) = self
+            .file_name()
+            .split_once('.')
+            .unwrap_or((self.file_name(), self.as_str()));
+        let time: String = time
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        format!("{}-{}.{}", stem, time, extension)
+    }
+}
+
+impl fmt::Display for MetadataFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MetadataFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jsonl" => Ok(MetadataFormat::Jsonl),
+            "json" => Ok(MetadataFormat::Json),
+            "csv" => Ok(MetadataFormat::Csv),
+            "yaml" | "yml" => Ok(MetadataFormat::Yaml),
+            _ => Err(anyhow::anyhow!(
+                "invalid metadata format '{}': expected one of jsonl, json, csv, yaml",
+                s
+            )),
+        }
+    }
+}
+
+/// arXiv ids read from a file, along with the lines that could not be parsed
+#[derive(Debug, Default)]
+pub struct IdFile {
+    pub ids: Vec<String>,
+    /// Line number and content of each malformed line
+    pub malformed: Vec<(usize, String)>,
+}
+
+/// Options controlling how papers are queried and saved
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    pub sort_by: SortBy,
+    pub sort_order: SortOrder,
+    /// Whether to save the metadata of the papers
+    pub save_metadata: bool,
+    /// Whether to download the PDF of the papers
+    pub save_pdfs: bool,
+    /// Whether to save the summary of the papers as txt files
+    pub save_summaries: bool,
+    /// Offset of the first result to fetch, used to page through results
+    pub start: i32,
+    /// Specific arXiv ids to fetch, without version suffix
+    pub id_list: Vec<String>,
+    /// Page through every result of the query, saving the metadata after each page
+    pub all: bool,
+    /// Client-side filters applied before anything is saved
+    pub filters: PaperFilters,
+    /// Number of papers picked at random among the fetched ones, after filtering
+    pub sample: Option<usize>,
+    /// Seed making the random sample reproducible
+    pub seed: Option<u64>,
+    /// Whether to convert the LaTeX in titles to plain Unicode text before
+    /// they are filtered, used in file names and saved
+    pub normalize_latex: bool,
+    /// Template for the names of the PDF and text files
+    pub filename_template: FilenameTemplate,
+    /// Maximum number of characters of the names of the PDF and text files,
+    /// extension included
+    pub max_filename_length: usize,
+    /// Format of the metadata file
+    pub format: MetadataFormat,
+    /// Path of the metadata file, relative to `output_dir` unless absolute,
+    /// instead of the default name for `format`
+    pub metadata_file: Option<PathBuf>,
+    /// Version of the papers fetched with `id_list` to download instead of the latest one
+    pub paper_version: Option<u32>,
+    /// Whether to read the license of the papers from their abstract pages,
+    /// which is also done when filtering on licenses
+    pub resolve_license: bool,
+    /// Minimum delay between two requests to the arXiv API
+    pub request_delay: Duration,
+    /// Whether to include the summary of the papers in the metadata file
+    pub include_summary: bool,
+    /// Whether to add the papers to the end of an existing JSONL metadata file
+    /// instead of replacing it, skipping the ones already listed there
+    pub append: bool,
+    /// Whether to print the metadata to standard output instead of writing
+    /// the metadata file
+    pub stdout: bool,
+    /// Whether to also print each paper of the JSONL metadata to standard
+    /// output as indented JSON; the file keeps one record per line
+    pub pretty: bool,
+    /// Whether to read the metadata file back once written, checking that
+    /// every record parses
+    pub validate: bool,
+    /// Whether to also save the metadata as CSV in metadata.csv
+    pub csv: bool,
+    /// Whether to also save the id, title and abstract of each paper to
+    /// abstracts.jsonl
+    pub with_abstracts: bool,
+    /// BibTeX file, relative to `output_dir` unless absolute, to which an
+    /// entry is appended for each paper
+    pub bibtex: Option<PathBuf>,
+    /// Markdown reading list, relative to `output_dir` unless absolute,
+    /// linking each paper and its downloaded PDF
+    pub markdown: Option<PathBuf>,
+    /// Self-contained HTML page, relative to `output_dir` unless absolute,
+    /// listing the papers and linking their downloaded PDFs
+    pub html: Option<PathBuf>,
+    /// SQLite database, relative to `output_dir` unless absolute, into which
+    /// the papers are upserted
+    pub sqlite: Option<PathBuf>,
+    /// Whether to fetch the source of the papers into `sources/`
+    pub save_sources: bool,
+    /// Whether to extract the full text of the papers from their HTML version
+    pub fulltext: bool,
+    /// Maximum number of PDFs downloaded at the same time
+    pub concurrency: usize,
+    /// Number of times a failed PDF download is retried
+    pub retries: u32,
+    /// Check that each downloaded PDF is complete, retrying the ones that are not
+    pub verify_pdfs: bool,
+    /// Record the SHA-256 digest of each downloaded PDF in SHA256SUMS in the
+    /// PDF directory
+    pub checksums: bool,
+    /// Re-download PDFs even when a non-empty file already exists
+    pub force: bool,
+    /// Hide the progress bars
+    pub quiet: bool,
+    /// Only print the papers matching the query, without writing anything
+    pub dry_run: bool,
+    /// Let the user pick the papers whose PDFs, summaries, full texts and
+    /// sources are downloaded
+    pub interactive: bool,
+    /// Only print a table of the papers matching the query, without writing
+    /// anything
+    pub table: bool,
+    /// Open the page of the first paper in the default browser
+    pub open: bool,
+    /// Fail when no paper is left to save, instead of only reporting it
+    pub fail_on_empty: bool,
+    /// Base directory under which metadata, PDFs and summaries are written
+    pub output_dir: PathBuf,
+    /// Directory of the PDFs, relative to `output_dir` unless absolute
+    pub pdf_dir: PathBuf,
+    /// Directory of the summaries and full texts, relative to `output_dir`
+    /// unless absolute
+    pub text_dir: PathBuf,
+}
+
+impl DownloadOptions {
+    /// Path of the metadata file written under `output_dir`
+    fn metadata_path(&self, output_dir: &Path) -> PathBuf {
+        match &self.metadata_file {
+            Some(file) => output_dir.join(file),
+            None => output_dir.join(self.format.file_name()),
+        }
+    }
+
+    /// Path of the metadata written after each page in --all mode
+    fn checkpoint_path(&self, output_dir: &Path) -> PathBuf {
+        let mut path = self.metadata_path(output_dir).into_os_string();
+        path.push(CHECKPOINT_SUFFIX);
+        PathBuf::from(path)
+    }
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            sort_by: SortBy::default(),
+            sort_order: SortOrder::default(),
+            save_metadata: true,
+            save_pdfs: false,
+            save_summaries: false,
+            start: 0,
+            id_list: vec![],
+            all: false,
+            filters: PaperFilters::default(),
+            sample: None,
+            seed: None,
+            normalize_latex: false,
+            filename_template: FilenameTemplate::default(),
+            max_filename_length: DEFAULT_MAX_FILENAME_LENGTH,
+            format: MetadataFormat::default(),
+            metadata_file: None,
+            paper_version: None,
+            resolve_license: false,
+            request_delay: DEFAULT_REQUEST_DELAY,
+            include_summary: false,
+            append: false,
+            stdout: false,
+            pretty: false,
+            validate: false,
+            csv: false,
+            with_abstracts: false,
+            bibtex: None,
+            markdown: None,
+            html: None,
+            sqlite: None,
+            save_sources: false,
+            fulltext: false,
+            concurrency: DEFAULT_CONCURRENCY,
+            retries: DEFAULT_RETRIES,
+            verify_pdfs: false,
+            checksums: false,
+            force: false,
+            quiet: false,
+            dry_run: false,
+            interactive: false,
+            table: false,
+            open: false,
+            fail_on_empty: false,
+            output_dir: PathBuf::from("."),
+            pdf_dir: PathBuf::from(PDF_DIRECTORY),
+            text_dir: PathBuf::from(TEXT_DIRECTORY),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct SerDesArxiv {
     pub id: String,
     pub updated: String,
     pub published: String,
     pub title: String,
-    #[serde(skip_serializing)]
+    #[serde(default, skip_serializing)]
     pub summary: String,
     pub authors: Vec<String>,
     pub primary_category: String,
@@ -21,64 +438,451 @@ pub struct SerDesArxiv {
     pub pdf_url: String,
     pub html_url: String,
     pub comment: Option<String>,
+    /// Reference of the published version (e.g. "Nature 123, 456 (2024)"), if any
+    #[serde(default)]
+    pub journal_ref: Option<String>,
+    /// DOI of the published version (e.g. "10.1038/s41586-023-06924-6"), if any
+    #[serde(default)]
+    pub doi: Option<String>,
+    /// URL of the license of the paper (e.g. "http://creativecommons.org/licenses/by/4.0/"), if known
+    #[serde(default)]
+    pub license: Option<String>,
 }
 
 impl SerDesArxiv {
-    fn from_arxiv(arxiv_paper: Arxiv) -> Self {
-        Self {
-            id: arxiv_paper.id,
-            updated: arxiv_paper.updated,
-            published: arxiv_paper.published,
-            title: arxiv_paper.title,
-            summary: arxiv_paper.summary,
-            authors: arxiv_paper.authors,
-            primary_category: arxiv_paper.primary_category,
-            categories: arxiv_paper.categories,
-            pdf_url: arxiv_paper.pdf_url.replace("httpss", "https"),
-            html_url: arxiv_paper.html_url.replace("httpss", "https"),
-            comment: arxiv_paper.comment,
-        }
-    }
-
-    pub async fn fetch_pdf(&self, out_path: &str) -> anyhow::Result<()> {
-        let body = reqwest::get(&self.pdf_url).await?.bytes().await?;
+    /// Point the id and URLs of the paper to the given version instead of the latest one
+    pub fn select_version(&mut self, version: u32) {
+        self.id = with_version(&self.id, version);
+        self.pdf_url = with_version(&self.pdf_url, version);
+        if !self.html_url.is_empty() {
+            self.html_url = with_version(&self.html_url, version);
+        }
+    }
+
+    /// Fetch the PDF and write it to `out_path`, returning the SHA-256 digest
+    /// of the file written. With `verify`, the download is checked to look
+    /// like a complete PDF before writing anything, and fails otherwise.
+    pub async fn fetch_pdf(&self, out_path: &str, verify: bool) -> Result<String, ArxivCliError> {
+        let response = client()
+            .get(&self.pdf_url)
+            .send()
+            .await
+            .map_err(|e| self.stalled(e, "PDF"))?;
+        // Missing versions get a 404 or an HTML page instead of the PDF
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| content_type.starts_with("text/html"));
+        if response.status() == reqwest::StatusCode::NOT_FOUND || is_html {
+            return Err(ArxivCliError::NotFound(
+                PdfNotFound::new(&self.id).to_string(),
+            ));
+        }
+        let body = response
+            .error_for_status()?
+            .bytes()
+            .await
+            .map_err(|e| self.stalled(e, "PDF"))?;
+        if verify {
+            verify_pdf(&body)?;
+        }
         let out_path = if out_path.ends_with(".pdf") {
             out_path.to_string()
         } else {
             format!("{}.pdf", out_path)
         };
-        let mut file = fs::File::create(out_path)?;
-        file.write_all(&body)?;
-        Ok(())
+        let digest = write_hashed(Path::new(&out_path), &body)?;
+        info!("Wrote {}", out_path);
+        Ok(digest)
+    }
+
+    /// Error of a failed download of `what` for the paper, naming the paper
+    /// when the download timed out
+    fn stalled(&self, error: reqwest::Error, what: &str) -> ArxivCliError {
+        if error.is_timeout() {
+            ArxivCliError::Timeout(format!(
+                "download of the {} of {} ({}) stalled and timed out",
+                what, self.title, self.id
+            ))
+        } else {
+            error.into()
+        }
+    }
+
+    /// Fetch the PDF, retrying up to `retries` times with exponential backoff,
+    /// and return the SHA-256 digest of the file written
+    pub async fn fetch_pdf_with_retries(
+        &self,
+        out_path: &str,
+        retries: u32,
+        verify: bool,
+    ) -> Result<String, ArxivCliError> {
+        let mut attempt = 0;
+        loop {
+            match self.fetch_pdf(out_path, verify).await {
+                Ok(digest) => return Ok(digest),
+                Err(e) if attempt < retries && !matches!(e, ArxivCliError::NotFound(_)) => {
+                    attempt += 1;
+                    let delay = backoff_delay(attempt);
+                    warn!(
+                        "Failed to download {} ({}), retry {}/{} in {}s",
+                        self.title,
+                        e,
+                        attempt,
+                        retries,
+                        delay.as_secs()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => {
+                    warn!(
+                        "Giving up on {} after {} retries: {}",
+                        self.pdf_url, retries, e
+                    );
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// URL of the e-print of the paper, i.e. the source files submitted by the
+    /// authors (e.g. https://arxiv.org/e-print/2401.00001v1)
+    pub fn source_url(&self) -> String {
+        let id = self
+            .id
+            .split_once("/abs/")
+            .map_or(self.id.as_str(), |(_, id)| id);
+        format!("https://arxiv.org/e-print/{}", id)
     }
 
-    // TODO: make this function actually usable
-    // pub async fn fetch_text(&self, out_path: &str) -> anyhow::Result<()> {
-    //     let body = reqwest::get(&self.html_url).await?.bytes().await?;
-    //     let html_text = from_read(&body[..], 20)?;
-    //     let out_path = if out_path.ends_with(".txt") {
-    //         out_path.to_string()
-    //     } else {
-    //         format!("{}.txt", out_path)
-    //     };
-    //     fs::write(out_path, &html_text)?;
-    //     Ok(())
-    // }
-
-    pub fn write_summary(&self, out_path: &str) -> anyhow::Result<()> {
+    /// Fetch the source of the paper, writing it to `out_path` with the
+    /// extension matching its content: `.tar.gz` for the usual gzipped
+    /// tarball, `.gz` for a single gzipped file, or `.pdf` for papers
+    /// submitted as a PDF only. Returns the path written.
+    pub async fn fetch_source(&self, out_path: &str) -> Result<PathBuf, ArxivCliError> {
+        let response = client()
+            .get(self.source_url())
+            .send()
+            .await
+            .map_err(|e| self.stalled(e, "source"))?
+            .error_for_status()?;
+        let header = |name: reqwest::header::HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let content_type = header(reqwest::header::CONTENT_TYPE);
+        let disposition = header(reqwest::header::CONTENT_DISPOSITION);
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| self.stalled(e, "source"))?;
+        let file_name = disposition.as_deref().and_then(disposition_file_name);
+        let extension = source_extension(content_type.as_deref(), file_name, &body);
+        let out_path = PathBuf::from(format!("{}{}", out_path, extension));
+        fs::write(&out_path, &body)?;
+        info!("Wrote {}", out_path.display());
+        Ok(out_path)
+    }
+
+    /// Fetch the HTML version of the paper and save it as plain text.
+    ///
+    /// Returns `false` without writing anything when arXiv has no HTML
+    /// rendering for the paper.
+    pub async fn fetch_text(&self, out_path: &str) -> Result<bool, ArxivCliError> {
+        let response = client()
+            .get(&self.html_url)
+            .send()
+            .await
+            .map_err(|e| self.stalled(e, "HTML version"))?;
+        if !response.status().is_success() {
+            info!(
+                "No HTML version at {} ({}), skipping full text",
+                self.html_url,
+                response.status()
+            );
+            return Ok(false);
+        }
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| self.stalled(e, "HTML version"))?;
+        let html_text = html_to_text(&body)?;
+        let out_path = if out_path.ends_with(".txt") {
+            out_path.to_string()
+        } else {
+            format!("{}.txt", out_path)
+        };
+        fs::write(&out_path, &html_text)?;
+        info!("Wrote {}", out_path);
+        Ok(true)
+    }
+
+    /// BibTeX entry of the paper, keyed by its citation key (e.g.
+    /// vaswani2017attention); use `cite_keys` to tell apart papers sharing a key
+    pub fn to_bibtex(&self) -> String {
+        bibtex_entry(self, &cite_key(self))
+    }
+
+    /// Write the summary to a text file, returning false without writing
+    /// anything if the summary is blank
+    pub fn write_summary(&self, out_path: &str) -> Result<bool, ArxivCliError> {
+        if self.summary.trim().is_empty() {
+            warn!(
+                "Empty summary for {} ({}), not writing it",
+                self.title, self.id
+            );
+            return Ok(false);
+        }
         let out_path = if out_path.ends_with(".txt") {
             out_path.to_string()
         } else {
             format!("{}.txt", out_path)
         };
         let summary = self.summary.clone();
-        fs::write(out_path, summary)?;
-        Ok(())
+        fs::write(&out_path, summary)?;
+        info!("Wrote {}", out_path);
+        Ok(true)
+    }
+}
+
+/// Writer computing the SHA-256 digest of the bytes that go through it
+struct HashingWriter<W: Write> {
+    writer: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(writer: W) -> Self {
+        Self {
+            writer,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Flush the writer and return the hex-encoded digest of what was written
+    fn finish(mut self) -> std::io::Result<String> {
+        self.writer.flush()?;
+        Ok(self
+            .hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+}
+
+/// Write `body` to `path`, returning the SHA-256 digest of the bytes written
+fn write_hashed(path: &Path, body: &[u8]) -> std::io::Result<String> {
+    let mut writer = HashingWriter::new(fs::File::create(path)?);
+    writer.write_all(body)?;
+    writer.finish()
+}
+
+/// Add the checksums of the files of `pdf_dir` to its SHA256SUMS, in the
+/// format checked by `sha256sum -c`, replacing the lines of the same files and
+/// keeping the others. Returns the path of SHA256SUMS.
+fn update_checksums(pdf_dir: &Path, checksums: &[(String, String)]) -> anyhow::Result<PathBuf> {
+    let path = pdf_dir.join(CHECKSUMS_FILE);
+    let existing = if path.exists() {
+        fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+    let mut content = String::new();
+    for line in existing.lines() {
+        // "<digest>  <name>", or "<digest> *<name>" in binary mode
+        let name = line
+            .split_once(' ')
+            .map(|(_, name)| name.strip_prefix(['*', ' ']).unwrap_or(name));
+        if name.is_some_and(|name| checksums.iter().any(|(file, _)| file == name)) {
+            continue;
+        }
+        content.push_str(line);
+        content.push('\n');
+    }
+    for (file, digest) in checksums {
+        content.push_str(&format!("{}  {}\n", digest, file));
+    }
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// Replace the version suffix of the arXiv id ending the URL, adding one if
+/// there is none (e.g. http://arxiv.org/pdf/cs/0112017v2 becomes
+/// http://arxiv.org/pdf/cs/0112017v1)
+fn with_version(url: &str, version: u32) -> String {
+    let (base, last) = url.rsplit_once('/').unwrap_or(("", url));
+    let (stem, extension) = match last.strip_suffix(".pdf") {
+        Some(stem) => (stem, ".pdf"),
+        None => (last, ""),
+    };
+    let separator = if base.is_empty() { "" } else { "/" };
+    format!(
+        "{}{}{}v{}{}",
+        base,
+        separator,
+        strip_version(stem),
+        version,
+        extension
+    )
+}
+
+/// Message for a PDF that does not exist, e.g. for a version that was never
+/// published, which is not worth retrying
+struct PdfNotFound {
+    id: String,
+    version: Option<String>,
+}
+
+impl PdfNotFound {
+    fn new(id: &str) -> Self {
+        let id = id.split_once("/abs/").map_or(id, |(_, id)| id);
+        let without_version = strip_version(id);
+        Self {
+            id: without_version.to_string(),
+            version: (without_version.len() < id.len())
+                .then(|| id[without_version.len()..].to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for PdfNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "version {} not found for {}", version, self.id),
+            None => write!(f, "PDF not found for {}", self.id),
+        }
+    }
+}
+
+/// Wait until at least `delay` has passed since the previous request to the
+/// arXiv API, so that paginated runs and batches do not get throttled
+async fn wait_for_api(delay: Duration) {
+    let wait = {
+        let mut last = LAST_API_REQUEST
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        // Reserve the slot before sleeping, so that concurrent callers queue up
+        let next = last.map_or(now, |last| (last + delay).max(now));
+        *last = Some(next);
+        next - now
+    };
+    if !wait.is_zero() {
+        debug!(
+            "Waiting {:.1}s before the next API request",
+            wait.as_secs_f64()
+        );
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Check that a download looks like a complete PDF: the `%PDF` header, at
+/// least `MIN_PDF_SIZE` bytes and the `%%EOF` marker near the end, which is
+/// missing from truncated files
+fn verify_pdf(body: &[u8]) -> Result<(), ArxivCliError> {
+    let invalid = |message: String| Err(ArxivCliError::InvalidPdf(message));
+    if !body.starts_with(b"%PDF") {
+        return invalid("not a PDF file (missing %PDF header)".to_string());
+    }
+    if body.len() < MIN_PDF_SIZE {
+        return invalid(format!("PDF file too small ({} bytes)", body.len()));
+    }
+    let tail = &body[body.len().saturating_sub(1024)..];
+    if !tail.windows(5).any(|window| window == b"%%EOF") {
+        return invalid("truncated PDF file (missing %%EOF marker)".to_string());
+    }
+    Ok(())
+}
+
+/// Delay before the given retry attempt (starting at 1), doubling every time
+fn backoff_delay(attempt: u32) -> Duration {
+    RETRY_BASE_DELAY * 2u32.saturating_pow(attempt.saturating_sub(1))
+}
+
+/// Convert an HTML document to readable plain text
+fn html_to_text(body: &[u8]) -> anyhow::Result<String> {
+    Ok(from_read(body, TEXT_WIDTH)?)
+}
+
+/// Create a progress bar for one phase of the download, hidden in quiet mode
+fn progress_bar(len: usize, prefix: &'static str, quiet: bool) -> anyhow::Result<ProgressBar> {
+    if quiet {
+        return Ok(ProgressBar::hidden());
+    }
+    let bar = ProgressBar::new(len as u64);
+    bar.set_style(ProgressStyle::with_template(PROGRESS_TEMPLATE)?.progress_chars("=> "));
+    bar.set_prefix(prefix);
+    Ok(bar)
+}
+
+/// File name given by a Content-Disposition header, e.g.
+/// `attachment; filename="arXiv-2401.00001v1.tar.gz"`
+fn disposition_file_name(disposition: &str) -> Option<&str> {
+    let (_, name) = disposition.split_once("filename=")?;
+    let name = name.split(';').next()?.trim().trim_matches('"');
+    (!name.is_empty()).then_some(name)
+}
+
+/// Extension of an e-print, told from its first bytes: gzipped content is a
+/// tarball unless the file name or content type say it is a single file
+fn source_extension(
+    content_type: Option<&str>,
+    file_name: Option<&str>,
+    body: &[u8],
+) -> &'static str {
+    if body.starts_with(&[0x1f, 0x8b]) {
+        let single_file = match file_name {
+            Some(name) => name.ends_with(".gz") && !name.ends_with(".tar.gz"),
+            None => content_type.is_some_and(|content_type| {
+                content_type.starts_with("application/x-eprint")
+                    && !content_type.starts_with("application/x-eprint-tar")
+            }),
+        };
+        if single_file { ".gz" } else { ".tar.gz" }
+    } else if body.starts_with(b"%PDF") {
+        ".pdf"
+    } else if body.get(257..262) == Some(b"ustar".as_slice()) {
+        ".tar"
+    } else {
+        ".tex"
     }
 }
 
-/// Sanitize a filename to be Windows-compatible
-fn sanitize_filename(name: &str) -> String {
+/// Existing non-empty source of a paper, whatever its extension
+fn existing_source(stem: &Path) -> Option<PathBuf> {
+    SOURCE_EXTENSIONS
+        .iter()
+        .map(|extension| PathBuf::from(format!("{}{}", stem.display(), extension)))
+        .find(|path| is_non_empty_file(path))
+}
+
+/// Check whether a file exists and has some content
+fn is_non_empty_file(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.len() > 0)
+        .unwrap_or(false)
+}
+
+/// Sanitize a filename to be Windows-compatible, keeping at most `max_length`
+/// characters
+pub fn sanitize_filename(name: &str, max_length: usize) -> String {
     // Replace invalid Windows filename characters with underscores
     let invalid_chars = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
     let mut sanitized = name.to_string();
@@ -87,273 +891,3913 @@ fn sanitize_filename(name: &str) -> String {
     }
     // Trim leading/trailing whitespace and dots
     sanitized = sanitized.trim().trim_end_matches('.').to_string();
-    // Limit filename length to 200 characters to be safe
-    if sanitized.len() > 200 {
-        sanitized.truncate(200);
+    // Limit the filename length, counting characters rather than bytes so
+    // that multi-byte characters are never split
+    if let Some((index, _)) = sanitized.char_indices().nth(max_length) {
+        sanitized.truncate(index);
     }
     sanitized
 }
 
-pub async fn download_arxiv_papers(
-    search_query: String,
-    num_results: i32,
-    save_metadata: bool,
-    save_pdfs: bool,
-    save_summaries: bool,
-) -> anyhow::Result<()> {
-    let query = ArxivQueryBuilder::new()
-        .search_query(&search_query)
-        .start(0)
-        .max_results(num_results)
-        .sort_by("submittedDate")
-        .sort_order("descending")
-        .build();
-    let arxivs = arxiv::fetch_arxivs(query).await?;
-    let mut jsonl_text: String = "".to_string();
-    for a in arxivs {
-        let paper = SerDesArxiv::from_arxiv(a);
-        if save_metadata {
-            let paper_copy = paper.clone();
-            let paper_metadata = serde_json::to_string(&paper_copy)?;
-            jsonl_text += &format!("{}\n", paper_metadata);
-        }
-        if save_pdfs {
-            let pdf_dir_exists = fs::exists(PDF_DIRECTORY)?;
-            if !pdf_dir_exists {
-                fs::create_dir(PDF_DIRECTORY)?;
-            }
-            let sanitized_title = sanitize_filename(&paper.title);
-            let path = format!("{}/{}", PDF_DIRECTORY, sanitized_title);
-            paper.fetch_pdf(&path).await?;
-        }
-        if save_summaries {
-            let txt_dir_exists = fs::exists(TEXT_DIRECTORY)?;
-            if !txt_dir_exists {
-                fs::create_dir(TEXT_DIRECTORY)?;
-            }
-            let sanitized_title = sanitize_filename(&paper.title);
-            let path = format!("{}/{}.txt", TEXT_DIRECTORY, sanitized_title);
-            paper.write_summary(&path)?;
-        }
-    }
-    if !jsonl_text.is_empty() {
-        fs::write(JSON_FILE, &jsonl_text)?;
+/// Paper owning each file name listed in the manifest, by lowercased file
+/// name without extension, so that the files written by earlier runs are not
+/// taken for the ones of another paper
+fn manifest_file_owners(manifest: &Manifest) -> HashMap<String, String> {
+    let mut owners = HashMap::new();
+    for (id, entry) in manifest.iter() {
+        for file in entry.files() {
+            let file_name = file.path.rsplit('/').next().unwrap_or(&file.path);
+            let stem = [LONGEST_EXTENSION, ".txt"]
+                .iter()
+                .chain(&SOURCE_EXTENSIONS)
+                .find_map(|extension| file_name.strip_suffix(extension))
+                .unwrap_or(file_name);
+            owners.insert(stem.to_lowercase(), id.to_string());
+        }
     }
-    Ok(())
+    owners
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use serial_test::serial;
-    use std::path::Path;
+/// File name, without extension, of each paper: papers whose names collide,
+/// e.g. because of identical or truncated titles, get a `_1`, `_2`, ... suffix
+/// so that they do not overwrite each other. Names are compared ignoring case,
+/// as some file systems do, and the names that `owners` lists for another
+/// arXiv id are skipped as well, as their files were written by earlier runs.
+///
+/// Names leave room for the longest extension, `.fulltext.txt`, whatever is
+/// saved, so that a paper keeps the same name across runs and `max_length`
+/// bounds every file written.
+fn unique_file_names(
+    papers: &[SerDesArxiv],
+    template: &FilenameTemplate,
+    max_length: usize,
+    owners: &HashMap<String, String>,
+) -> Vec<String> {
+    let max_length = max_length.saturating_sub(LONGEST_EXTENSION.chars().count());
+    let mut taken = HashSet::new();
+    papers
+        .iter()
+        .map(|paper| {
+            let id = paper_id_without_version(&paper.id);
+            let base = template.render(paper, max_length);
+            let mut name = base.clone();
+            let mut counter = 0;
+            while owners
+                .get(&name.to_lowercase())
+                .is_some_and(|owner| owner != id)
+                || !taken.insert(name.to_lowercase())
+            {
+                counter += 1;
+                let suffix = format!("_{}", counter);
+                let base: String = base
+                    .chars()
+                    .take(max_length.saturating_sub(suffix.len()))
+                    .collect();
+                name = format!("{}{}", base, suffix);
+            }
+            name
+        })
+        .collect()
+}
 
-    #[tokio::test]
-    #[serial]
-    async fn integration_test_defaults() {
-        if Path::new(PDF_DIRECTORY).exists() {
-            fs::remove_dir_all(PDF_DIRECTORY).expect("Should be able to remove PDF directory");
+/// Check whether `id` is a new-style arXiv identifier (e.g. 2401.12345)
+fn is_new_style_id(id: &str) -> bool {
+    match id.split_once('.') {
+        Some((yymm, number)) => {
+            yymm.len() == 4
+                && (4..=5).contains(&number.len())
+                && yymm.chars().all(|c| c.is_ascii_digit())
+                && number.chars().all(|c| c.is_ascii_digit())
         }
-        if Path::new(TEXT_DIRECTORY).exists() {
-            fs::remove_dir_all(TEXT_DIRECTORY).expect("Should be able to remove text directory");
-        }
-        if Path::new(JSON_FILE).exists() {
-            fs::remove_file(JSON_FILE).expect("Should be able to remove metadata.jsonl file");
+        None => false,
+    }
+}
+
+/// Check whether `id` is an old-style arXiv identifier (e.g. cs/0112017, math.GT/0309136)
+fn is_old_style_id(id: &str) -> bool {
+    match id.split_once('/') {
+        Some((archive, number)) => {
+            let (archive, subject_class) = match archive.split_once('.') {
+                Some((archive, subject_class)) => (archive, Some(subject_class)),
+                None => (archive, None),
+            };
+            !archive.is_empty()
+                && archive.chars().all(|c| c.is_ascii_lowercase() || c == '-')
+                && subject_class.is_none_or(|class| {
+                    class.len() == 2 && class.chars().all(|c| c.is_ascii_uppercase())
+                })
+                && number.len() == 7
+                && number.chars().all(|c| c.is_ascii_digit())
         }
-        let result = download_arxiv_papers("cat:cs.CL".to_string(), 5, true, false, false).await;
-        match result {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("An error occurred: {}", e.to_string());
-                assert!(false)
-            }
+        None => false,
+    }
+}
+
+/// Strip the version suffix (e.g. `v2`) from an arXiv identifier
+fn strip_version(id: &str) -> &str {
+    match id.rfind('v') {
+        Some(index)
+            if index + 1 < id.len() && id[index + 1..].chars().all(|c| c.is_ascii_digit()) =>
+        {
+            &id[..index]
         }
-        let file_exists = fs::exists(JSON_FILE)
-            .expect("Should be able to check the existance of the metadata.jsonl file");
-        assert!(file_exists);
-        let content =
-            fs::read_to_string(JSON_FILE).expect("Should be able to read metadata.jsonl file");
-        assert!(content.len() > 0);
+        _ => id,
     }
+}
 
-    #[tokio::test]
-    #[serial]
-    async fn integration_test_pdfs() {
-        if Path::new(PDF_DIRECTORY).exists() {
-            fs::remove_dir_all(PDF_DIRECTORY).expect("Should be able to remove PDF directory");
+/// Validate an arXiv identifier and normalize it by removing any `arXiv:`
+/// prefix and version suffix
+pub fn parse_arxiv_id(id: &str) -> Result<String, ArxivCliError> {
+    let trimmed = id.trim();
+    let trimmed = trimmed
+        .strip_prefix("arXiv:")
+        .or_else(|| trimmed.strip_prefix("arxiv:"))
+        .unwrap_or(trimmed);
+    let normalized = strip_version(trimmed);
+    if is_new_style_id(normalized) || is_old_style_id(normalized) {
+        Ok(normalized.to_string())
+    } else {
+        Err(ArxivCliError::Parse(format!(
+            "invalid arXiv id '{}': expected e.g. 2401.12345, 2401.12345v2 or cs/0112017",
+            id
+        )))
+    }
+}
+
+/// Extract the bare arXiv id, without version, from the id of a feed entry
+/// (e.g. http://arxiv.org/abs/2401.12345v1)
+pub fn paper_id_without_version(id: &str) -> &str {
+    let id = id.split_once("/abs/").map_or(id, |(_, id)| id);
+    strip_version(id)
+}
+
+/// Parse a file listing one arXiv id per line, skipping blank lines and `#`
+/// comments, and deduplicating the ids
+pub fn read_id_file(path: &Path) -> Result<IdFile, ArxivCliError> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("failed to read id file {}: {}", path.display(), e),
+        )
+    })?;
+    Ok(parse_id_file(&content))
+}
+
+fn parse_id_file(content: &str) -> IdFile {
+    let mut id_file = IdFile::default();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
         }
-        if Path::new(TEXT_DIRECTORY).exists() {
-            fs::remove_dir_all(TEXT_DIRECTORY).expect("Should be able to remove text directory");
+        match parse_arxiv_id(line) {
+            Ok(id) => id_file.ids.push(id),
+            Err(_) => id_file.malformed.push((index + 1, line.to_string())),
         }
-        if Path::new(JSON_FILE).exists() {
-            fs::remove_file(JSON_FILE).expect("Should be able to remove metadata.jsonl file");
+    }
+    dedup_ids(&mut id_file.ids);
+    id_file
+}
+
+/// Read a file listing one search query per line, skipping blank lines and
+/// lines starting with `#`
+pub fn read_query_file(path: &Path) -> Result<Vec<String>, ArxivCliError> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("failed to read query file {}: {}", path.display(), e),
+        )
+    })?;
+    let queries = parse_query_file(&content);
+    if queries.is_empty() {
+        return Err(ArxivCliError::Parse(format!(
+            "no queries in query file {}",
+            path.display()
+        )));
+    }
+    Ok(queries)
+}
+
+fn parse_query_file(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Remove duplicated ids, keeping the first occurrence of each
+pub fn dedup_ids(ids: &mut Vec<String>) {
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(id.clone()));
+}
+
+/// View of a paper written to the metadata file, with the summary only when requested
+#[derive(Serialize)]
+struct MetadataRecord<'a> {
+    #[serde(flatten)]
+    paper: &'a SerDesArxiv,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+}
+
+impl<'a> MetadataRecord<'a> {
+    fn new(paper: &'a SerDesArxiv, include_summary: bool) -> Self {
+        Self {
+            paper,
+            summary: include_summary.then_some(paper.summary.as_str()),
         }
-        let result = download_arxiv_papers("cat:cs.CL".to_string(), 2, false, true, false).await;
-        match result {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("An error occurred: {}", e.to_string());
-                assert!(false)
+    }
+}
+
+/// Indented JSON of each paper, as previewed by --pretty, one record after
+/// the other
+fn pretty_records(papers: &[SerDesArxiv], include_summary: bool) -> anyhow::Result<String> {
+    let mut out = String::new();
+    for paper in papers {
+        out.push_str(&serde_json::to_string_pretty(&MetadataRecord::new(
+            paper,
+            include_summary,
+        ))?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Record of abstracts.jsonl, e.g. to feed the abstracts to an embedding model
+#[derive(Serialize)]
+struct AbstractRecord<'a> {
+    id: &'a str,
+    title: String,
+    #[serde(rename = "abstract")]
+    summary: &'a str,
+}
+
+/// Write the id, title and abstract of each paper, one JSON object per line
+fn write_abstracts<W: Write>(mut writer: W, papers: &[SerDesArxiv]) -> anyhow::Result<()> {
+    for paper in papers {
+        let record = AbstractRecord {
+            id: &paper.id,
+            title: normalize_whitespace(&paper.title),
+            summary: paper.summary.trim(),
+        };
+        serde_json::to_writer(&mut writer, &record)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Metadata file written one paper at a time, so that an interrupted run
+/// leaves the papers processed so far on disk
+struct MetadataWriter<W: Write> {
+    writer: W,
+    format: MetadataFormat,
+    include_summary: bool,
+    /// Content of the file when appending
+    existing: Option<ExistingMetadata>,
+    /// Whether the CSV header row is still to be written
+    needs_header: bool,
+    written: usize,
+}
+
+/// Metadata file being appended to, so that the papers already in it are only
+/// written again in a newer version, which then replaces the old record
+struct ExistingMetadata {
+    path: PathBuf,
+    content: String,
+    entries: Vec<MetadataEntry>,
+    /// Update time of each paper, by id without version, the papers written
+    /// so far included
+    updated: HashMap<String, String>,
+    /// Ids of the papers whose record in `content` has been superseded
+    superseded: HashSet<String>,
+}
+
+impl ExistingMetadata {
+    fn new(path: PathBuf, content: String, format: MetadataFormat) -> Self {
+        let entries = metadata_entries(&content, format);
+        let mut updated = HashMap::new();
+        for (index, (range, key)) in entries.iter().enumerate() {
+            match key {
+                Some((id, time)) => {
+                    updated.insert(id.clone(), time.clone());
+                }
+                // The CSV header is not a record
+                None if format == MetadataFormat::Csv && index == 0 => {}
+                None if content[range.clone()].trim().is_empty() => {}
+                None => warn!(
+                    "Keeping unparseable record at line {} of {} as is",
+                    content[..range.start].lines().count() + 1,
+                    path.display()
+                ),
             }
         }
-        let file_exists = fs::exists(JSON_FILE)
-            .expect("Should be able to check the existance of the metadata.jsonl file");
-        assert!(!file_exists);
-        let dir_exists: bool = fs::exists(PDF_DIRECTORY)
-            .expect("Should be able to check the existance of the PDF directory");
-        assert!(dir_exists);
-        let dir_content =
-            fs::read_dir(PDF_DIRECTORY).expect("Should be able to read the PDF directory");
-        let mut count = 0;
-        for entry in dir_content {
-            let _dir_entry = entry.expect("Should be able to read entry");
-            count += 1;
+        Self {
+            path,
+            content,
+            entries,
+            updated,
+            superseded: HashSet::new(),
         }
-        assert_eq!(count, 2);
     }
 
-    #[tokio::test]
-    #[serial]
-    async fn integration_test_texts() {
-        if Path::new(PDF_DIRECTORY).exists() {
-            fs::remove_dir_all(PDF_DIRECTORY).expect("Should be able to remove PDF directory");
+    /// Whether `paper` is to be written: a new paper, or a newer version of
+    /// one in the file, whose old record is then marked as superseded
+    fn admit(&mut self, paper: &SerDesArxiv) -> bool {
+        let id = paper_id_without_version(&paper.id).to_string();
+        match self.updated.get(&id) {
+            None => {}
+            Some(updated) if paper.updated > *updated => {
+                debug!("Replacing {}: newer than the metadata file", paper.id);
+                self.superseded.insert(id.clone());
+            }
+            Some(_) => {
+                debug!("Skipping {}: already in the metadata file", paper.id);
+                return false;
+            }
         }
-        if Path::new(TEXT_DIRECTORY).exists() {
-            fs::remove_dir_all(TEXT_DIRECTORY).expect("Should be able to remove text directory");
+        self.updated.insert(id, paper.updated.clone());
+        true
+    }
+
+    /// Rewrite the file without the superseded records, keeping everything
+    /// else, the unparseable records and the ones just appended included
+    fn drop_superseded(&self) -> anyhow::Result<()> {
+        if self.superseded.is_empty() {
+            return Ok(());
         }
-        if Path::new(JSON_FILE).exists() {
-            fs::remove_file(JSON_FILE).expect("Should be able to remove metadata.jsonl file");
+        let current = fs::read_to_string(&self.path)?;
+        let appended = current
+            .get(self.content.len()..)
+            .unwrap_or_default()
+            .trim_start_matches('\n');
+        let mut kept = String::with_capacity(current.len());
+        for (range, key) in &self.entries {
+            if key
+                .as_ref()
+                .is_none_or(|(id, _)| !self.superseded.contains(id))
+            {
+                kept.push_str(&self.content[range.clone()]);
+            }
         }
-        let result = download_arxiv_papers("cat:cs.CL".to_string(), 2, false, false, true).await;
-        match result {
-            Ok(_) => {}
+        if !kept.is_empty() && !kept.ends_with('\n') {
+            kept.push('\n');
+        }
+        kept.push_str(appended);
+        // Through a temporary file, so that an interruption cannot lose records
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, kept)?;
+        fs::rename(&temporary, &self.path)?;
+        info!(
+            "Replaced {} outdated record(s) in {}",
+            self.superseded.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+/// Where the metadata goes: the metadata file, or standard output with
+/// `options.stdout`
+enum MetadataTarget {
+    File(PathBuf),
+    Stdout,
+}
+
+impl fmt::Display for MetadataTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataTarget::File(path) => write!(f, "{}", path.display()),
+            MetadataTarget::Stdout => f.write_str("standard output"),
+        }
+    }
+}
+
+impl MetadataWriter<Box<dyn Write>> {
+    /// Create the metadata file under `output_dir`, along with its parent
+    /// directories, replacing any previous one unless `options.append` is set;
+    /// with `options.stdout`, write to standard output instead. When
+    /// appending, the papers already in the file are skipped unless updated
+    /// since.
+    fn create(
+        output_dir: &Path,
+        options: &DownloadOptions,
+    ) -> anyhow::Result<(Self, MetadataTarget)> {
+        if options.stdout {
+            let stdout: Box<dyn Write> = Box::new(BufWriter::new(std::io::stdout()));
+            return Ok((Self::new(stdout, options), MetadataTarget::Stdout));
+        }
+        let path = options.metadata_path(output_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if !options.append {
+            let file: Box<dyn Write> = Box::new(BufWriter::new(fs::File::create(&path)?));
+            return Ok((Self::new(file, options), MetadataTarget::File(path)));
+        }
+        if matches!(options.format, MetadataFormat::Json | MetadataFormat::Yaml) {
+            anyhow::bail!("--append only supports the jsonl and csv metadata formats");
+        }
+        let content = if path.exists() {
+            fs::read_to_string(&path)?
+        } else {
+            String::new()
+        };
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut writer = Self::new(Box::new(BufWriter::new(file)), options);
+        // Otherwise the first new record would be glued to the last one
+        if !content.is_empty() && !content.ends_with('\n') {
+            writeln!(writer.writer)?;
+        }
+        // The header is already there when adding rows to an existing file
+        writer.needs_header &= content.is_empty();
+        writer.existing = Some(ExistingMetadata::new(path.clone(), content, options.format));
+        Ok((writer, MetadataTarget::File(path)))
+    }
+}
+
+impl<W: Write> MetadataWriter<W> {
+    fn new(writer: W, options: &DownloadOptions) -> Self {
+        Self {
+            writer,
+            format: options.format,
+            include_summary: options.include_summary,
+            existing: None,
+            needs_header: options.format == MetadataFormat::Csv,
+            written: 0,
+        }
+    }
+
+    /// Write the metadata of the paper, unless it is already in the file in
+    /// the same version
+    fn write(&mut self, paper: &SerDesArxiv) -> anyhow::Result<()> {
+        if let Some(existing) = &mut self.existing
+            && !existing.admit(paper)
+        {
+            return Ok(());
+        }
+        let record = MetadataRecord::new(paper, self.include_summary);
+        match self.format {
+            MetadataFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(&mut self.writer);
+                if self.needs_header {
+                    writer.write_record(csv_header(self.include_summary))?;
+                    self.needs_header = false;
+                }
+                writer.write_record(csv_record(paper, self.include_summary))?;
+                writer.flush()?;
+            }
+            MetadataFormat::Jsonl => {
+                serde_json::to_writer(&mut self.writer, &record)?;
+                writeln!(self.writer)?;
+            }
+            // Same layout as a pretty-printed array, one indented element at a time
+            MetadataFormat::Json => {
+                let separator = if self.written == 0 { "[\n" } else { ",\n" };
+                let element = serde_json::to_string_pretty(&record)?;
+                write!(
+                    self.writer,
+                    "{}  {}",
+                    separator,
+                    element.replace('\n', "\n  ")
+                )?;
+            }
+            // A sequence of one paper, so that the items add up to a single sequence
+            MetadataFormat::Yaml => {
+                serde_yaml::to_writer(&mut self.writer, &[record])?;
+            }
+        }
+        self.writer.flush()?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Close the JSON array or write the CSV header if needed, returning the
+    /// number of papers written
+    fn finish(mut self) -> anyhow::Result<usize> {
+        if self.format == MetadataFormat::Json {
+            let end = if self.written == 0 { "[]" } else { "\n]" };
+            write!(self.writer, "{}", end)?;
+        }
+        if self.format == MetadataFormat::Yaml && self.written == 0 {
+            writeln!(self.writer, "[]")?;
+        }
+        if self.needs_header {
+            let mut writer = csv::Writer::from_writer(&mut self.writer);
+            writer.write_record(csv_header(self.include_summary))?;
+            writer.flush()?;
+        }
+        self.writer.flush()?;
+        drop(self.writer);
+        if let Some(existing) = self.existing {
+            existing.drop_superseded()?;
+        }
+        Ok(self.written)
+    }
+}
+
+/// Parse the metadata file back, returning the number of papers it lists, or
+/// an error listing every line that does not parse
+fn validate_metadata(path: &Path, format: MetadataFormat) -> anyhow::Result<usize> {
+    let content = fs::read_to_string(path)?;
+    let result = match format {
+        MetadataFormat::Jsonl => {
+            let mut count = 0;
+            let mut errors: Vec<String> = vec![];
+            for (index, line) in content.lines().enumerate() {
+                match serde_json::from_str::<SerDesArxiv>(line) {
+                    Ok(_) => count += 1,
+                    Err(e) => errors.push(format!("line {}: {}", index + 1, e)),
+                }
+            }
+            if errors.is_empty() {
+                Ok(count)
+            } else {
+                Err(errors)
+            }
+        }
+        MetadataFormat::Csv => validate_csv(&content),
+        MetadataFormat::Json => {
+            return serde_json::from_str::<Vec<SerDesArxiv>>(&content)
+                .map(|papers| papers.len())
+                .map_err(|e| anyhow::anyhow!("invalid metadata in {}: {}", path.display(), e));
+        }
+        MetadataFormat::Yaml => {
+            return serde_yaml::from_str::<Vec<SerDesArxiv>>(&content)
+                .map(|papers| papers.len())
+                .map_err(|e| anyhow::anyhow!("invalid metadata in {}: {}", path.display(), e));
+        }
+    };
+    result.map_err(|errors| {
+        anyhow::anyhow!(
+            "invalid metadata in {}:\n  {}",
+            path.display(),
+            errors.join("\n  ")
+        )
+    })
+}
+
+/// Records of a JSONL or CSV metadata file, in the order of the file
+fn metadata_entries(content: &str, format: MetadataFormat) -> Vec<MetadataEntry> {
+    if format == MetadataFormat::Csv {
+        return csv_entries(content);
+    }
+    let mut entries = vec![];
+    let mut start = 0;
+    for line in content.split_inclusive('\n') {
+        let key = serde_json::from_str::<serde_json::Value>(line)
+            .ok()
+            .and_then(|record| {
+                let id = record["id"].as_str()?;
+                let updated = record["updated"].as_str().unwrap_or_default();
+                Some((
+                    paper_id_without_version(id).to_string(),
+                    updated.to_string(),
+                ))
+            });
+        entries.push((start..start + line.len(), key));
+        start += line.len();
+    }
+    entries
+}
+
+/// Write the metadata of the papers fetched so far to `path`, replacing the
+/// previous checkpoint. The papers are not filtered yet, so they never go to
+/// the metadata file itself, which may be appended to.
+fn write_checkpoint(
+    path: &Path,
+    papers: &[SerDesArxiv],
+    options: &DownloadOptions,
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file: Box<dyn Write> = Box::new(BufWriter::new(fs::File::create(path)?));
+    let mut writer = MetadataWriter::new(file, options);
+    for paper in papers {
+        writer.write(paper)?;
+    }
+    let written = writer.finish()?;
+    debug!(
+        "Wrote metadata of {} paper(s) to {}",
+        written,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Line printed for a paper in dry-run mode: id, title and primary category,
+/// separated by tabs
+fn dry_run_line(paper: &SerDesArxiv) -> String {
+    let id = paper
+        .id
+        .split_once("/abs/")
+        .map_or(paper.id.as_str(), |(_, id)| id);
+    format!(
+        "{}\t{}\t{}",
+        id,
+        normalize_whitespace(&paper.title),
+        paper.primary_category
+    )
+}
+
+/// Ids of the papers already fetched during a run, so that each paper is only
+/// processed once however many pages, categories or queries it matched
+#[derive(Debug, Default)]
+struct SeenPapers {
+    ids: HashSet<String>,
+    /// Number of papers dropped because they were already fetched
+    duplicates: usize,
+}
+
+impl SeenPapers {
+    /// Remove the papers that were already seen, keeping the first occurrence
+    /// of each id regardless of its version
+    fn retain_new(&mut self, papers: &mut Vec<SerDesArxiv>) {
+        let before = papers.len();
+        papers.retain(|paper| {
+            self.ids
+                .insert(paper_id_without_version(&paper.id).to_string())
+        });
+        self.duplicates += before - papers.len();
+    }
+}
+
+fn build_query(
+    search_query: &str,
+    id_list: &[String],
+    start: i32,
+    num_results: i32,
+    options: &DownloadOptions,
+) -> ArxivQuery {
+    let num_results = if id_list.is_empty() {
+        num_results
+    } else {
+        id_list.len() as i32
+    };
+    ArxivQueryBuilder::new()
+        .search_query(search_query)
+        .id_list(&id_list.join(","))
+        .start(start)
+        .max_results(num_results)
+        .sort_by(options.sort_by.as_str())
+        .sort_order(options.sort_order.as_str())
+        .build()
+}
+
+/// Run the query page by page until `num_results` distinct papers are fetched or
+/// the results are exhausted, waiting between requests as arXiv asks clients to.
+/// Papers repeated across page boundaries are dropped and do not count.
+/// In `--all` mode, the progress is reported and the metadata fetched so far is
+/// written to the `checkpoint` file after each page, so an interrupted run keeps it
+async fn fetch_pages(
+    search_query: &str,
+    num_results: i32,
+    options: &DownloadOptions,
+    checkpoint: Option<&Path>,
+    seen: &mut SeenPapers,
+) -> anyhow::Result<SearchResults> {
+    let mut papers: Vec<SerDesArxiv> = vec![];
+    let mut total_results = None;
+    let mut start = options.start;
+    let mut page = 0;
+    while (papers.len() as i32) < num_results {
+        let page_size = (num_results - papers.len() as i32).min(PAGE_SIZE);
+        info!(
+            "Fetching results {} to {} of query '{}'",
+            start,
+            start + page_size - 1,
+            search_query
+        );
+        let query = build_query(search_query, &[], start, page_size, options);
+        wait_for_api(options.request_delay).await;
+        let feed = fetch_feed(&query).await?;
+        if page == 0
+            && let Some(total) = feed.total_results
+        {
+            total_results = Some(total);
+            if !options.quiet {
+                eprintln!("{}", total_results_line(total, options.start, num_results));
+            }
+        }
+        let mut new_papers = feed.papers;
+        let fetched = new_papers.len() as i32;
+        for paper in &new_papers {
+            debug!("Fetched {} ({})", paper.title, paper.id);
+        }
+        seen.retain_new(&mut new_papers);
+        papers.extend(new_papers);
+        page += 1;
+        if options.all {
+            if !options.quiet {
+                eprintln!(
+                    "Page {}: fetched {} paper(s), {} in total",
+                    page,
+                    fetched,
+                    papers.len()
+                );
+            }
+            if let Some(checkpoint) = checkpoint {
+                write_checkpoint(checkpoint, &papers, options)?;
+            }
+        }
+        if fetched < page_size {
+            break;
+        }
+        start += fetched;
+    }
+    Ok(SearchResults {
+        papers,
+        total_results,
+    })
+}
+
+/// Line reporting how many papers the query matched on arXiv and how many of
+/// them are fetched, e.g. "Query matched 4300 paper(s), fetching 5"
+fn total_results_line(total: usize, start: i32, num_results: i32) -> String {
+    let fetching = total
+        .saturating_sub(start.max(0) as usize)
+        .min(num_results.max(0) as usize);
+    format!("Query matched {} paper(s), fetching {}", total, fetching)
+}
+
+/// Run the query, fetching the requested ids in batches if any, and report
+/// the papers that were fetched more than once
+async fn fetch_results(
+    search_query: &str,
+    num_results: i32,
+    options: &DownloadOptions,
+    checkpoint: Option<&Path>,
+) -> anyhow::Result<SearchResults> {
+    let mut seen = SeenPapers::default();
+    let mut results = if options.id_list.is_empty() {
+        fetch_pages(search_query, num_results, options, checkpoint, &mut seen).await?
+    } else {
+        let mut papers = fetch_ids(search_query, num_results, options, &mut seen).await?;
+        if let Some(version) = options.paper_version {
+            for paper in &mut papers {
+                paper.select_version(version);
+            }
+        }
+        SearchResults {
+            papers,
+            total_results: None,
+        }
+    };
+    if seen.duplicates > 0 {
+        eprintln!("Skipped {} duplicate paper(s)", seen.duplicates);
+    }
+    if options.normalize_latex {
+        for paper in &mut results.papers {
+            paper.title = latex_to_unicode(&paper.title);
+        }
+    }
+    Ok(results)
+}
+
+/// Fetch the requested ids in batches, reporting the ids that do not exist
+async fn fetch_ids(
+    search_query: &str,
+    num_results: i32,
+    options: &DownloadOptions,
+    seen: &mut SeenPapers,
+) -> anyhow::Result<Vec<SerDesArxiv>> {
+    let mut papers: Vec<SerDesArxiv> = vec![];
+    for batch in options.id_list.chunks(ID_BATCH_SIZE) {
+        info!("Fetching {} arXiv id(s): {}", batch.len(), batch.join(", "));
+        let query = build_query(search_query, batch, 0, num_results, options);
+        wait_for_api(options.request_delay).await;
+        let fetched = fetch_feed(&query).await?.papers;
+        for paper in &fetched {
+            debug!("Fetched {} ({})", paper.title, paper.id);
+        }
+        papers.extend(fetched);
+    }
+    // Error entries returned for unknown ids do not match any requested id
+    papers.retain(|paper| {
+        options
+            .id_list
+            .iter()
+            .any(|id| id == paper_id_without_version(&paper.id))
+    });
+    seen.retain_new(&mut papers);
+    let missing: Vec<&str> = options
+        .id_list
+        .iter()
+        .map(|id| id.as_str())
+        .filter(|id| {
+            !papers
+                .iter()
+                .any(|paper| paper_id_without_version(&paper.id) == *id)
+        })
+        .collect();
+    eprintln!(
+        "Resolved {} of {} arXiv id(s), {} missing",
+        options.id_list.len() - missing.len(),
+        options.id_list.len(),
+        missing.len()
+    );
+    if !missing.is_empty() {
+        eprintln!("Missing arXiv id(s): {}", missing.join(", "));
+    }
+    Ok(papers)
+}
+
+/// Fetch the papers matching `search_query`, or the ids in `options.id_list`,
+/// and apply the client-side filters of `options`, keeping at most
+/// `num_results` papers. Nothing is written to disk: this is the entry point
+/// for using the crate as a library.
+///
+/// Only the query, paging and filter options are used, e.g. `sort_by`,
+/// `sort_order`, `start`, `id_list`, `all` and `filters`.
+pub async fn fetch_papers(
+    search_query: &str,
+    num_results: i32,
+    options: &DownloadOptions,
+) -> Result<Vec<SerDesArxiv>, ArxivCliError> {
+    Ok(search_papers(search_query, num_results, options)
+        .await?
+        .papers)
+}
+
+/// Papers fetched for a query, along with the number of papers it matches
+#[derive(Clone, Default)]
+pub struct SearchResults {
+    pub papers: Vec<SerDesArxiv>,
+    /// Number of papers matching the query on arXiv, however many were
+    /// fetched, as reported by the API; unknown when fetching ids
+    pub total_results: Option<usize>,
+}
+
+/// Like [`fetch_papers`], also returning the number of papers the query
+/// matches on arXiv, so that callers can tell how much `num_results` leaves out
+pub async fn search_papers(
+    search_query: &str,
+    num_results: i32,
+    options: &DownloadOptions,
+) -> Result<SearchResults, ArxivCliError> {
+    Ok(fetch_filtered_papers(search_query, num_results, options, None).await?)
+}
+
+/// Number of papers matching a query, before and after the client-side filters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaperCount {
+    /// Papers matching the query, as reported by the API, or fetched when
+    /// filters are active
+    pub matched: usize,
+    /// Papers left after filtering, if any filter is active
+    pub kept: Option<usize>,
+    /// Whether the query matches more papers than were fetched to be filtered
+    pub capped: bool,
+}
+
+impl PaperCount {
+    /// Final number of papers, after filtering if any filter is active
+    pub fn count(&self) -> usize {
+        self.kept.unwrap_or(self.matched)
+    }
+
+    /// Render the count as a JSON object, e.g. {"count":3} or
+    /// {"count":3,"matched":5} when filters are active, with "capped":true
+    /// when only part of the matching papers were counted
+    pub fn to_json(&self) -> String {
+        let mut json = match self.kept {
+            Some(kept) => serde_json::json!({ "count": kept, "matched": self.matched }),
+            None => serde_json::json!({ "count": self.matched }),
+        };
+        if self.capped {
+            json["capped"] = serde_json::Value::Bool(true);
+        }
+        json.to_string()
+    }
+}
+
+impl std::fmt::Display for PaperCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kept {
+            Some(kept) => write!(f, "{} ({} before filtering)", kept, self.matched),
+            None => write!(f, "{}", self.matched),
+        }
+    }
+}
+
+/// Count the papers matching the query, without writing anything. Without
+/// client-side filters, the count reported by the API is used, so a single
+/// request is made. Otherwise at most `num_results` papers are fetched and
+/// filtered, and the count is marked as capped if the query matches more.
+pub async fn count_papers(
+    search_query: &str,
+    num_results: i32,
+    options: &DownloadOptions,
+) -> Result<PaperCount, ArxivCliError> {
+    let start = options.start.max(0) as usize;
+    if !options.filters.is_active() && options.id_list.is_empty() {
+        let query = build_query(search_query, &[], options.start, 1, options);
+        wait_for_api(options.request_delay).await;
+        if let Some(total) = fetch_feed(&query).await?.total_results {
+            return Ok(PaperCount {
+                matched: total.saturating_sub(start),
+                kept: None,
+                capped: false,
+            });
+        }
+    }
+    let SearchResults {
+        mut papers,
+        total_results,
+    } = fetch_results(search_query, num_results, options, None).await?;
+    let matched = papers.len();
+    let capped =
+        total_results.filter(|total| total.saturating_sub(start) > num_results.max(0) as usize);
+    if let Some(total) = capped {
+        eprintln!(
+            "Counted the first {} of {} matching paper(s), raise --max-total to count them all",
+            num_results, total
+        );
+    }
+    let capped = capped.is_some();
+    if !options.filters.is_active() {
+        return Ok(PaperCount {
+            matched,
+            kept: None,
+            capped,
+        });
+    }
+    filter_papers(&mut papers, options).await;
+    Ok(PaperCount {
+        matched,
+        kept: Some(papers.len()),
+        capped,
+    })
+}
+
+/// Resolve the licenses if they are saved or filtered on, then apply the
+/// client-side filters, returning how many papers each filter dropped
+async fn filter_papers(
+    papers: &mut Vec<SerDesArxiv>,
+    options: &DownloadOptions,
+) -> BTreeMap<&'static str, usize> {
+    if options.resolve_license || !options.filters.licenses.is_empty() {
+        resolve_licenses(papers, options).await;
+    }
+    options.filters.apply(papers)
+}
+
+async fn fetch_filtered_papers(
+    search_query: &str,
+    num_results: i32,
+    options: &DownloadOptions,
+    checkpoint: Option<&Path>,
+) -> anyhow::Result<SearchResults> {
+    // In --all mode num_results is the safety cap, which is never exceeded
+    let fetch_limit = if options.filters.is_active() && !options.all {
+        num_results.saturating_mul(OVERFETCH_FACTOR)
+    } else {
+        num_results
+    };
+    let SearchResults {
+        mut papers,
+        total_results,
+    } = fetch_results(search_query, fetch_limit, options, checkpoint).await?;
+    let dropped = filter_papers(&mut papers, options).await;
+    info!("{} paper(s) left after filtering", papers.len());
+    if options.id_list.is_empty() {
+        papers.truncate(num_results.max(0) as usize);
+    }
+    if !dropped.is_empty() {
+        let reasons: Vec<String> = dropped
+            .iter()
+            .map(|(reason, count)| format!("{} by {}", count, reason))
+            .collect();
+        eprintln!(
+            "Filtered out {} paper(s): {}",
+            dropped.values().sum::<usize>(),
+            reasons.join(", ")
+        );
+    }
+    if let Some(sample) = options.sample {
+        if sample > papers.len() {
+            eprintln!(
+                "Cannot sample {} paper(s) out of {}, keeping all of them",
+                sample,
+                papers.len()
+            );
+        }
+        sample_papers(&mut papers, sample, options.seed);
+    }
+    Ok(SearchResults {
+        papers,
+        total_results,
+    })
+}
+
+/// Fill in the licenses missing from the feed from the abstract pages of the
+/// papers, leaving them unknown when a page cannot be fetched
+async fn resolve_licenses(papers: &mut [SerDesArxiv], options: &DownloadOptions) {
+    let bar = progress_bar(papers.len(), "Licenses", options.quiet)
+        .unwrap_or_else(|_| ProgressBar::hidden());
+    let licenses: Vec<_> = stream::iter(papers.iter().map(|paper| async move {
+        if paper.license.is_some() {
+            return paper.license.clone();
+        }
+        match fetch_license(paper).await {
+            Ok(license) => license,
+            Err(e) => {
+                warn!("Failed to read the license of {}: {}", paper.id, e);
+                None
+            }
+        }
+    }))
+    .buffered(options.concurrency.max(1))
+    .inspect(|_| bar.inc(1))
+    .collect()
+    .await;
+    bar.finish_and_clear();
+    for (paper, license) in papers.iter_mut().zip(licenses) {
+        paper.license = license;
+    }
+}
+
+/// Keep `sample` papers picked uniformly at random, in their original order,
+/// or all of them if there are not enough
+fn sample_papers(papers: &mut Vec<SerDesArxiv>, sample: usize, seed: Option<u64>) {
+    if sample >= papers.len() {
+        return;
+    }
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    };
+    let mut picked = index::sample(&mut rng, papers.len(), sample).into_vec();
+    picked.sort_unstable();
+    let mut picked = picked.into_iter().peekable();
+    let mut index = 0;
+    papers.retain(|_| {
+        let keep = picked.next_if_eq(&index).is_some();
+        index += 1;
+        keep
+    });
+}
+
+/// Fetch the papers like [`fetch_papers`] and save their metadata, PDFs,
+/// summaries and full texts under `options.output_dir` as requested,
+/// returning the number of papers the query matches on arXiv if known
+pub async fn download_arxiv_papers(
+    search_query: String,
+    num_results: i32,
+    options: &DownloadOptions,
+) -> Result<Option<usize>, ArxivCliError> {
+    let checkpoint = (options.all
+        && options.save_metadata
+        && !options.stdout
+        && !options.dry_run
+        && !options.table)
+        .then(|| options.checkpoint_path(&options.output_dir));
+    let SearchResults {
+        papers,
+        total_results,
+    } = fetch_filtered_papers(&search_query, num_results, options, checkpoint.as_deref()).await?;
+    // Nothing is saved when the filters leave no paper, the checkpoint goes too
+    if papers.is_empty() {
+        remove_checkpoint(checkpoint.as_deref())?;
+    }
+    check_not_empty(&papers, options)?;
+    if options.dry_run {
+        print_dry_run(&papers);
+    } else if options.table {
+        print_table(&papers);
+    } else {
+        let search_queries = std::slice::from_ref(&search_query);
+        save_papers(&papers, search_queries, options).await?;
+        // The metadata file now has the papers, filtered
+        remove_checkpoint(checkpoint.as_deref())?;
+        if !options.stdout {
+            write_run_manifest(
+                search_queries,
+                num_results,
+                total_results,
+                papers.len(),
+                options,
+            )?;
+        }
+    }
+    if options.open
+        && let Some(paper) = papers.first()
+    {
+        open_in_browser(&paper.html_url)?;
+    }
+    Ok(total_results)
+}
+
+/// Remove the metadata written after each page in --all mode, if any, once
+/// the run no longer needs it
+fn remove_checkpoint(checkpoint: Option<&Path>) -> anyhow::Result<()> {
+    if let Some(checkpoint) = checkpoint
+        && checkpoint.exists()
+    {
+        fs::remove_file(checkpoint)?;
+    }
+    Ok(())
+}
+
+/// What a run searched for and how, written to run.json so that it can be
+/// reproduced or cited later
+#[derive(Debug, Serialize)]
+struct RunManifest<'a> {
+    tool_version: &'static str,
+    timestamp: String,
+    /// Queries as sent to arXiv, once composed from the command line flags
+    search_queries: &'a [String],
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    id_list: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    paper_version: Option<u32>,
+    limit: i32,
+    all: bool,
+    start: i32,
+    sort_by: &'static str,
+    sort_order: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sample: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    /// Number of papers the query matches on arXiv, when known
+    total_results: Option<usize>,
+    /// Number of papers saved
+    papers: usize,
+}
+
+impl<'a> RunManifest<'a> {
+    fn new(
+        search_queries: &'a [String],
+        num_results: i32,
+        total_results: Option<usize>,
+        papers: usize,
+        options: &'a DownloadOptions,
+        timestamp: String,
+    ) -> Self {
+        Self {
+            tool_version: env!("CARGO_PKG_VERSION"),
+            timestamp,
+            search_queries,
+            id_list: &options.id_list,
+            paper_version: options.paper_version,
+            limit: num_results,
+            all: options.all,
+            start: options.start,
+            sort_by: options.sort_by.as_str(),
+            sort_order: options.sort_order.as_str(),
+            sample: options.sample,
+            seed: options.seed,
+            total_results,
+            papers,
+        }
+    }
+}
+
+/// Write the manifest of the run to run.json under `options.output_dir`,
+/// replacing the one of the previous run
+fn write_run_manifest(
+    search_queries: &[String],
+    num_results: i32,
+    total_results: Option<usize>,
+    papers: usize,
+    options: &DownloadOptions,
+) -> anyhow::Result<()> {
+    let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let manifest = RunManifest::new(
+        search_queries,
+        num_results,
+        total_results,
+        papers,
+        options,
+        timestamp,
+    );
+    let path = options.output_dir.join(RUN_MANIFEST_FILE);
+    create_parent_dir(&path)?;
+    fs::write(&path, serde_json::to_string_pretty(&manifest)? + "\n")?;
+    info!("Wrote run manifest to {}", path.display());
+    Ok(())
+}
+
+/// Run each query in turn, keeping at most `num_results` papers per query,
+/// and save the papers they matched together, each paper only once. A failing
+/// query does not stop the others, but makes the whole run fail once the
+/// papers of the other queries are saved.
+pub async fn download_batch(
+    search_queries: &[String],
+    num_results: i32,
+    options: &DownloadOptions,
+) -> Result<(), ArxivCliError> {
+    let mut seen = SeenPapers::default();
+    let mut papers: Vec<SerDesArxiv> = vec![];
+    let mut reports: Vec<String> = vec![];
+    let mut failed = 0;
+    for search_query in search_queries {
+        match fetch_filtered_papers(search_query, num_results, options, None).await {
+            Ok(SearchResults {
+                papers: mut matched,
+                ..
+            }) => {
+                let total = matched.len();
+                seen.retain_new(&mut matched);
+                reports.push(format!(
+                    "{}: {} paper(s), {} new",
+                    search_query,
+                    total,
+                    matched.len()
+                ));
+                papers.extend(matched);
+            }
             Err(e) => {
-                eprintln!("An error occurred: {}", e.to_string());
-                assert!(false)
+                failed += 1;
+                warn!("Query '{}' failed: {}", search_query, e);
+                reports.push(format!("{}: failed ({})", search_query, e));
+            }
+        }
+    }
+    eprintln!(
+        "Ran {} quer{}, {} distinct paper(s):",
+        search_queries.len(),
+        if search_queries.len() == 1 {
+            "y"
+        } else {
+            "ies"
+        },
+        papers.len()
+    );
+    for report in &reports {
+        eprintln!("  - {}", report);
+    }
+    // Failed queries are reported below, whether or not the others matched
+    if failed < search_queries.len() {
+        check_not_empty(&papers, options)?;
+    }
+    if options.dry_run {
+        print_dry_run(&papers);
+    } else if options.table {
+        print_table(&papers);
+    } else {
+        save_papers(&papers, search_queries, options).await?;
+        if !options.stdout {
+            write_run_manifest(search_queries, num_results, None, papers.len(), options)?;
+        }
+    }
+    if options.open
+        && let Some(paper) = papers.first()
+    {
+        open_in_browser(&paper.html_url)?;
+    }
+    if failed > 0 {
+        return Err(ArxivCliError::Other(anyhow::anyhow!(
+            "{} of {} queries failed",
+            failed,
+            search_queries.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Renders the papers as a reading list, given the links to their PDFs, the
+/// queries that matched them and the date
+type ReadingListRenderer =
+    fn(&[SerDesArxiv], &[Option<String>], &[String], chrono::NaiveDate) -> String;
+
+/// Link to `path` from a file in `base`, with forward slashes whatever the
+/// platform; paths outside of `base` are linked as they are
+fn relative_link(path: &Path, base: &Path) -> String {
+    let relative = path.strip_prefix(base).unwrap_or(path);
+    relative
+        .to_string_lossy()
+        .replace(std::path::MAIN_SEPARATOR, "/")
+}
+
+/// Command opening `url` in the default browser of the platform
+fn browser_command(url: &str) -> std::process::Command {
+    let mut command = if cfg!(target_os = "windows") {
+        // The empty argument is the title of the window opened by start
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    command.arg(url);
+    command
+}
+
+/// Open `url` in the default browser, without waiting for it
+fn open_in_browser(url: &str) -> anyhow::Result<()> {
+    eprintln!("Opening {}", url);
+    browser_command(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("failed to open {} in a browser: {}", url, e))?;
+    Ok(())
+}
+
+/// Tell the user when no paper is left to save, which is an error with
+/// `options.fail_on_empty`
+fn check_not_empty(papers: &[SerDesArxiv], options: &DownloadOptions) -> anyhow::Result<()> {
+    if !papers.is_empty() {
+        return Ok(());
+    }
+    if options.fail_on_empty {
+        return Err(ArxivCliError::EmptyQuery.into());
+    }
+    eprintln!("No papers matched your query.");
+    Ok(())
+}
+
+/// Let the user pick, in a list with checkboxes, the papers whose files are
+/// downloaded, returning whether each paper was picked
+fn select_papers(papers: &[SerDesArxiv]) -> anyhow::Result<Vec<bool>> {
+    if !console::Term::stderr().is_term() {
+        anyhow::bail!("--interactive needs a terminal to pick the papers from");
+    }
+    let items: Vec<String> = papers.iter().map(selection_item).collect();
+    let picked = dialoguer::MultiSelect::new()
+        .with_prompt("Papers to download (space to toggle, enter to confirm, esc to skip all)")
+        .items(&items)
+        .max_length(20)
+        .interact_opt()?
+        .unwrap_or_default();
+    let mut selected = vec![false; papers.len()];
+    for index in picked {
+        selected[index] = true;
+    }
+    eprintln!(
+        "Selected {} of {} paper(s)",
+        selected.iter().filter(|s| **s).count(),
+        papers.len()
+    );
+    Ok(selected)
+}
+
+/// Line listing a paper in the --interactive selection: id, date and title
+fn selection_item(paper: &SerDesArxiv) -> String {
+    let id = paper
+        .id
+        .split_once("/abs/")
+        .map_or(paper.id.as_str(), |(_, id)| id);
+    format!(
+        "{}  {}  {}",
+        id,
+        paper.published.get(..10).unwrap_or(&paper.published),
+        normalize_whitespace(&paper.title)
+    )
+}
+
+fn print_table(papers: &[SerDesArxiv]) {
+    let width = terminal_width().unwrap_or(DEFAULT_TABLE_WIDTH);
+    print!("{}", render_table(papers, width));
+}
+
+fn print_dry_run(papers: &[SerDesArxiv]) {
+    for paper in papers {
+        println!("{}", dry_run_line(paper));
+    }
+    eprintln!("{} paper(s) would be downloaded", papers.len());
+}
+
+/// Create the directory `path` is to be written in, if needed
+fn create_parent_dir(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+/// Save the metadata, PDFs, summaries and full texts of the papers under
+/// `options.output_dir` as requested, `queries` being the ones that matched them
+async fn save_papers(
+    papers: &[SerDesArxiv],
+    queries: &[String],
+    options: &DownloadOptions,
+) -> anyhow::Result<()> {
+    // The output directory is only created along with a file to write in it,
+    // so that --stdout leaves nothing on disk
+    let output_dir = options.output_dir.as_path();
+    let pdf_dir = output_dir.join(&options.pdf_dir);
+    let text_dir = output_dir.join(&options.text_dir);
+    let mut skipped_fulltext: Vec<String> = vec![];
+    let mut skipped_summaries: Vec<String> = vec![];
+    // Files written in this run, merged into the manifest at the end
+    let mut manifest = Manifest::default();
+    let now = || chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    // Only the files of the papers picked by the user are downloaded, the
+    // metadata lists them all
+    let downloads_files =
+        options.save_pdfs || options.save_summaries || options.fulltext || options.save_sources;
+    let selected = if options.interactive && downloads_files && !papers.is_empty() {
+        select_papers(papers)?
+    } else {
+        vec![true; papers.len()]
+    };
+    let owners = if downloads_files {
+        manifest_file_owners(&Manifest::load(&output_dir.join(MANIFEST_FILE))?)
+    } else {
+        HashMap::new()
+    };
+    let file_names = unique_file_names(
+        papers,
+        &options.filename_template,
+        options.max_filename_length,
+        &owners,
+    );
+    // JSON arrays are always indented, JSONL files must keep one record per line
+    if options.save_metadata && options.pretty && options.format == MetadataFormat::Jsonl {
+        print!("{}", pretty_records(papers, options.include_summary)?);
+    }
+    // Progress bars and logs go to stderr, so that stdout only holds the metadata
+    let mut metadata = if options.save_metadata && (options.stdout || !papers.is_empty()) {
+        Some(MetadataWriter::create(output_dir, options)?)
+    } else {
+        None
+    };
+    let bar = progress_bar(papers.len(), "Processing", options.quiet)?;
+    for ((paper, file_name), selected) in papers.iter().zip(&file_names).zip(&selected) {
+        bar.set_message(paper.title.clone());
+        if let Some((metadata, _)) = &mut metadata {
+            metadata.write(paper)?;
+        }
+        if options.save_summaries && *selected {
+            fs::create_dir_all(&text_dir)?;
+            let path = text_dir.join(format!("{}.txt", file_name));
+            if paper.write_summary(&path.to_string_lossy())? {
+                let link = relative_link(&path, output_dir);
+                manifest.record(paper, Artifact::Summary, &path, link, &now())?;
+            } else {
+                skipped_summaries.push(format!("{} ({})", paper.title, paper.id));
+            }
+        }
+        if options.fulltext && *selected {
+            fs::create_dir_all(&text_dir)?;
+            let path = text_dir.join(format!("{}{}.txt", file_name, FULLTEXT_SUFFIX));
+            if paper.fetch_text(&path.to_string_lossy()).await? {
+                let link = relative_link(&path, output_dir);
+                manifest.record(paper, Artifact::Fulltext, &path, link, &now())?;
+            } else {
+                skipped_fulltext.push(format!("{} ({})", paper.title, paper.id));
+            }
+        }
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    if let Some((metadata, target)) = metadata {
+        let written = metadata.finish()?;
+        info!("Wrote metadata of {} paper(s) to {}", written, target);
+        if let MetadataTarget::File(path) = target
+            && options.validate
+        {
+            let count = validate_metadata(&path, options.format)?;
+            eprintln!(
+                "Validated {} metadata record(s) in {}",
+                count,
+                path.display()
+            );
+        }
+    }
+    if let Some(sqlite) = &options.sqlite
+        && !papers.is_empty()
+    {
+        let path = output_dir.join(sqlite);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let counts = PaperStore::open(&path)?.upsert(papers)?;
+        eprintln!(
+            "Saved {} paper(s) to {}: {} new, {} updated, {} unchanged",
+            papers.len(),
+            path.display(),
+            counts.inserted,
+            counts.updated,
+            counts.unchanged
+        );
+    }
+    let mut failed_pdfs: Vec<String> = vec![];
+    if options.save_pdfs {
+        fs::create_dir_all(&pdf_dir)?;
+        let mut to_download = vec![];
+        for ((paper, file_name), _) in papers
+            .iter()
+            .zip(&file_names)
+            .zip(&selected)
+            .filter(|(_, selected)| **selected)
+        {
+            let path = pdf_dir.join(format!("{}.pdf", file_name));
+            if !options.force && is_non_empty_file(&path) {
+                eprintln!(
+                    "Skipping {} ({}): {} already exists",
+                    paper.title,
+                    paper.id,
+                    path.display()
+                );
+                continue;
+            }
+            to_download.push((paper, path));
+        }
+        let bar = progress_bar(to_download.len(), "Downloading", options.quiet)?;
+        let downloads = to_download.into_iter().map(|(paper, path)| async move {
+            let result = paper
+                .fetch_pdf_with_retries(
+                    &path.to_string_lossy(),
+                    options.retries,
+                    options.verify_pdfs,
+                )
+                .await;
+            (paper, path, result)
+        });
+        let results: Vec<_> = stream::iter(downloads)
+            .buffer_unordered(options.concurrency.max(1))
+            .inspect(|(paper, _, _)| {
+                bar.set_message(paper.title.clone());
+                bar.inc(1);
+            })
+            .collect()
+            .await;
+        bar.finish_and_clear();
+        let mut checksums: Vec<(String, String)> = vec![];
+        for (paper, path, result) in results {
+            match result {
+                Ok(digest) => {
+                    let link = relative_link(&path, output_dir);
+                    manifest.record(paper, Artifact::Pdf, &path, link, &now())?;
+                    if let Some(name) = path.file_name() {
+                        checksums.push((name.to_string_lossy().into_owned(), digest));
+                    }
+                }
+                Err(e) => failed_pdfs.push(format!("{} ({}): {}", paper.title, paper.id, e)),
             }
         }
-        let file_exists = fs::exists(JSON_FILE)
+        if options.checksums && !checksums.is_empty() {
+            checksums.sort();
+            let path = update_checksums(&pdf_dir, &checksums)?;
+            info!(
+                "Wrote the checksums of {} PDF(s) to {}",
+                checksums.len(),
+                path.display()
+            );
+        }
+    }
+    let mut failed_sources: Vec<String> = vec![];
+    if options.save_sources {
+        let source_dir = output_dir.join(SOURCE_DIRECTORY);
+        fs::create_dir_all(&source_dir)?;
+        let mut to_download = vec![];
+        for ((paper, file_name), _) in papers
+            .iter()
+            .zip(&file_names)
+            .zip(&selected)
+            .filter(|(_, selected)| **selected)
+        {
+            let stem = source_dir.join(file_name);
+            if !options.force
+                && let Some(path) = existing_source(&stem)
+            {
+                eprintln!(
+                    "Skipping {} ({}): {} already exists",
+                    paper.title,
+                    paper.id,
+                    path.display()
+                );
+                continue;
+            }
+            to_download.push((paper, stem));
+        }
+        let bar = progress_bar(to_download.len(), "Sources", options.quiet)?;
+        let downloads = to_download.into_iter().map(|(paper, stem)| async move {
+            let result = paper.fetch_source(&stem.to_string_lossy()).await;
+            (paper, result)
+        });
+        let results: Vec<_> = stream::iter(downloads)
+            .buffer_unordered(options.concurrency.max(1))
+            .inspect(|(paper, _)| {
+                bar.set_message(paper.title.clone());
+                bar.inc(1);
+            })
+            .collect()
+            .await;
+        bar.finish_and_clear();
+        for (paper, result) in results {
+            match result {
+                Ok(path) => {
+                    let link = relative_link(&path, output_dir);
+                    manifest.record(paper, Artifact::Source, &path, link, &now())?;
+                }
+                Err(e) => failed_sources.push(format!("{} ({}): {}", paper.title, paper.id, e)),
+            }
+        }
+    }
+    if !manifest.is_empty() {
+        let path = output_dir.join(MANIFEST_FILE);
+        create_parent_dir(&path)?;
+        let count = manifest.save_merged(&path)?;
+        info!(
+            "Wrote {} listing the files of {} paper(s)",
+            path.display(),
+            count
+        );
+    }
+    if !skipped_summaries.is_empty() {
+        eprintln!(
+            "Empty summary, skipped the summary file of {} paper(s):",
+            skipped_summaries.len()
+        );
+        for skipped in &skipped_summaries {
+            eprintln!("  - {}", skipped);
+        }
+    }
+    if !skipped_fulltext.is_empty() {
+        eprintln!(
+            "No HTML version available, skipped full text for {} paper(s):",
+            skipped_fulltext.len()
+        );
+        for skipped in &skipped_fulltext {
+            eprintln!("  - {}", skipped);
+        }
+    }
+    // The metadata file already is a CSV file with --format csv
+    let metadata_is_csv = options.save_metadata && options.format == MetadataFormat::Csv;
+    if options.csv && !metadata_is_csv && !papers.is_empty() {
+        let path = output_dir.join(CSV_FILE);
+        create_parent_dir(&path)?;
+        write_csv(fs::File::create(&path)?, papers, options.include_summary)?;
+        info!("Wrote {}", path.display());
+    }
+    if options.with_abstracts && !papers.is_empty() {
+        let path = output_dir.join(ABSTRACTS_FILE);
+        create_parent_dir(&path)?;
+        write_abstracts(BufWriter::new(fs::File::create(&path)?), papers)?;
+        info!("Wrote {}", path.display());
+    }
+    let reading_lists: [(&Option<PathBuf>, ReadingListRenderer); 2] = [
+        (&options.markdown, render_markdown),
+        (&options.html, render_html),
+    ];
+    for (file, render) in reading_lists {
+        let Some(file) = file.as_ref().filter(|_| !papers.is_empty()) else {
+            continue;
+        };
+        let path = output_dir.join(file);
+        let base = path.parent().unwrap_or(Path::new(""));
+        if !base.as_os_str().is_empty() {
+            fs::create_dir_all(base)?;
+        }
+        // Only the PDFs that are on disk, downloaded now or earlier, are linked
+        let pdf_links: Vec<Option<String>> = file_names
+            .iter()
+            .map(|file_name| {
+                let pdf = pdf_dir.join(format!("{}.pdf", file_name));
+                (options.save_pdfs && is_non_empty_file(&pdf)).then(|| relative_link(&pdf, base))
+            })
+            .collect();
+        let date = chrono::Utc::now().date_naive();
+        fs::write(&path, render(papers, &pdf_links, queries, date))?;
+        info!("Wrote {}", path.display());
+    }
+    if let Some(bibtex) = &options.bibtex
+        && !papers.is_empty()
+    {
+        let path = output_dir.join(bibtex);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Papers already in the file are skipped, and new keys must not
+        // collide with the ones there
+        let existing = if path.exists() {
+            bibtex_entries(&fs::read_to_string(&path)?)
+        } else {
+            BibtexEntries::default()
+        };
+        let new_papers: Vec<&SerDesArxiv> = papers
+            .iter()
+            .filter(|paper| {
+                !existing
+                    .eprints
+                    .contains(paper_id_without_version(&paper.id))
+            })
+            .collect();
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let keys = cite_keys(new_papers.iter().copied(), &existing.keys);
+        for (paper, key) in new_papers.iter().zip(keys) {
+            writeln!(file, "{}", bibtex_entry(paper, &key))?;
+        }
+        info!(
+            "Appended {} BibTeX entries to {}, {} paper(s) already in it",
+            new_papers.len(),
+            path.display(),
+            papers.len() - new_papers.len()
+        );
+    }
+    for failed in &failed_sources {
+        eprintln!("Failed to download source for {}", failed);
+    }
+    if !failed_pdfs.is_empty() {
+        for failed in &failed_pdfs {
+            eprintln!("Failed to download PDF for {}", failed);
+        }
+        anyhow::bail!("{} PDF download(s) failed", failed_pdfs.len());
+    }
+    if !failed_sources.is_empty() {
+        anyhow::bail!("{} source download(s) failed", failed_sources.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::license::LicenseFamily;
+    use serial_test::serial;
+
+    fn fixture(id: &str, title: &str) -> SerDesArxiv {
+        SerDesArxiv {
+            id: format!("http://arxiv.org/abs/{}", id),
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Write the metadata of the papers to the metadata file under `output_dir`,
+    /// as `save_papers` does
+    fn write_metadata(
+        output_dir: &Path,
+        papers: &[SerDesArxiv],
+        options: &DownloadOptions,
+    ) -> anyhow::Result<()> {
+        let (mut writer, _) = MetadataWriter::create(output_dir, options)?;
+        for paper in papers {
+            writer.write(paper)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Output directory named after the test, empty at first, so that tests
+    /// writing files neither run one at a time nor leave files behind
+    fn test_output_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arxiv_cli_{}", name));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).expect("Should be able to remove output directory");
+        }
+        dir
+    }
+
+    /// Lightweight syntactic check of a BibTeX file: every entry is an
+    /// `@type{key,` header followed by fields with balanced braces, and no key
+    /// is used twice. Returns the keys of the entries.
+    fn check_bibtex(content: &str) -> Vec<String> {
+        let mut keys = vec![];
+        for entry in format!("\n{}", content).split("\n@").skip(1) {
+            let (entry_type, rest) = entry.split_once('{').expect("Should have an entry type");
+            assert!(
+                entry_type.chars().all(|c| c.is_ascii_alphabetic()),
+                "Invalid entry type '{}'",
+                entry_type
+            );
+            let (key, _) = rest.split_once(",\n").expect("Should have a citation key");
+            assert!(!key.is_empty() && !key.contains(char::is_whitespace));
+            assert!(!keys.contains(&key.to_string()), "Duplicate key {}", key);
+            keys.push(key.to_string());
+            let mut depth = 1;
+            let mut chars = rest.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                assert!(depth >= 0, "Unbalanced braces in entry {}", key);
+            }
+            assert_eq!(depth, 0, "Unbalanced braces in entry {}", key);
+        }
+        keys
+    }
+
+    #[tokio::test]
+    async fn integration_test_bibtex() {
+        let root = std::env::temp_dir().join("arxiv_cli_integration_test_bibtex");
+        if root.exists() {
+            fs::remove_dir_all(&root).expect("Should be able to remove directory");
+        }
+        let options = DownloadOptions {
+            save_metadata: false,
+            bibtex: Some(PathBuf::from("refs.bib")),
+            output_dir: root.clone(),
+            ..Default::default()
+        };
+        download_arxiv_papers("cat:cs.CL".to_string(), 5, &options)
+            .await
+            .expect("Should download papers");
+        let content =
+            fs::read_to_string(root.join("refs.bib")).expect("Should be able to read refs.bib");
+        assert_eq!(check_bibtex(&content).len(), 5);
+        fs::remove_dir_all(&root).expect("Should be able to remove directory");
+    }
+
+    #[tokio::test]
+    async fn integration_test_defaults() {
+        let output_dir = test_output_dir("integration_test_defaults");
+        let result = download_arxiv_papers(
+            "cat:cs.CL".to_string(),
+            5,
+            &DownloadOptions {
+                output_dir: output_dir.clone(),
+                ..Default::default()
+            },
+        )
+        .await;
+        if let Err(e) = result {
+            panic!("An error occurred: {}", e);
+        }
+        let file_exists = fs::exists(output_dir.join(JSON_FILE))
+            .expect("Should be able to check the existance of the metadata.jsonl file");
+        assert!(file_exists);
+        let content = fs::read_to_string(output_dir.join(JSON_FILE))
+            .expect("Should be able to read metadata.jsonl file");
+        assert!(!content.is_empty());
+        fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+    }
+
+    #[tokio::test]
+    async fn integration_test_pdfs() {
+        let output_dir = test_output_dir("integration_test_pdfs");
+        let result = download_arxiv_papers(
+            "cat:cs.CL".to_string(),
+            2,
+            &DownloadOptions {
+                save_metadata: false,
+                save_pdfs: true,
+                output_dir: output_dir.clone(),
+                ..Default::default()
+            },
+        )
+        .await;
+        if let Err(e) = result {
+            panic!("An error occurred: {}", e);
+        }
+        let file_exists = fs::exists(output_dir.join(JSON_FILE))
+            .expect("Should be able to check the existance of the metadata.jsonl file");
+        assert!(!file_exists);
+        let dir_exists: bool = fs::exists(output_dir.join(PDF_DIRECTORY))
+            .expect("Should be able to check the existance of the PDF directory");
+        assert!(dir_exists);
+        let dir_content = fs::read_dir(output_dir.join(PDF_DIRECTORY))
+            .expect("Should be able to read the PDF directory");
+        let mut count = 0;
+        for entry in dir_content {
+            let _dir_entry = entry.expect("Should be able to read entry");
+            count += 1;
+        }
+        assert_eq!(count, 2);
+        fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+    }
+
+    #[tokio::test]
+    async fn integration_test_texts() {
+        let output_dir = test_output_dir("integration_test_texts");
+        let result = download_arxiv_papers(
+            "cat:cs.CL".to_string(),
+            2,
+            &DownloadOptions {
+                save_metadata: false,
+                save_summaries: true,
+                output_dir: output_dir.clone(),
+                ..Default::default()
+            },
+        )
+        .await;
+        if let Err(e) = result {
+            panic!("An error occurred: {}", e);
+        }
+        let file_exists = fs::exists(output_dir.join(JSON_FILE))
             .expect("Should be able to check the existance of the metadata.jsonl file");
         assert!(!file_exists);
-        let pdf_dir_exists: bool = fs::exists(PDF_DIRECTORY)
+        let pdf_dir_exists: bool = fs::exists(output_dir.join(PDF_DIRECTORY))
             .expect("Should be able to check the existance of the PDF directory");
         assert!(!pdf_dir_exists);
-        let text_dir_exists: bool = fs::exists(TEXT_DIRECTORY)
+        let text_dir_exists: bool = fs::exists(output_dir.join(TEXT_DIRECTORY))
+            .expect("Should be able to check the existance of the text directory");
+        assert!(text_dir_exists);
+        let dir_content = fs::read_dir(output_dir.join(TEXT_DIRECTORY))
+            .expect("Should be able to read the PDF directory");
+        let mut count = 0;
+        for entry in dir_content {
+            let _dir_entry = entry.expect("Should be able to read entry");
+            count += 1;
+        }
+        assert_eq!(count, 2);
+        fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+    }
+
+    #[tokio::test]
+    async fn integration_test_all() {
+        let output_dir = test_output_dir("integration_test_all");
+        let result = download_arxiv_papers(
+            "cat:cs.CL".to_string(),
+            2,
+            &DownloadOptions {
+                save_pdfs: true,
+                save_summaries: true,
+                output_dir: output_dir.clone(),
+                ..Default::default()
+            },
+        )
+        .await;
+        if let Err(e) = result {
+            panic!("An error occurred: {}", e);
+        }
+        let file_exists = fs::exists(output_dir.join(JSON_FILE))
+            .expect("Should be able to check the existance of the metadata.jsonl file");
+        assert!(file_exists);
+        let content = fs::read_to_string(output_dir.join(JSON_FILE))
+            .expect("Should be able to read metadata.jsonl file");
+        assert!(!content.is_empty());
+        let pdf_dir_exists: bool = fs::exists(output_dir.join(PDF_DIRECTORY))
+            .expect("Should be able to check the existance of the PDF directory");
+        assert!(pdf_dir_exists);
+        let pdf_dir_content = fs::read_dir(output_dir.join(PDF_DIRECTORY))
+            .expect("Should be able to read the PDF directory");
+        let mut pdf_count = 0;
+        for entry in pdf_dir_content {
+            let _dir_entry = entry.expect("Should be able to read entry");
+            pdf_count += 1;
+        }
+        assert_eq!(pdf_count, 2);
+        let text_dir_exists: bool = fs::exists(output_dir.join(TEXT_DIRECTORY))
             .expect("Should be able to check the existance of the text directory");
         assert!(text_dir_exists);
-        let dir_content =
-            fs::read_dir(TEXT_DIRECTORY).expect("Should be able to read the PDF directory");
+        let dir_content = fs::read_dir(output_dir.join(TEXT_DIRECTORY))
+            .expect("Should be able to read the PDF directory");
         let mut count = 0;
         for entry in dir_content {
             let _dir_entry = entry.expect("Should be able to read entry");
             count += 1;
         }
         assert_eq!(count, 2);
+        fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+    }
+
+    #[tokio::test]
+    async fn integration_test_output_dir() {
+        let root = test_output_dir("integration_test_output_dir");
+        let output_dir = root.join("nested");
+        let options = DownloadOptions {
+            save_pdfs: true,
+            save_summaries: true,
+            output_dir: output_dir.clone(),
+            ..Default::default()
+        };
+        let result = download_arxiv_papers("cat:cs.CL".to_string(), 2, &options).await;
+        match result {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("An error occurred: {}", e);
+                panic!()
+            }
+        }
+        assert!(output_dir.join(JSON_FILE).exists());
+        assert!(output_dir.join(PDF_DIRECTORY).is_dir());
+        assert!(output_dir.join(TEXT_DIRECTORY).is_dir());
+        assert!(!root.join(JSON_FILE).exists());
+        fs::remove_dir_all(&root).expect("Should be able to remove output directory");
+    }
+
+    #[tokio::test]
+    async fn test_save_papers_layout() {
+        let root = test_output_dir("test_save_papers_layout");
+        let output_dir = root.join("nested").join("dir");
+        let options = DownloadOptions {
+            save_summaries: true,
+            csv: true,
+            with_abstracts: true,
+            bibtex: Some(PathBuf::from(BIBTEX_FILE)),
+            markdown: Some(PathBuf::from("reading-list.md")),
+            quiet: true,
+            output_dir: output_dir.clone(),
+            ..Default::default()
+        };
+        let mut first = fixture("2401.00001v1", "First");
+        first.summary = "An abstract.".to_string();
+        let papers = [first, fixture("2401.00002v1", "Second")];
+        save_papers(&papers, &["ti:test".to_string()], &options)
+            .await
+            .expect("Should save papers");
+        assert!(output_dir.join(JSON_FILE).is_file());
+        assert!(output_dir.join(CSV_FILE).is_file());
+        let abstracts = fs::read_to_string(output_dir.join(ABSTRACTS_FILE))
+            .expect("Should be able to read abstracts");
+        assert_eq!(abstracts.lines().count(), 2);
+        let bibtex = fs::read_to_string(output_dir.join(BIBTEX_FILE))
+            .expect("Should be able to read BibTeX file");
+        assert_eq!(
+            check_bibtex(&bibtex),
+            vec!["anonymousfirst", "anonymoussecond"]
+        );
+        let markdown = fs::read_to_string(output_dir.join("reading-list.md"))
+            .expect("Should be able to read reading list");
+        assert!(markdown.contains("Query: `ti:test`"));
+        assert_eq!(markdown.matches("\n## [").count(), 2);
+        // PDFs were not requested, so none is linked
+        assert!(!markdown.contains("**PDF:**"));
+        assert!(output_dir.join(TEXT_DIRECTORY).join("First.txt").is_file());
+        // The second paper has an empty summary
+        assert!(!output_dir.join(TEXT_DIRECTORY).join("Second.txt").exists());
+        assert!(!output_dir.join(PDF_DIRECTORY).exists());
+        assert!(!Path::new(JSON_FILE).exists());
+        assert!(!Path::new(TEXT_DIRECTORY).exists());
+        // A second run only appends the new paper, under a key not used yet
+        let papers = [
+            papers[0].clone(),
+            papers[1].clone(),
+            fixture("2401.00003v1", "First"),
+        ];
+        save_papers(&papers, &["ti:test".to_string()], &options)
+            .await
+            .expect("Should save papers");
+        let bibtex = fs::read_to_string(output_dir.join(BIBTEX_FILE))
+            .expect("Should be able to read BibTeX file");
+        assert_eq!(
+            check_bibtex(&bibtex),
+            vec!["anonymousfirst", "anonymoussecond", "anonymousfirsta"]
+        );
+        fs::remove_dir_all(&root).expect("Should be able to remove output directory");
+    }
+
+    #[tokio::test]
+    async fn test_save_papers_keeps_files_of_other_papers() {
+        let output_dir = test_output_dir("test_save_papers_keeps_files_of_other_papers");
+        let text_dir = output_dir.join(TEXT_DIRECTORY);
+        fs::create_dir_all(&text_dir).expect("Should be able to create directory");
+        // An earlier run saved the summary of another paper with the same title
+        let mut earlier = fixture("2401.00001v1", "Same title");
+        earlier.summary = "Earlier abstract.".to_string();
+        let path = text_dir.join("Same title.txt");
+        fs::write(&path, &earlier.summary).expect("Should write summary");
+        let mut manifest = Manifest::default();
+        manifest
+            .record(
+                &earlier,
+                Artifact::Summary,
+                &path,
+                "texts/Same title.txt".to_string(),
+                "2024-03-01T12:00:00Z",
+            )
+            .expect("Should record summary");
+        manifest
+            .save_merged(&output_dir.join(MANIFEST_FILE))
+            .expect("Should save manifest");
+        let options = DownloadOptions {
+            save_metadata: false,
+            save_summaries: true,
+            force: true,
+            quiet: true,
+            output_dir: output_dir.clone(),
+            ..Default::default()
+        };
+        let mut paper = fixture("2401.00002v1", "Same title");
+        paper.summary = "New abstract.".to_string();
+        save_papers(&[paper], &["ti:same".to_string()], &options)
+            .await
+            .expect("Should save papers");
+        assert_eq!(
+            fs::read_to_string(&path).expect("Should read summary"),
+            "Earlier abstract."
+        );
+        let summary = fs::read_to_string(text_dir.join("Same title_1.txt"))
+            .expect("Should write the summary under another name");
+        assert!(summary.contains("New abstract."));
+        // The paper that owns the file keeps its name
+        save_papers(&[earlier], &["ti:same".to_string()], &options)
+            .await
+            .expect("Should save papers");
+        assert!(!text_dir.join("Same title_2.txt").exists());
+        fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+    }
+
+    #[tokio::test]
+    async fn test_save_papers_dirs() {
+        let root = std::env::temp_dir().join("arxiv_cli_test_save_papers_dirs");
+        if root.exists() {
+            fs::remove_dir_all(&root).expect("Should be able to remove directory");
+        }
+        let pdf_dir = root.join("papers").join("raw");
+        let output_dir = root.join("output");
+        let options = DownloadOptions {
+            save_metadata: false,
+            save_pdfs: true,
+            save_summaries: true,
+            quiet: true,
+            output_dir: output_dir.clone(),
+            pdf_dir: pdf_dir.clone(),
+            // Trailing separators are ignored when joining
+            text_dir: PathBuf::from("abstracts/"),
+            markdown: Some(PathBuf::from("lists/reading.md")),
+            ..Default::default()
+        };
+        let mut paper = fixture("2401.00001v1", "First");
+        paper.summary = "An abstract.".to_string();
+        // An existing PDF is skipped, so that nothing is downloaded
+        fs::create_dir_all(&pdf_dir).expect("Should be able to create directory");
+        fs::write(pdf_dir.join("First.pdf"), "%PDF").expect("Should be able to write PDF");
+        save_papers(&[paper], &[], &options)
+            .await
+            .expect("Should save papers");
+        let summary = fs::read_to_string(output_dir.join("abstracts").join("First.txt"))
+            .expect("Should be able to read summary");
+        assert_eq!(summary, "An abstract.");
+        // The PDF directory is outside of the reading list's, so its path is kept
+        let markdown = fs::read_to_string(output_dir.join("lists").join("reading.md"))
+            .expect("Should be able to read reading list");
+        let link = pdf_dir.join("First.pdf");
+        assert!(markdown.contains(&format!("[First.pdf](<{}>)", link.display())));
+        assert!(!output_dir.join(PDF_DIRECTORY).exists());
+        assert!(!output_dir.join(TEXT_DIRECTORY).exists());
+        fs::remove_dir_all(&root).expect("Should be able to remove directory");
+    }
+
+    #[tokio::test]
+    async fn test_save_papers_stdout() {
+        let output_dir = test_output_dir("test_save_papers_stdout");
+        let options = DownloadOptions {
+            stdout: true,
+            output_dir: output_dir.clone(),
+            ..Default::default()
+        };
+        save_papers(&[fixture("2401.00001v1", "First")], &[], &options)
+            .await
+            .expect("Should print metadata");
+        assert!(!output_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn test_save_papers_manifest() {
+        let output_dir = std::env::temp_dir().join("arxiv_cli_test_save_papers_manifest");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir).expect("Should be able to remove directory");
+        }
+        let options = DownloadOptions {
+            save_metadata: false,
+            save_pdfs: true,
+            save_summaries: true,
+            quiet: true,
+            output_dir: output_dir.clone(),
+            ..Default::default()
+        };
+        let mut first = fixture("2401.00001v1", "First");
+        first.summary = "An abstract.".to_string();
+        // No summary, so no summary file
+        let second = fixture("2401.00002v1", "Second");
+        // An existing PDF is skipped: it is not written by this run
+        let pdf_dir = output_dir.join(PDF_DIRECTORY);
+        fs::create_dir_all(&pdf_dir).expect("Should be able to create directory");
+        for name in ["First", "Second"] {
+            fs::write(pdf_dir.join(format!("{}.pdf", name)), "%PDF")
+                .expect("Should be able to write PDF");
+        }
+        save_papers(&[first, second], &[], &options)
+            .await
+            .expect("Should save papers");
+        let manifest =
+            Manifest::load(&output_dir.join(MANIFEST_FILE)).expect("Should load manifest");
+        assert_eq!(manifest.len(), 1);
+        let entry = manifest.get("2401.00001").expect("Should list first paper");
+        assert_eq!(entry.id, "2401.00001v1");
+        assert_eq!(entry.title, "First");
+        assert!(entry.pdf.is_none());
+        let summary = entry.summary.as_ref().expect("Should list summary");
+        assert_eq!(summary.path, "texts/First.txt");
+        let on_disk = fs::metadata(output_dir.join(&summary.path)).expect("Should exist");
+        assert_eq!(summary.bytes, on_disk.len());
+        assert!(manifest.get("2401.00002").is_none());
+        fs::remove_dir_all(&output_dir).expect("Should be able to remove directory");
+    }
+
+    #[tokio::test]
+    async fn test_save_papers_html() {
+        let output_dir = std::env::temp_dir().join("arxiv_cli_test_save_papers_html");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir).expect("Should be able to remove directory");
+        }
+        let options = DownloadOptions {
+            save_metadata: false,
+            save_pdfs: true,
+            html: Some(PathBuf::from("index.html")),
+            quiet: true,
+            output_dir: output_dir.clone(),
+            ..Default::default()
+        };
+        let papers = [
+            fixture("2401.00001v1", "First paper"),
+            fixture("2401.00002v1", "Second"),
+        ];
+        // Existing PDFs are skipped, so that nothing is downloaded
+        let pdf_dir = output_dir.join(PDF_DIRECTORY);
+        fs::create_dir_all(&pdf_dir).expect("Should be able to create directory");
+        for name in ["First paper", "Second"] {
+            fs::write(pdf_dir.join(format!("{}.pdf", name)), "%PDF")
+                .expect("Should be able to write PDF");
+        }
+        save_papers(&papers, &[], &options)
+            .await
+            .expect("Should save papers");
+        let html = fs::read_to_string(output_dir.join("index.html"))
+            .expect("Should be able to read HTML page");
+        let hrefs: Vec<&str> = html
+            .split("<p>PDF: <a href=\"")
+            .skip(1)
+            .filter_map(|rest| rest.split_once('"').map(|(href, _)| href))
+            .collect();
+        assert_eq!(hrefs, vec!["pdfs/First%20paper.pdf", "pdfs/Second.pdf"]);
+        for href in hrefs {
+            let path = href.replace("%20", " ");
+            assert!(output_dir.join(path).is_file(), "{}", href);
+        }
+        fs::remove_dir_all(&output_dir).expect("Should be able to remove directory");
+    }
+
+    #[tokio::test]
+    async fn integration_test_sort_order() {
+        let mut first_published = vec![];
+        for sort_order in [SortOrder::Ascending, SortOrder::Descending] {
+            let output_dir =
+                test_output_dir(&format!("integration_test_sort_{}", sort_order.as_str()));
+            let metadata_file = output_dir.join("metadata.jsonl");
+            let options = DownloadOptions {
+                sort_order,
+                metadata_file: Some(PathBuf::from("metadata.jsonl")),
+                output_dir: output_dir.clone(),
+                ..Default::default()
+            };
+            let result = download_arxiv_papers("cat:cs.CL".to_string(), 1, &options).await;
+            match result {
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("An error occurred: {}", e);
+                    panic!()
+                }
+            }
+            let content =
+                fs::read_to_string(&metadata_file).expect("Should be able to read metadata file");
+            let paper: SerDesArxiv = serde_json::from_str(content.lines().next().unwrap_or(""))
+                .expect("Should be able to deserialize metadata");
+            first_published.push(paper.published);
+            fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+        }
+        assert!(first_published[0] < first_published[1]);
+    }
+
+    #[tokio::test]
+    async fn integration_test_start() {
+        let mut ids = vec![];
+        for start in [0, 1] {
+            let output_dir = test_output_dir(&format!("integration_test_start_{}", start));
+            let metadata_file = output_dir.join("metadata.jsonl");
+            let options = DownloadOptions {
+                start,
+                metadata_file: Some(PathBuf::from("metadata.jsonl")),
+                output_dir: output_dir.clone(),
+                ..Default::default()
+            };
+            let result = download_arxiv_papers("cat:cs.CL".to_string(), 3, &options).await;
+            match result {
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("An error occurred: {}", e);
+                    panic!()
+                }
+            }
+            let content =
+                fs::read_to_string(&metadata_file).expect("Should be able to read metadata file");
+            let page: Vec<String> = content
+                .lines()
+                .map(|line| {
+                    serde_json::from_str::<SerDesArxiv>(line)
+                        .expect("Should be able to deserialize metadata")
+                        .id
+                })
+                .collect();
+            ids.push(page);
+            fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+        }
+        assert_eq!(ids[0].len(), 3);
+        assert_eq!(ids[1].len(), 3);
+        assert_eq!(ids[0][1..], ids[1][..2]);
+    }
+
+    #[tokio::test]
+    async fn integration_test_pagination() {
+        let output_dir = test_output_dir("integration_test_pagination");
+        let metadata_file = output_dir.join("metadata.jsonl");
+        let options = DownloadOptions {
+            metadata_file: Some(PathBuf::from("metadata.jsonl")),
+            output_dir: output_dir.clone(),
+            ..Default::default()
+        };
+        let result = download_arxiv_papers("cat:cs.CL".to_string(), 150, &options).await;
+        match result {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("An error occurred: {}", e);
+                panic!()
+            }
+        }
+        let content =
+            fs::read_to_string(&metadata_file).expect("Should be able to read metadata file");
+        assert_eq!(content.lines().count(), 150);
+        let mut ids = HashSet::new();
+        for line in content.lines() {
+            let paper: SerDesArxiv =
+                serde_json::from_str(line).expect("Should be able to parse metadata line");
+            assert!(ids.insert(paper.id), "Duplicate paper in metadata file");
+        }
+        fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+    }
+
+    #[tokio::test]
+    async fn integration_test_all_pages() {
+        let output_dir = test_output_dir("integration_test_all_pages");
+        let metadata_file = output_dir.join("metadata.jsonl");
+        let options = DownloadOptions {
+            all: true,
+            metadata_file: Some(PathBuf::from("metadata.jsonl")),
+            output_dir: output_dir.clone(),
+            ..Default::default()
+        };
+        let result = download_arxiv_papers("cat:cs.CL".to_string(), 120, &options).await;
+        match result {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("An error occurred: {}", e);
+                panic!()
+            }
+        }
+        let content =
+            fs::read_to_string(&metadata_file).expect("Should be able to read metadata file");
+        assert_eq!(content.lines().count(), 120);
+        fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+    }
+
+    #[tokio::test]
+    async fn integration_test_all_append_filter() {
+        let output_dir = test_output_dir("integration_test_all_append_filter");
+        let metadata_file = output_dir.join("metadata.jsonl");
+        let options = DownloadOptions {
+            all: true,
+            append: true,
+            metadata_file: Some(PathBuf::from("metadata.jsonl")),
+            output_dir: output_dir.clone(),
+            filters: PaperFilters {
+                min_authors: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = download_arxiv_papers("cat:cs.CL".to_string(), 120, &options).await;
+        match result {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("An error occurred: {}", e);
+                panic!()
+            }
+        }
+        // The unfiltered papers of each page only went to the checkpoint
+        assert!(!options.checkpoint_path(&output_dir).exists());
+        let content =
+            fs::read_to_string(&metadata_file).expect("Should be able to read metadata file");
+        assert!(content.lines().count() > 0);
+        for line in content.lines() {
+            let paper: SerDesArxiv =
+                serde_json::from_str(line).expect("Should be able to parse metadata line");
+            assert!(
+                paper.authors.len() >= 3,
+                "Unfiltered paper in metadata file"
+            );
+        }
+        fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+    }
+
+    #[tokio::test]
+    async fn integration_test_all_filtered_out() {
+        let output_dir = test_output_dir("integration_test_all_filtered_out");
+        let metadata_file = output_dir.join("metadata.jsonl");
+        let options = DownloadOptions {
+            all: true,
+            metadata_file: Some(PathBuf::from("metadata.jsonl")),
+            output_dir: output_dir.clone(),
+            filters: PaperFilters {
+                title_regex: Some(regex::Regex::new("^no title is like this$").unwrap()),
+                ..Default::default()
+            },
+            fail_on_empty: true,
+            ..Default::default()
+        };
+        let result = download_arxiv_papers("cat:cs.CL".to_string(), 120, &options).await;
+        assert!(matches!(result, Err(ArxivCliError::EmptyQuery)));
+        // Neither the metadata file nor the unfiltered checkpoint are left
+        assert!(!metadata_file.exists());
+        assert!(!options.checkpoint_path(&output_dir).exists());
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+        }
+    }
+
+    #[tokio::test]
+    async fn integration_test_fetch_papers() {
+        if Path::new(JSON_FILE).exists() {
+            fs::remove_file(JSON_FILE).expect("Should be able to remove metadata.jsonl file");
+        }
+        let papers = fetch_papers("cat:cs.CL", 3, &DownloadOptions::default())
+            .await
+            .expect("Should fetch papers");
+        assert_eq!(papers.len(), 3);
+        assert!(papers.iter().all(|paper| !paper.title.is_empty()));
+        assert!(!Path::new(JSON_FILE).exists());
+    }
+
+    #[test]
+    fn test_total_results_line() {
+        assert_eq!(
+            total_results_line(4300, 0, 5),
+            "Query matched 4300 paper(s), fetching 5"
+        );
+        assert_eq!(
+            total_results_line(3, 0, 5),
+            "Query matched 3 paper(s), fetching 3"
+        );
+        assert_eq!(
+            total_results_line(120, 100, 50),
+            "Query matched 120 paper(s), fetching 20"
+        );
+        assert_eq!(
+            total_results_line(0, 0, 5),
+            "Query matched 0 paper(s), fetching 0"
+        );
+    }
+
+    #[tokio::test]
+    async fn integration_test_search_papers() {
+        let results = search_papers("cat:cs.CL", 3, &DownloadOptions::default())
+            .await
+            .expect("Should search papers");
+        assert_eq!(results.papers.len(), 3);
+        assert!(results.total_results.is_some_and(|total| total > 3));
+    }
+
+    #[test]
+    fn test_verify_pdf() {
+        assert!(matches!(
+            verify_pdf(b"<html>"),
+            Err(ArxivCliError::InvalidPdf(_))
+        ));
+        let mut pdf = b"%PDF-1.5\n".to_vec();
+        pdf.resize(MIN_PDF_SIZE, b' ');
+        pdf.extend_from_slice(b"\nstartxref\n1234\n%%EOF\n");
+        assert!(verify_pdf(&pdf).is_ok());
+        let truncated = &pdf[..MIN_PDF_SIZE + 2];
+        assert!(
+            verify_pdf(truncated)
+                .unwrap_err()
+                .to_string()
+                .contains("truncated")
+        );
+        assert!(
+            verify_pdf(b"%PDF-1.5\n%%EOF")
+                .unwrap_err()
+                .to_string()
+                .contains("too small")
+        );
+        let html = b"<!DOCTYPE html><html>Not found</html>";
+        assert!(
+            verify_pdf(html)
+                .unwrap_err()
+                .to_string()
+                .contains("not a PDF")
+        );
+    }
+
+    #[test]
+    fn test_source_extension() {
+        let gzip = [0x1f, 0x8b, 0x08, 0x00];
+        assert_eq!(
+            source_extension(None, Some("arXiv-2401.00001v1.tar.gz"), &gzip),
+            ".tar.gz"
+        );
+        assert_eq!(
+            source_extension(None, Some("arXiv-2401.00001v1.gz"), &gzip),
+            ".gz"
+        );
+        assert_eq!(
+            source_extension(Some("application/x-eprint-tar"), None, &gzip),
+            ".tar.gz"
+        );
+        assert_eq!(
+            source_extension(Some("application/x-eprint"), None, &gzip),
+            ".gz"
+        );
+        assert_eq!(source_extension(None, None, &gzip), ".tar.gz");
+        assert_eq!(
+            source_extension(Some("application/pdf"), None, b"%PDF-1.5"),
+            ".pdf"
+        );
+        let mut tar = vec![0; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(source_extension(None, None, &tar), ".tar");
+        assert_eq!(
+            source_extension(None, None, b"\\documentclass{article}"),
+            ".tex"
+        );
+    }
+
+    #[test]
+    fn test_disposition_file_name() {
+        assert_eq!(
+            disposition_file_name("attachment; filename=\"arXiv-2401.00001v1.tar.gz\""),
+            Some("arXiv-2401.00001v1.tar.gz")
+        );
+        assert_eq!(
+            disposition_file_name("attachment; filename=source.gz; size=10"),
+            Some("source.gz")
+        );
+        assert_eq!(disposition_file_name("inline"), None);
+    }
+
+    #[test]
+    fn test_selection_item() {
+        let mut paper = fixture("2401.12345v2", "Attention\n  is all you need");
+        paper.published = "2024-01-15T10:00:00Z".to_string();
+        assert_eq!(
+            selection_item(&paper),
+            "2401.12345v2  2024-01-15  Attention is all you need"
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_browser_command() {
+        let command = browser_command("https://arxiv.org/abs/1706.03762v7");
+        assert_eq!(command.get_program(), "xdg-open");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["https://arxiv.org/abs/1706.03762v7"]);
+    }
+
+    #[test]
+    fn test_source_url() {
+        let mut paper = fixture("1706.03762v7", "Attention");
+        assert_eq!(paper.source_url(), "https://arxiv.org/e-print/1706.03762v7");
+        paper.id = "http://arxiv.org/abs/cs/0112017v1".to_string();
+        assert_eq!(paper.source_url(), "https://arxiv.org/e-print/cs/0112017v1");
+    }
+
+    #[tokio::test]
+    async fn integration_test_fetch_source() {
+        let dir = std::env::temp_dir().join("arxiv_cli_integration_test_fetch_source");
+        fs::create_dir_all(&dir).expect("Should be able to create directory");
+        let paper = fixture("1706.03762v7", "Attention Is All You Need");
+        let path = paper
+            .fetch_source(&dir.join("Attention").to_string_lossy())
+            .await
+            .expect("Should fetch the source");
+        assert_eq!(path, dir.join("Attention.tar.gz"));
+        let body = fs::read(&path).expect("Should be able to read source");
+        assert!(body.starts_with(&[0x1f, 0x8b]));
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+
+    #[test]
+    fn test_relative_link() {
+        let base = Path::new("out");
+        assert_eq!(
+            relative_link(&base.join("pdfs").join("A B.pdf"), base),
+            "pdfs/A B.pdf"
+        );
+        assert_eq!(
+            relative_link(Path::new("/data/pdfs/A.pdf"), base),
+            "/data/pdfs/A.pdf"
+        );
+    }
+
+    #[test]
+    fn test_check_not_empty() {
+        let options = DownloadOptions::default();
+        assert!(check_not_empty(&[], &options).is_ok());
+        assert!(check_not_empty(&[fixture("2401.00001", "First")], &options).is_ok());
+        let options = DownloadOptions {
+            fail_on_empty: true,
+            ..Default::default()
+        };
+        let err = check_not_empty(&[], &options).expect_err("Should fail on empty results");
+        assert_eq!(err.to_string(), "no papers matched the query");
+        assert!(check_not_empty(&[fixture("2401.00001", "First")], &options).is_ok());
+    }
+
+    #[tokio::test]
+    async fn integration_test_fail_on_empty() {
+        let options = DownloadOptions {
+            fail_on_empty: true,
+            ..Default::default()
+        };
+        let err = download_arxiv_papers("ti:qwxzyvvkj AND au:zzqqxxjj".to_string(), 5, &options)
+            .await
+            .expect_err("Should fail on a query matching nothing");
+        assert!(matches!(err, ArxivCliError::EmptyQuery));
+        assert_eq!(err.to_string(), "no papers matched the query");
+    }
+
+    #[test]
+    fn test_dry_run_line() {
+        let mut paper = fixture("2401.12345v2", "Attention\n  is all you need");
+        paper.primary_category = "cs.CL".to_string();
+        assert_eq!(
+            dry_run_line(&paper),
+            "2401.12345v2\tAttention is all you need\tcs.CL"
+        );
+    }
+
+    #[tokio::test]
+    async fn integration_test_dry_run() {
+        let output_dir = test_output_dir("integration_test_dry_run");
+        let options = DownloadOptions {
+            save_pdfs: true,
+            save_summaries: true,
+            dry_run: true,
+            output_dir: output_dir.clone(),
+            ..Default::default()
+        };
+        let result = download_arxiv_papers("cat:cs.CL".to_string(), 2, &options).await;
+        match result {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("An error occurred: {}", e);
+                panic!()
+            }
+        }
+        assert!(!output_dir.exists());
+    }
+
+    #[test]
+    fn test_write_metadata() {
+        let dir = test_output_dir("test_write_metadata");
+        fs::create_dir_all(&dir).expect("Should be able to create directory");
+        let options = DownloadOptions::default();
+        write_metadata(&dir, &[fixture("2401.00001", "First")], &options)
+            .expect("Should write metadata");
+        let papers = [
+            fixture("2401.00001", "First"),
+            fixture("2401.00002", "Second"),
+        ];
+        write_metadata(&dir, &papers, &options).expect("Should write metadata");
+        let content =
+            fs::read_to_string(dir.join(JSON_FILE)).expect("Should be able to read metadata");
+        assert_eq!(content.lines().count(), 2);
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+
+    #[test]
+    fn test_write_checkpoint() {
+        let dir = std::env::temp_dir().join("arxiv_cli_test_write_checkpoint");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+        }
+        let options = DownloadOptions {
+            all: true,
+            append: true,
+            ..Default::default()
+        };
+        let checkpoint = options.checkpoint_path(&dir);
+        assert_eq!(checkpoint, dir.join("metadata.jsonl.partial"));
+        let papers = [
+            fixture("2401.00001v1", "First"),
+            fixture("2401.00002v1", "Second"),
+        ];
+        write_checkpoint(&checkpoint, &papers[..1], &options).expect("Should write checkpoint");
+        // Each page replaces the previous checkpoint, even when appending
+        write_checkpoint(&checkpoint, &papers, &options).expect("Should write checkpoint");
+        let content = fs::read_to_string(&checkpoint).expect("Should read checkpoint");
+        assert_eq!(content.lines().count(), 2);
+        assert!(!options.metadata_path(&dir).exists());
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+
+    #[test]
+    fn test_write_hashed() {
+        let dir = std::env::temp_dir().join("arxiv_cli_test_write_hashed");
+        fs::create_dir_all(&dir).expect("Should be able to create directory");
+        let path = dir.join("paper.pdf");
+        let digest = write_hashed(&path, b"%PDF-1.4 test").expect("Should write file");
+        // Same as `printf '%PDF-1.4 test' | sha256sum`
+        assert_eq!(
+            digest,
+            "d663640088750cf16276d623c2588d7233f2b84b45f4b2e20832f47b16aa5618"
+        );
+        assert_eq!(
+            fs::read(&path).expect("Should be able to read file"),
+            b"%PDF-1.4 test"
+        );
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+
+    #[test]
+    fn test_update_checksums() {
+        let dir = std::env::temp_dir().join("arxiv_cli_test_update_checksums");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+        }
+        fs::create_dir_all(&dir).expect("Should be able to create directory");
+        let digest = |c: char| c.to_string().repeat(64);
+        let path = update_checksums(
+            &dir,
+            &[
+                ("First.pdf".to_string(), digest('a')),
+                ("Second paper.pdf".to_string(), digest('b')),
+            ],
+        )
+        .expect("Should write checksums");
+        assert_eq!(path, dir.join(CHECKSUMS_FILE));
+        assert_eq!(
+            fs::read_to_string(&path).expect("Should read checksums"),
+            format!(
+                "{}  First.pdf\n{}  Second paper.pdf\n",
+                digest('a'),
+                digest('b')
+            )
+        );
+        // A later run replaces the line of a downloaded again PDF, whatever its mode
+        let content = format!(
+            "{} *Second paper.pdf\n{}  First.pdf\n",
+            digest('b'),
+            digest('a')
+        );
+        fs::write(&path, content).expect("Should write checksums");
+        update_checksums(
+            &dir,
+            &[
+                ("Second paper.pdf".to_string(), digest('c')),
+                ("Third.pdf".to_string(), digest('d')),
+            ],
+        )
+        .expect("Should update checksums");
+        assert_eq!(
+            fs::read_to_string(&path).expect("Should read checksums"),
+            format!(
+                "{}  First.pdf\n{}  Second paper.pdf\n{}  Third.pdf\n",
+                digest('a'),
+                digest('c'),
+                digest('d')
+            )
+        );
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+
+    #[test]
+    fn test_timestamped_file_name() {
+        assert_eq!(
+            MetadataFormat::Jsonl.timestamped_file_name("2024-06-01T12:30:00"),
+            "metadata-2024-06-01T12-30-00.jsonl"
+        );
+        assert_eq!(
+            MetadataFormat::Csv.timestamped_file_name("2024-06-01T12:30:00"),
+            "metadata-2024-06-01T12-30-00.csv"
+        );
+        // Separators, spaces and anything else outside of [A-Za-z0-9_-]
+        assert_eq!(
+            MetadataFormat::Yaml.timestamped_file_name("2024/06/01 12:30:00.5+02:00"),
+            "metadata-2024-06-01-12-30-00-5-02-00.yaml"
+        );
+    }
+
+    #[test]
+    fn test_run_manifest() {
+        let options = DownloadOptions {
+            sort_by: SortBy::SubmittedDate,
+            seed: Some(7),
+            ..Default::default()
+        };
+        let queries = ["(cat:cs.CL OR cat:cs.LG) AND ti:retrieval".to_string()];
+        let manifest = RunManifest::new(
+            &queries,
+            20,
+            Some(1234),
+            20,
+            &options,
+            "2024-03-01T12:00:00Z".to_string(),
+        );
+        let value = serde_json::to_value(&manifest).expect("Should serialize");
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "tool_version": env!("CARGO_PKG_VERSION"),
+                "timestamp": "2024-03-01T12:00:00Z",
+                "search_queries": ["(cat:cs.CL OR cat:cs.LG) AND ti:retrieval"],
+                "limit": 20,
+                "all": false,
+                "start": 0,
+                "sort_by": "submittedDate",
+                "sort_order": "descending",
+                "seed": 7,
+                "total_results": 1234,
+                "papers": 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_write_metadata_append() {
+        let dir = test_output_dir("test_write_metadata_append");
+        fs::create_dir_all(&dir).expect("Should be able to create directory");
+        let options = DownloadOptions {
+            append: true,
+            ..Default::default()
+        };
+        // The file does not exist yet
+        write_metadata(&dir, &[fixture("2401.00001v1", "First")], &options)
+            .expect("Should write metadata");
+        let papers = [
+            fixture("2401.00001v2", "First, revised"),
+            fixture("2401.00002v1", "Second"),
+        ];
+        write_metadata(&dir, &papers, &options).expect("Should append metadata");
+        let content =
+            fs::read_to_string(dir.join(JSON_FILE)).expect("Should be able to read metadata");
+        let titles: Vec<String> = content
+            .lines()
+            .map(|line| {
+                let paper: SerDesArxiv = serde_json::from_str(line).expect("Should deserialize");
+                paper.title
+            })
+            .collect();
+        assert_eq!(titles, vec!["First", "Second"]);
+        // A file edited by hand may lack the trailing newline
+        fs::write(dir.join(JSON_FILE), content.trim_end()).expect("Should write metadata");
+        write_metadata(&dir, &[fixture("2401.00003v1", "Third")], &options)
+            .expect("Should append metadata");
+        let content =
+            fs::read_to_string(dir.join(JSON_FILE)).expect("Should be able to read metadata");
+        assert_eq!(content.lines().count(), 3);
+        assert!(
+            content
+                .lines()
+                .all(|line| serde_json::from_str::<SerDesArxiv>(line).is_ok())
+        );
+        let options = DownloadOptions {
+            format: MetadataFormat::Json,
+            ..options
+        };
+        assert!(write_metadata(&dir, &papers, &options).is_err());
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
     }
 
-    #[tokio::test]
-    #[serial]
-    async fn integration_test_all() {
-        if Path::new(PDF_DIRECTORY).exists() {
-            fs::remove_dir_all(PDF_DIRECTORY).expect("Should be able to remove PDF directory");
-        }
-        if Path::new(TEXT_DIRECTORY).exists() {
-            fs::remove_dir_all(TEXT_DIRECTORY).expect("Should be able to remove text directory");
-        }
-        if Path::new(JSON_FILE).exists() {
-            fs::remove_file(JSON_FILE).expect("Should be able to remove metadata.jsonl file");
-        }
-        let result = download_arxiv_papers("cat:cs.CL".to_string(), 2, true, true, true).await;
-        match result {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("An error occurred: {}", e.to_string());
-                assert!(false)
-            }
-        }
-        let file_exists = fs::exists(JSON_FILE)
-            .expect("Should be able to check the existance of the metadata.jsonl file");
-        assert!(file_exists);
-        let content =
-            fs::read_to_string(JSON_FILE).expect("Should be able to read metadata.jsonl file");
-        assert!(content.len() > 0);
-        let pdf_dir_exists: bool = fs::exists(PDF_DIRECTORY)
-            .expect("Should be able to check the existance of the PDF directory");
-        assert!(pdf_dir_exists);
-        let pdf_dir_content =
-            fs::read_dir(PDF_DIRECTORY).expect("Should be able to read the PDF directory");
-        let mut pdf_count = 0;
-        for entry in pdf_dir_content {
-            let _dir_entry = entry.expect("Should be able to read entry");
-            pdf_count += 1;
-        }
-        assert_eq!(pdf_count, 2);
-        let text_dir_exists: bool = fs::exists(TEXT_DIRECTORY)
-            .expect("Should be able to check the existance of the text directory");
-        assert!(text_dir_exists);
-        let dir_content =
-            fs::read_dir(TEXT_DIRECTORY).expect("Should be able to read the PDF directory");
-        let mut count = 0;
-        for entry in dir_content {
-            let _dir_entry = entry.expect("Should be able to read entry");
-            count += 1;
+    #[test]
+    fn test_write_metadata_append_updated() {
+        let dir = test_output_dir("test_write_metadata_append_updated");
+        let options = DownloadOptions {
+            append: true,
+            ..Default::default()
+        };
+        let paper = |id: &str, title: &str, updated: &str| {
+            let mut paper = fixture(id, title);
+            paper.updated = updated.to_string();
+            paper
+        };
+        let read = || -> Vec<String> {
+            fs::read_to_string(dir.join(JSON_FILE))
+                .expect("Should be able to read metadata")
+                .lines()
+                .map(str::to_string)
+                .collect()
+        };
+        // Fresh file
+        let first = [
+            paper("2401.00001v1", "First", "2024-01-01T00:00:00Z"),
+            paper("2401.00002v1", "Second", "2024-01-01T00:00:00Z"),
+        ];
+        write_metadata(&dir, &first, &options).expect("Should write metadata");
+        assert_eq!(read().len(), 2);
+        // A pure duplicate is skipped
+        write_metadata(&dir, &first[..1], &options).expect("Should append metadata");
+        assert_eq!(read().len(), 2);
+        // A corrupt line is kept as is
+        let mut content = read().join("\n");
+        content.push_str("\n{not json\n");
+        fs::write(dir.join(JSON_FILE), content).expect("Should write metadata");
+        // A newer version replaces the old record
+        let revised = paper("2401.00001v2", "First, revised", "2024-02-01T00:00:00Z");
+        write_metadata(&dir, &[revised], &options).expect("Should append metadata");
+        let lines = read();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "{not json");
+        let titles: Vec<String> = [&lines[0], &lines[2]]
+            .iter()
+            .map(|line| {
+                let paper: SerDesArxiv = serde_json::from_str(line).expect("Should deserialize");
+                paper.title
+            })
+            .collect();
+        assert_eq!(titles, vec!["Second", "First, revised"]);
+        // An older version does not replace the newer one
+        write_metadata(&dir, &first[..1], &options).expect("Should append metadata");
+        assert_eq!(read(), lines);
+        assert!(!dir.join("metadata.tmp").exists());
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+
+    #[test]
+    fn test_write_metadata_csv_append_updated() {
+        let dir = test_output_dir("test_write_metadata_csv_append_updated");
+        let options = DownloadOptions {
+            format: MetadataFormat::Csv,
+            include_summary: true,
+            append: true,
+            ..Default::default()
+        };
+        let mut first = fixture("2401.00001v1", "First");
+        first.summary = "An abstract,\nover two lines.".to_string();
+        let second = fixture("2401.00002v1", "Second");
+        write_metadata(&dir, &[first, second.clone()], &options).expect("Should write metadata");
+        let mut revised = fixture("2401.00001v2", "First, revised");
+        revised.updated = "2024-02-01T00:00:00Z".to_string();
+        write_metadata(&dir, &[revised.clone()], &options).expect("Should append metadata");
+        let path = dir.join(CSV_FILE);
+        let content = fs::read_to_string(&path).expect("Should be able to read metadata");
+        let mut reader = csv::Reader::from_reader(content.as_bytes());
+        let rows: Vec<csv::StringRecord> = reader
+            .records()
+            .collect::<Result<_, _>>()
+            .expect("Should read rows");
+        assert_eq!(
+            rows,
+            vec![csv_record(&second, true), csv_record(&revised, true)]
+        );
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+
+    #[test]
+    fn test_write_metadata_csv_append() {
+        let dir = test_output_dir("test_write_metadata_csv_append");
+        let options = DownloadOptions {
+            format: MetadataFormat::Csv,
+            include_summary: true,
+            append: true,
+            ..Default::default()
+        };
+        let mut first = fixture("2401.00001v1", "Graphs, \"Retrieval\", and Generation");
+        first.summary = "An abstract,\nover two lines.".to_string();
+        write_metadata(&dir, &[first.clone()], &options).expect("Should write metadata");
+        let papers = [
+            fixture("2401.00001v2", "First, revised"),
+            fixture("2401.00002v1", "Second"),
+        ];
+        write_metadata(&dir, &papers, &options).expect("Should append metadata");
+        let path = dir.join(CSV_FILE);
+        let content = fs::read_to_string(&path).expect("Should be able to read metadata");
+        let mut reader = csv::Reader::from_reader(content.as_bytes());
+        assert_eq!(
+            reader.headers().expect("Should read header"),
+            csv_header(true)
+        );
+        // The header is only written once, and the known paper is skipped
+        let rows: Vec<csv::StringRecord> = reader
+            .records()
+            .collect::<Result<_, _>>()
+            .expect("Should read rows");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], csv_record(&first, true));
+        assert_eq!(&rows[1][1], "Second");
+        assert_eq!(validate_metadata(&path, MetadataFormat::Csv).ok(), Some(2));
+        // An empty run still writes the header
+        let options = DownloadOptions {
+            append: false,
+            ..options
+        };
+        write_metadata(&dir, &[], &options).expect("Should write metadata");
+        let content = fs::read_to_string(&path).expect("Should be able to read metadata");
+        assert_eq!(
+            content.lines().collect::<Vec<_>>(),
+            vec![csv_header(true).join(",")]
+        );
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+
+    #[test]
+    fn test_write_abstracts() {
+        let mut first = fixture("2401.00001v1", "A title\n  over two lines");
+        first.summary = "  An abstract,\nwith a \"quote\".\n".to_string();
+        let papers = [first, fixture("2401.00002v1", "Second")];
+        let mut buffer = vec![];
+        write_abstracts(&mut buffer, &papers).expect("Should write abstracts");
+        let content = String::from_utf8(buffer).expect("Should be UTF-8");
+        let records: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("Should parse record"))
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                serde_json::json!({
+                    "id": "http://arxiv.org/abs/2401.00001v1",
+                    "title": "A title over two lines",
+                    "abstract": "An abstract,\nwith a \"quote\"."
+                }),
+                serde_json::json!({
+                    "id": "http://arxiv.org/abs/2401.00002v1",
+                    "title": "Second",
+                    "abstract": ""
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_metadata() {
+        let dir = test_output_dir("test_validate_metadata");
+        fs::create_dir_all(&dir).expect("Should be able to create directory");
+        let papers = [
+            fixture("2401.00001v1", "First"),
+            fixture("2401.00002v1", "Second"),
+        ];
+        for format in [
+            MetadataFormat::Jsonl,
+            MetadataFormat::Json,
+            MetadataFormat::Csv,
+            MetadataFormat::Yaml,
+        ] {
+            let options = DownloadOptions {
+                format,
+                include_summary: true,
+                ..Default::default()
+            };
+            write_metadata(&dir, &papers, &options).expect("Should write metadata");
+            let count = validate_metadata(&dir.join(format.file_name()), format)
+                .expect("Should validate metadata");
+            assert_eq!(count, 2);
         }
-        assert_eq!(count, 2);
+        let path = dir.join(JSON_FILE);
+        let content = fs::read_to_string(&path).expect("Should be able to read metadata");
+        let corrupted: Vec<&str> = content
+            .lines()
+            .flat_map(|line| [line, r#"{"id": "truncated"#, "[]"])
+            .collect();
+        fs::write(&path, corrupted.join("\n")).expect("Should be able to write metadata");
+        let err = validate_metadata(&path, MetadataFormat::Jsonl)
+            .expect_err("Should reject corrupted metadata");
+        let message = err.to_string();
+        assert!(message.contains("line 2:"));
+        assert!(message.contains("line 3:"));
+        assert!(message.contains("line 6:"));
+        assert!(!message.contains("line 1:"));
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+
+    #[test]
+    fn test_write_metadata_file() {
+        let dir = test_output_dir("test_write_metadata_file");
+        let options = DownloadOptions {
+            metadata_file: Some(PathBuf::from("runs/attention.json")),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.metadata_path(&dir),
+            dir.join("runs").join("attention.json")
+        );
+        let papers = [
+            fixture("2401.00001", "First"),
+            fixture("2401.00002", "Second"),
+        ];
+        write_metadata(&dir, &papers, &options).expect("Should write metadata");
+        assert!(!dir.join(JSON_FILE).exists());
+        // The extension does not change the format
+        let content = fs::read_to_string(dir.join("runs").join("attention.json"))
+            .expect("Should be able to read metadata");
+        assert_eq!(
+            content,
+            serialize_metadata(&papers, MetadataFormat::Jsonl, false).expect("Should serialize")
+        );
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+        let absolute = std::env::temp_dir().join("metadata.jsonl");
+        let options = DownloadOptions {
+            metadata_file: Some(absolute.clone()),
+            ..Default::default()
+        };
+        assert_eq!(options.metadata_path(&dir), absolute);
+    }
+
+    #[test]
+    fn test_write_metadata_json() {
+        let dir = test_output_dir("test_write_metadata_json");
+        fs::create_dir_all(&dir).expect("Should be able to create directory");
+        let options = DownloadOptions {
+            format: MetadataFormat::Json,
+            include_summary: true,
+            ..Default::default()
+        };
+        let papers = [
+            fixture("2401.00001", "First"),
+            fixture("2401.00002", "Second"),
+        ];
+        write_metadata(&dir, &papers, &options).expect("Should write metadata");
+        let records: Vec<MetadataRecord> = papers
+            .iter()
+            .map(|paper| MetadataRecord::new(paper, true))
+            .collect();
+        let content = fs::read_to_string(dir.join(options.format.file_name()))
+            .expect("Should be able to read metadata");
+        assert_eq!(
+            content,
+            serde_json::to_string_pretty(&records).expect("Should serialize")
+        );
+        let parsed: Vec<SerDesArxiv> =
+            serde_json::from_str(&content).expect("Should be a valid JSON array");
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1].title, "Second");
+        write_metadata(&dir, &[], &options).expect("Should write metadata");
+        let content = fs::read_to_string(dir.join(options.format.file_name()))
+            .expect("Should be able to read metadata");
+        assert_eq!(content, "[]");
+        let parsed: Vec<SerDesArxiv> =
+            serde_json::from_str(&content).expect("Should be a valid JSON array");
+        assert!(parsed.is_empty());
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
     }
 
     #[test]
     fn test_serdes_arxiv_write_summary() {
         let paper = SerDesArxiv {
-            id: "".to_string(),
-            updated: "".to_string(),
-            published: "".to_string(),
             title: "test_title".to_string(),
             summary: "This is a test summary.".to_string(),
-            authors: vec![],
-            primary_category: "".to_string(),
-            categories: vec![],
-            pdf_url: "".to_string(),
-            html_url: "".to_string(),
-            comment: None,
+            ..Default::default()
         };
         let out_path = "test_summary.txt";
-        paper
-            .write_summary(out_path)
-            .expect("Should write summary to file");
+        assert!(
+            paper
+                .write_summary(out_path)
+                .expect("Should write summary to file")
+        );
         let written = fs::read_to_string(out_path).expect("Should read summary file");
         assert_eq!(written, "This is a test summary.");
         fs::remove_file(out_path).expect("Should clean up summary file");
     }
 
+    #[test]
+    fn test_serdes_arxiv_write_empty_summary() {
+        let mut paper = fixture("2401.00001v1", "No abstract");
+        paper.summary = " \n ".to_string();
+        let out_path = "test_empty_summary.txt";
+        assert!(
+            !paper
+                .write_summary(out_path)
+                .expect("Should skip empty summary")
+        );
+        assert!(!Path::new(out_path).exists());
+    }
+
     #[test]
     fn test_serdes_arxiv_to_string() {
         let paper = SerDesArxiv {
-            id: "".to_string(),
-            updated: "".to_string(),
-            published: "".to_string(),
             title: "test_title".to_string(),
             summary: "This is a test summary.".to_string(),
-            authors: vec![],
-            primary_category: "".to_string(),
-            categories: vec![],
-            pdf_url: "".to_string(),
-            html_url: "".to_string(),
-            comment: None,
+            ..Default::default()
         };
         let json_content = serde_json::to_string(&paper).expect("Should be able to serialize");
         assert!(!json_content.contains("summary"));
     }
 
+    #[test]
+    fn test_build_query_sort_by() {
+        for (sort_by, expected) in [
+            (SortBy::Relevance, "sortBy=relevance"),
+            (SortBy::LastUpdatedDate, "sortBy=lastUpdatedDate"),
+            (SortBy::SubmittedDate, "sortBy=submittedDate"),
+        ] {
+            let options = DownloadOptions {
+                sort_by,
+                ..Default::default()
+            };
+            let url = build_query("cat:cs.CL", &[], 0, 5, &options).to_url();
+            assert!(url.contains("search_query=cat:cs.CL"));
+            assert!(url.contains(expected));
+        }
+    }
+
+    #[test]
+    fn test_sort_by_from_str() {
+        assert_eq!(
+            "relevance".parse::<SortBy>().expect("Should parse"),
+            SortBy::Relevance
+        );
+        assert_eq!(
+            "lastUpdatedDate".parse::<SortBy>().expect("Should parse"),
+            SortBy::LastUpdatedDate
+        );
+        assert_eq!(
+            "submittedDate".parse::<SortBy>().expect("Should parse"),
+            SortBy::SubmittedDate
+        );
+        let err = "newest"
+            .parse::<SortBy>()
+            .expect_err("Should reject unknown field");
+        assert!(err.to_string().contains("invalid sort field 'newest'"));
+        assert_eq!(SortBy::default(), SortBy::SubmittedDate);
+    }
+
+    #[test]
+    fn test_build_query_sort_order() {
+        for (sort_order, expected) in [
+            (SortOrder::Ascending, "sortOrder=ascending"),
+            (SortOrder::Descending, "sortOrder=descending"),
+        ] {
+            let options = DownloadOptions {
+                sort_by: SortBy::SubmittedDate,
+                sort_order,
+                ..Default::default()
+            };
+            let url = build_query("cat:cs.CL", &[], 0, 5, &options).to_url();
+            assert!(url.contains("sortBy=submittedDate"));
+            assert!(url.contains(expected));
+        }
+        let err = "oldest"
+            .parse::<SortOrder>()
+            .expect_err("Should reject unknown order");
+        assert!(err.to_string().contains("invalid sort order 'oldest'"));
+    }
+
+    #[test]
+    fn test_build_query_raw_query() {
+        let raw = "(cat:cs.CL OR cat:cs.LG) ANDNOT au:smith";
+        let url = build_query(raw, &[], 0, 5, &DownloadOptions::default()).to_url();
+        assert!(url.contains(&format!("search_query={}&", raw)));
+    }
+
+    #[test]
+    fn test_build_query_start() {
+        let url = build_query("cat:cs.CL", &[], 100, 100, &DownloadOptions::default()).to_url();
+        assert!(url.contains("start=100"));
+        assert!(url.contains("max_results=100"));
+    }
+
+    #[test]
+    fn test_parse_arxiv_id() {
+        for (id, expected) in [
+            ("2401.12345", "2401.12345"),
+            ("2401.12345v2", "2401.12345"),
+            ("0704.0001", "0704.0001"),
+            ("arXiv:2401.12345v1", "2401.12345"),
+            ("cs/0112017", "cs/0112017"),
+            ("cs/0112017v1", "cs/0112017"),
+            ("math.GT/0309136", "math.GT/0309136"),
+            ("hep-th/9901001v3", "hep-th/9901001"),
+        ] {
+            assert_eq!(parse_arxiv_id(id).expect("Should parse id"), expected);
+        }
+        for id in [
+            "",
+            "2401",
+            "24011.2345",
+            "2401.123",
+            "cs/01120",
+            "CS/0112017",
+            "graphrag",
+        ] {
+            assert!(
+                matches!(parse_arxiv_id(id), Err(ArxivCliError::Parse(_))),
+                "{} should be rejected",
+                id
+            );
+        }
+    }
+
+    #[test]
+    fn test_paper_id_without_version() {
+        assert_eq!(
+            paper_id_without_version("http://arxiv.org/abs/2401.12345v1"),
+            "2401.12345"
+        );
+        assert_eq!(
+            paper_id_without_version("http://arxiv.org/abs/cs/0112017v2"),
+            "cs/0112017"
+        );
+        assert_eq!(paper_id_without_version("2401.12345"), "2401.12345");
+    }
+
+    #[test]
+    fn test_build_query_id_list() {
+        let id_list = vec!["2401.12345".to_string(), "cs/0112017".to_string()];
+        let url = build_query("", &id_list, 0, 5, &DownloadOptions::default()).to_url();
+        assert!(url.contains("id_list=2401.12345,cs/0112017"));
+        assert!(url.contains("max_results=2"));
+    }
+
+    #[test]
+    fn test_read_id_file() {
+        let dir = test_output_dir("test_read_id_file");
+        fs::create_dir_all(&dir).expect("Should be able to create directory");
+        let path = dir.join("ids.txt");
+        fs::write(
+            &path,
+            "# reading group, week 1\n2401.12345\n\n2401.12345v2\ncs/0112017 # classic\n  # indented comment\nnot-an-id\n2312.00001\n",
+        )
+        .expect("Should write id file");
+        let id_file = read_id_file(&path).expect("Should read id file");
+        assert_eq!(id_file.ids, vec!["2401.12345", "cs/0112017", "2312.00001"]);
+        assert_eq!(id_file.malformed, vec![(7, "not-an-id".to_string())]);
+        assert!(read_id_file(&dir.join("missing_ids.txt")).is_err());
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+
+    #[test]
+    fn test_dedup_papers() {
+        let cs_cl = vec![
+            fixture("2401.00001v1", "Only in cs.CL"),
+            fixture("2401.00002v1", "Cross-listed"),
+        ];
+        let cs_lg = vec![
+            fixture("2401.00002v2", "Cross-listed"),
+            fixture("2401.00003v1", "Only in cs.LG"),
+        ];
+        let mut papers: Vec<SerDesArxiv> = cs_cl.into_iter().chain(cs_lg).collect();
+        SeenPapers::default().retain_new(&mut papers);
+        let titles: Vec<&str> = papers.iter().map(|paper| paper.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec!["Only in cs.CL", "Cross-listed", "Only in cs.LG"]
+        );
+        assert_eq!(papers[1].id, "http://arxiv.org/abs/2401.00002v1");
+    }
+
+    #[test]
+    fn test_sample_papers() {
+        let pool: Vec<SerDesArxiv> = (0..50)
+            .map(|i| fixture(&format!("2401.{:05}", i), &format!("Paper {}", i)))
+            .collect();
+        let ids = |papers: &[SerDesArxiv]| -> Vec<String> {
+            papers.iter().map(|paper| paper.id.clone()).collect()
+        };
+        let mut first = pool.clone();
+        sample_papers(&mut first, 5, Some(42));
+        let mut second = pool.clone();
+        sample_papers(&mut second, 5, Some(42));
+        assert_eq!(first.len(), 5);
+        assert_eq!(ids(&first), ids(&second));
+        // The sampled papers keep the order of the pool
+        let positions: Vec<usize> = first
+            .iter()
+            .map(|paper| pool.iter().position(|p| p.id == paper.id).expect("In pool"))
+            .collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+        let mut other_seed = pool.clone();
+        sample_papers(&mut other_seed, 5, Some(7));
+        assert_ne!(ids(&first), ids(&other_seed));
+        let mut small = pool[..3].to_vec();
+        sample_papers(&mut small, 5, Some(42));
+        assert_eq!(ids(&small), ids(&pool[..3]));
+    }
+
+    #[test]
+    fn test_parse_query_file() {
+        let content = "# standing searches\ngraph neural networks\n\n  retrieval augmented generation  \n#llm\ncat:cs.CL AND ti:survey\n";
+        assert_eq!(
+            parse_query_file(content),
+            vec![
+                "graph neural networks",
+                "retrieval augmented generation",
+                "cat:cs.CL AND ti:survey"
+            ]
+        );
+        assert!(parse_query_file("# nothing here\n\n").is_empty());
+    }
+
+    #[tokio::test]
+    async fn integration_test_batch() {
+        let output_dir = test_output_dir("integration_test_batch");
+        let metadata_file = output_dir.join("metadata.jsonl");
+        let options = DownloadOptions {
+            metadata_file: Some(PathBuf::from("metadata.jsonl")),
+            output_dir: output_dir.clone(),
+            ..Default::default()
+        };
+        let queries = vec!["cat:cs.CL".to_string(), "cat:cs.CL".to_string()];
+        let result = download_batch(&queries, 3, &options).await;
+        match result {
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("An error occurred: {}", e);
+                panic!()
+            }
+        }
+        let content =
+            fs::read_to_string(&metadata_file).expect("Should be able to read metadata file");
+        assert_eq!(content.lines().count(), 3);
+        fs::remove_dir_all(&output_dir).expect("Should be able to remove output directory");
+    }
+
+    #[test]
+    fn test_seen_papers_across_batches() {
+        let mut seen = SeenPapers::default();
+        let mut papers = vec![];
+        let mut cs_cl = vec![
+            fixture("2401.00001v1", "Only in cs.CL"),
+            fixture("2401.00002v1", "Cross-listed"),
+        ];
+        seen.retain_new(&mut cs_cl);
+        papers.extend(cs_cl);
+        let mut cs_lg = vec![
+            fixture("2401.00002v1", "Cross-listed"),
+            fixture("2401.00003v1", "Only in cs.LG"),
+            fixture("2401.00001v2", "Only in cs.CL"),
+        ];
+        seen.retain_new(&mut cs_lg);
+        papers.extend(cs_lg);
+        assert_eq!(seen.duplicates, 2);
+        let metadata =
+            serialize_metadata(&papers, MetadataFormat::Jsonl, false).expect("Should serialize");
+        assert_eq!(metadata.lines().count(), 3);
+        let file_names = unique_file_names(
+            &papers,
+            &FilenameTemplate::default(),
+            DEFAULT_MAX_FILENAME_LENGTH,
+            &HashMap::new(),
+        );
+        assert_eq!(
+            file_names,
+            vec!["Only in cs.CL", "Cross-listed", "Only in cs.LG"]
+        );
+    }
+
+    fn serialize_metadata(
+        papers: &[SerDesArxiv],
+        format: MetadataFormat,
+        include_summary: bool,
+    ) -> anyhow::Result<String> {
+        let options = DownloadOptions {
+            format,
+            include_summary,
+            ..Default::default()
+        };
+        let mut buffer = vec![];
+        let mut writer = MetadataWriter::new(&mut buffer, &options);
+        for paper in papers {
+            writer.write(paper)?;
+        }
+        writer.finish()?;
+        Ok(String::from_utf8(buffer)?)
+    }
+
+    #[test]
+    fn test_serialize_metadata() {
+        let mut first = fixture("2401.00001v1", "First");
+        first.summary = "Not serialized".to_string();
+        let papers = vec![first, fixture("2401.00002v1", "Second")];
+        let jsonl = serialize_metadata(&papers, MetadataFormat::Jsonl, false)
+            .expect("Should serialize to JSONL");
+        assert_eq!(jsonl.lines().count(), 2);
+        assert!(!jsonl.contains("summary"));
+        let json = serialize_metadata(&papers, MetadataFormat::Json, false)
+            .expect("Should serialize to JSON");
+        assert!(!json.contains("summary"));
+        let parsed: serde_json::Value = serde_json::from_str(&json).expect("Should be valid JSON");
+        let array = parsed.as_array().expect("Should be a JSON array");
+        assert_eq!(array.len(), 2);
+        assert_eq!(array[1]["title"], "Second");
+        assert_eq!(MetadataFormat::Json.file_name(), "metadata.json");
+        assert!("xml".parse::<MetadataFormat>().is_err());
+    }
+
+    #[test]
+    fn test_pretty_records() {
+        let mut first = fixture("2401.00001v1", "First");
+        first.summary = "A summary.".to_string();
+        let papers = vec![first, fixture("2401.00002v1", "Second")];
+        // The JSONL file keeps one record per line, whatever the preview
+        let jsonl = serialize_metadata(&papers, MetadataFormat::Jsonl, true)
+            .expect("Should serialize to JSONL");
+        assert_eq!(jsonl.lines().count(), 2);
+        for line in jsonl.lines() {
+            serde_json::from_str::<SerDesArxiv>(line).expect("Should be a JSON object");
+        }
+        let preview = pretty_records(&papers, true).expect("Should serialize");
+        assert!(preview.starts_with("{\n  \"id\": \"http://arxiv.org/abs/2401.00001v1\",\n"));
+        assert!(preview.contains("\n  \"summary\": \"A summary.\"\n}\n{\n"));
+        // The JSON array is indented
+        let json = serialize_metadata(&papers, MetadataFormat::Json, false)
+            .expect("Should serialize to JSON");
+        assert!(json.starts_with("[\n  {\n    \"id\": "));
+        assert!(json.contains("\n  },\n  {\n"));
+    }
+
+    #[test]
+    fn test_serialize_metadata_yaml() {
+        let mut first = fixture("2401.00001v1", "First: a \"quoted\" title");
+        first.summary = "A summary wrapped\nover two lines.".to_string();
+        first.authors = vec!["Jane Doe".to_string(), "John Smith".to_string()];
+        first.categories = vec!["cs.CL".to_string(), "cs.LG".to_string()];
+        first.doi = Some("10.1000/xyz".to_string());
+        let mut second = fixture("2401.00002v1", "Second");
+        second.summary = "One line.".to_string();
+        let papers = vec![first, second];
+        let yaml = serialize_metadata(&papers, MetadataFormat::Yaml, true)
+            .expect("Should serialize to YAML");
+        assert!(yaml.starts_with("- id: http://arxiv.org/abs/2401.00001v1\n"));
+        // Multi-line abstracts are block scalars, kept readable
+        assert!(yaml.contains("  summary: |-\n    A summary wrapped\n    over two lines.\n"));
+        let parsed: Vec<SerDesArxiv> = serde_yaml::from_str(&yaml).expect("Should be valid YAML");
+        assert_eq!(parsed.len(), 2);
+        for (parsed, paper) in parsed.iter().zip(&papers) {
+            assert_eq!(
+                serde_json::to_value(parsed).expect("Should serialize"),
+                serde_json::to_value(paper).expect("Should serialize")
+            );
+            assert_eq!(parsed.summary, paper.summary);
+        }
+        let yaml = serialize_metadata(&papers, MetadataFormat::Yaml, false)
+            .expect("Should serialize to YAML");
+        assert!(!yaml.contains("summary"));
+        let yaml =
+            serialize_metadata(&[], MetadataFormat::Yaml, false).expect("Should serialize to YAML");
+        let parsed: Vec<SerDesArxiv> = serde_yaml::from_str(&yaml).expect("Should be valid YAML");
+        assert!(parsed.is_empty());
+        assert_eq!(
+            "yml".parse::<MetadataFormat>().ok(),
+            Some(MetadataFormat::Yaml)
+        );
+        assert_eq!(MetadataFormat::Yaml.file_name(), "metadata.yaml");
+    }
+
+    #[test]
+    fn test_serialize_summary() {
+        let mut paper = fixture("2401.00001v1", "First");
+        paper.summary = "An abstract\nover two lines.".to_string();
+        let jsonl = serialize_metadata(std::slice::from_ref(&paper), MetadataFormat::Jsonl, false)
+            .expect("Should serialize to JSONL");
+        let record: serde_json::Value =
+            serde_json::from_str(jsonl.trim_end()).expect("Should be valid JSON");
+        assert!(record.get("summary").is_none());
+        let parsed: SerDesArxiv =
+            serde_json::from_str(jsonl.trim_end()).expect("Should deserialize without summary");
+        assert_eq!(parsed.summary, "");
+        let jsonl = serialize_metadata(std::slice::from_ref(&paper), MetadataFormat::Jsonl, true)
+            .expect("Should serialize to JSONL");
+        let record: serde_json::Value =
+            serde_json::from_str(jsonl.trim_end()).expect("Should be valid JSON");
+        assert_eq!(record["summary"], "An abstract\nover two lines.");
+        let parsed: SerDesArxiv =
+            serde_json::from_str(jsonl.trim_end()).expect("Should deserialize with summary");
+        assert_eq!(parsed.summary, paper.summary);
+        assert_eq!(parsed.title, "First");
+    }
+
+    #[test]
+    fn test_serialize_journal_ref() {
+        let mut paper = fixture("2401.00001v1", "Published");
+        paper.journal_ref = Some("Nature 625, 468 (2024)".to_string());
+        let jsonl = serialize_metadata(&[paper], MetadataFormat::Jsonl, false)
+            .expect("Should serialize to JSONL");
+        let record: serde_json::Value =
+            serde_json::from_str(jsonl.trim_end()).expect("Should be valid JSON");
+        assert_eq!(record["journal_ref"], "Nature 625, 468 (2024)");
+        let jsonl = serialize_metadata(
+            &[fixture("2401.00002v1", "Preprint")],
+            MetadataFormat::Jsonl,
+            false,
+        )
+        .expect("Should serialize to JSONL");
+        let record: serde_json::Value =
+            serde_json::from_str(jsonl.trim_end()).expect("Should be valid JSON");
+        assert!(record["journal_ref"].is_null());
+        // Metadata written before the field existed can still be read
+        let mut record = record;
+        record
+            .as_object_mut()
+            .expect("Should be an object")
+            .remove("journal_ref");
+        let parsed: SerDesArxiv = serde_json::from_value(record).expect("Should deserialize");
+        assert_eq!(parsed.journal_ref, None);
+    }
+
+    #[test]
+    fn test_serialize_doi() {
+        let mut paper = fixture("2401.00001v1", "Published");
+        paper.doi = Some("10.1038/s41586-023-06924-6".to_string());
+        let papers = [paper, fixture("2401.00002v1", "Preprint")];
+        let jsonl = serialize_metadata(&papers, MetadataFormat::Jsonl, false)
+            .expect("Should serialize to JSONL");
+        let records: Vec<serde_json::Value> = jsonl
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("Should be valid JSON"))
+            .collect();
+        assert_eq!(records[0]["doi"], "10.1038/s41586-023-06924-6");
+        // Papers without a DOI keep the key, with a null value
+        assert!(
+            records[1]
+                .as_object()
+                .expect("Should be an object")
+                .contains_key("doi")
+        );
+        assert!(records[1]["doi"].is_null());
+        let parsed: SerDesArxiv =
+            serde_json::from_value(records[0].clone()).expect("Should deserialize");
+        assert_eq!(parsed.doi.as_deref(), Some("10.1038/s41586-023-06924-6"));
+    }
+
+    #[test]
+    fn test_serialize_metadata_with_summary() {
+        let mut paper = fixture("2401.00001v1", "First");
+        paper.summary = "An abstract.".to_string();
+        let papers = vec![paper];
+        let jsonl = serialize_metadata(&papers, MetadataFormat::Jsonl, true)
+            .expect("Should serialize to JSONL");
+        let record: serde_json::Value =
+            serde_json::from_str(jsonl.trim_end()).expect("Should be valid JSON");
+        assert_eq!(record["summary"], "An abstract.");
+        assert_eq!(record["title"], "First");
+        let parsed: SerDesArxiv =
+            serde_json::from_str(jsonl.trim_end()).expect("Should deserialize");
+        assert_eq!(parsed.summary, "An abstract.");
+        let without_summary = serialize_metadata(&papers, MetadataFormat::Jsonl, false)
+            .expect("Should serialize to JSONL");
+        let parsed: SerDesArxiv =
+            serde_json::from_str(without_summary.trim_end()).expect("Should deserialize");
+        assert_eq!(parsed.summary, "");
+    }
+
+    #[test]
+    fn test_paper_count() {
+        let count = PaperCount {
+            matched: 12,
+            kept: None,
+            capped: false,
+        };
+        assert_eq!(count.count(), 12);
+        assert_eq!(count.to_string(), "12");
+        assert_eq!(count.to_json(), r#"{"count":12}"#);
+        let count = PaperCount {
+            matched: 12,
+            kept: Some(0),
+            capped: false,
+        };
+        assert_eq!(count.count(), 0);
+        assert_eq!(count.to_string(), "0 (12 before filtering)");
+        assert_eq!(count.to_json(), r#"{"count":0,"matched":12}"#);
+        let count = PaperCount {
+            matched: 10_000,
+            kept: Some(40),
+            capped: true,
+        };
+        assert_eq!(
+            count.to_json(),
+            r#"{"capped":true,"count":40,"matched":10000}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn integration_test_count_papers() {
+        let options = DownloadOptions {
+            all: true,
+            quiet: true,
+            ..Default::default()
+        };
+        let count = count_papers("ti:\"attention is all you need\"", 150, &options)
+            .await
+            .expect("Should count papers");
+        assert!(count.matched > 0);
+        assert!(count.kept.is_none());
+        assert!(!Path::new(JSON_FILE).exists());
+    }
+
+    #[tokio::test]
+    async fn integration_test_count_papers_license() {
+        // 1706.03762 is under arXiv's non-exclusive license, which the feed
+        // does not report: counting must resolve it like a real run
+        let options = DownloadOptions {
+            id_list: vec!["1706.03762".to_string()],
+            filters: PaperFilters {
+                licenses: vec![LicenseFamily::Arxiv],
+                strict_license: true,
+                ..Default::default()
+            },
+            quiet: true,
+            ..Default::default()
+        };
+        let count = count_papers("", 1, &options)
+            .await
+            .expect("Should count papers");
+        assert_eq!(count.kept, Some(1));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_wait_for_api() {
+        let delay = Duration::from_millis(200);
+        wait_for_api(delay).await;
+        let start = Instant::now();
+        wait_for_api(delay).await;
+        wait_for_api(delay).await;
+        assert!(start.elapsed() >= Duration::from_millis(350));
+        let start = Instant::now();
+        wait_for_api(Duration::ZERO).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_with_version() {
+        assert_eq!(
+            with_version("https://arxiv.org/pdf/2401.12345v3", 1),
+            "https://arxiv.org/pdf/2401.12345v1"
+        );
+        assert_eq!(
+            with_version("https://arxiv.org/html/2401.12345v3", 2),
+            "https://arxiv.org/html/2401.12345v2"
+        );
+        assert_eq!(
+            with_version("http://arxiv.org/abs/2401.12345", 2),
+            "http://arxiv.org/abs/2401.12345v2"
+        );
+        assert_eq!(
+            with_version("http://arxiv.org/pdf/cs/0112017v2", 1),
+            "http://arxiv.org/pdf/cs/0112017v1"
+        );
+        assert_eq!(
+            with_version("http://arxiv.org/pdf/hep-th/9901001v1.pdf", 12),
+            "http://arxiv.org/pdf/hep-th/9901001v12.pdf"
+        );
+        assert_eq!(with_version("2401.12345v10", 3), "2401.12345v3");
+    }
+
+    #[test]
+    fn test_select_version() {
+        let mut paper = fixture("2401.12345v3", "Versioned");
+        paper.pdf_url = "https://arxiv.org/pdf/2401.12345v3".to_string();
+        paper.select_version(1);
+        assert_eq!(paper.id, "http://arxiv.org/abs/2401.12345v1");
+        assert_eq!(paper.pdf_url, "https://arxiv.org/pdf/2401.12345v1");
+        assert_eq!(paper.html_url, "");
+        assert_eq!(
+            PdfNotFound::new(&paper.id).to_string(),
+            "version v1 not found for 2401.12345"
+        );
+        assert_eq!(
+            PdfNotFound::new("cs/0112017").to_string(),
+            "PDF not found for cs/0112017"
+        );
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        assert_eq!(backoff_delay(1), Duration::from_secs(1));
+        assert_eq!(backoff_delay(2), Duration::from_secs(2));
+        assert_eq!(backoff_delay(3), Duration::from_secs(4));
+        assert_eq!(backoff_delay(4), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        let html = b"<html><body><h1>Title</h1><p>Some <span>plain</span> text.</p></body></html>";
+        let text = html_to_text(html).expect("Should convert HTML to text");
+        assert!(text.contains("Title"));
+        assert!(text.contains("Some plain text."));
+        assert!(!text.contains("<p>"));
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        let bar = progress_bar(3, "Processing", false).expect("Should create progress bar");
+        assert_eq!(bar.length(), Some(3));
+        assert_eq!(bar.prefix(), "Processing");
+        let hidden = progress_bar(3, "Processing", true).expect("Should create progress bar");
+        assert!(hidden.is_hidden());
+    }
+
+    #[test]
+    fn test_is_non_empty_file() {
+        let dir = test_output_dir("test_is_non_empty_file");
+        fs::create_dir_all(&dir).expect("Should be able to create directory");
+        let empty_path = dir.join("empty.pdf");
+        let full_path = dir.join("full.pdf");
+        fs::write(&empty_path, "").expect("Should write empty file");
+        fs::write(&full_path, "%PDF-1.5").expect("Should write file");
+        assert!(!is_non_empty_file(&empty_path));
+        assert!(is_non_empty_file(&full_path));
+        assert!(!is_non_empty_file(&dir.join("missing.pdf")));
+        assert!(!is_non_empty_file(&dir));
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+
+    #[test]
+    fn test_unique_file_names() {
+        let papers = [
+            fixture("2401.00001", "Same title"),
+            fixture("2401.00002", "Other title"),
+            fixture("2401.00003", "Same title"),
+            fixture("2401.00004", "Same title_1"),
+            fixture("2401.00005", "SAME TITLE"),
+        ];
+        assert_eq!(
+            unique_file_names(
+                &papers,
+                &FilenameTemplate::default(),
+                DEFAULT_MAX_FILENAME_LENGTH,
+                &HashMap::new()
+            ),
+            vec![
+                "Same title",
+                "Other title",
+                "Same title_1",
+                "Same title_1_1",
+                "SAME TITLE_2"
+            ]
+        );
+        // Names of files that earlier runs wrote for other papers are skipped
+        let owners = manifest_file_owners(&serde_json::from_str(
+            r#"{"2401.00009": {"id": "2401.00009v1", "title": "Same title",
+                "pdf": {"path": "pdfs/Same title.pdf", "bytes": 8, "downloaded_at": ""},
+                "fulltext": {"path": "texts/Other title.fulltext.txt", "bytes": 8, "downloaded_at": ""}}}"#,
+        )
+        .expect("Should parse manifest"));
+        assert_eq!(
+            unique_file_names(
+                &papers[..2],
+                &FilenameTemplate::default(),
+                DEFAULT_MAX_FILENAME_LENGTH,
+                &owners
+            ),
+            vec!["Same title_1", "Other title_1"]
+        );
+        assert_eq!(
+            unique_file_names(
+                &[fixture("2401.00009v2", "Same title")],
+                &FilenameTemplate::default(),
+                DEFAULT_MAX_FILENAME_LENGTH,
+                &owners
+            ),
+            vec!["Same title"]
+        );
+        let template: FilenameTemplate = "{id}".parse().expect("Should parse template");
+        assert_eq!(
+            unique_file_names(
+                &papers[..2],
+                &template,
+                DEFAULT_MAX_FILENAME_LENGTH,
+                &HashMap::new()
+            ),
+            vec!["2401.00001", "2401.00002"]
+        );
+    }
+
+    #[test]
+    fn test_unique_file_names_max_length() {
+        let long_title = "A very long title ".repeat(10);
+        let papers = [
+            fixture("2401.00001", &long_title),
+            fixture("2401.00002", &long_title),
+        ];
+        let file_names =
+            unique_file_names(&papers, &FilenameTemplate::default(), 40, &HashMap::new());
+        // Room is left for the longest extension, .fulltext.txt
+        assert_eq!(file_names[0], "A very long title A very lo");
+        assert_eq!(file_names[1], "A very long title A very _1");
+        for name in &file_names {
+            assert!(format!("{}{}", name, LONGEST_EXTENSION).chars().count() <= 40);
+        }
+        let papers = [fixture("2401.00003", &long_title.repeat(2))];
+        let file_names =
+            unique_file_names(&papers, &FilenameTemplate::default(), 200, &HashMap::new());
+        assert_eq!(file_names[0].chars().count(), 200 - LONGEST_EXTENSION.len());
+    }
+
+    #[test]
+    fn test_sanitize_file_name_unicode() {
+        let accented =
+            "Étude théorique des équations différentielles à coefficients variés ".repeat(5);
+        let sanitized = sanitize_filename(&accented, DEFAULT_MAX_FILENAME_LENGTH);
+        assert_eq!(sanitized.chars().count(), 200);
+        assert!(accented.starts_with(&sanitized));
+        let cjk = "大規模言語モデルにおける推論能力の評価".repeat(20);
+        let sanitized = sanitize_filename(&cjk, DEFAULT_MAX_FILENAME_LENGTH);
+        assert_eq!(sanitized.chars().count(), 200);
+        assert!(cjk.starts_with(&sanitized));
+        assert_eq!(
+            sanitize_filename("深層学習: 概要", DEFAULT_MAX_FILENAME_LENGTH),
+            "深層学習_ 概要"
+        );
+    }
+
     #[test]
     fn test_sanitize_file_name() {
         let to_replace = "x < y | x > y? better: /, \"\\\" or *".to_string();
         let to_truncate = "Sed ut perspiciatis unde omnis iste natus error sit voluptatem accusantium doloremque laudantium, totam rem aperiam, eaque ipsa quae ab illo inventore veritatis et quasi architecto beatae vitae dictas sunt".to_string();
         assert_eq!(
-            sanitize_filename(&to_replace),
+            sanitize_filename(&to_replace, DEFAULT_MAX_FILENAME_LENGTH),
             "x _ y _ x _ y_ better_ _, ___ or _"
         );
         assert_eq!(
-            sanitize_filename(&to_truncate),
+            sanitize_filename(&to_truncate, DEFAULT_MAX_FILENAME_LENGTH),
             "Sed ut perspiciatis unde omnis iste natus error sit voluptatem accusantium doloremque laudantium, totam rem aperiam, eaque ipsa quae ab illo inventore veritatis et quasi architecto beatae vitae dictas"
         );
     }
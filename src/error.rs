@@ -0,0 +1,83 @@
+use thiserror::Error;
+
+/// Error returned by the public functions of the crate, so that callers can
+/// tell failures apart, e.g. to retry only on `Http`
+#[derive(Debug, Error)]
+pub enum ArxivCliError {
+    /// A request to arXiv failed: network error, timeout or error status
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    /// A file could not be read or written
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// An arXiv id, a feed or a metadata file could not be parsed
+    #[error("{0}")]
+    Parse(String),
+    /// No paper matched the query, with `fail_on_empty` set
+    #[error("no papers matched the query")]
+    EmptyQuery,
+    /// The PDF of the paper, or the requested version of it, does not exist
+    #[error("{0}")]
+    NotFound(String),
+    /// A download stalled for longer than the timeout
+    #[error("{0}")]
+    Timeout(String),
+    /// A downloaded PDF is truncated or not a PDF at all
+    #[error("{0}")]
+    InvalidPdf(String),
+    /// Any other failure, e.g. a batch where some queries failed
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<serde_json::Error> for ArxivCliError {
+    fn from(error: serde_json::Error) -> Self {
+        match error.io_error_kind() {
+            Some(kind) => ArxivCliError::Io(std::io::Error::new(kind, error)),
+            None => ArxivCliError::Parse(error.to_string()),
+        }
+    }
+}
+
+/// Recover the kind of the errors raised through `anyhow` inside the crate
+impl From<anyhow::Error> for ArxivCliError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<ArxivCliError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<reqwest::Error>() {
+            Ok(error) => return ArxivCliError::Http(error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<std::io::Error>() {
+            Ok(error) => return ArxivCliError::Io(error),
+            Err(error) => error,
+        };
+        match error.downcast::<serde_json::Error>() {
+            Ok(error) => error.into(),
+            Err(error) => ArxivCliError::Other(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_anyhow() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        let error = ArxivCliError::from(anyhow::Error::from(io));
+        assert!(matches!(error, ArxivCliError::Io(_)));
+        assert_eq!(error.to_string(), "missing");
+        let error = ArxivCliError::from(anyhow::Error::from(ArxivCliError::EmptyQuery));
+        assert!(matches!(error, ArxivCliError::EmptyQuery));
+        let json = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let error = ArxivCliError::from(anyhow::Error::from(json));
+        assert!(matches!(error, ArxivCliError::Parse(_)));
+        let error = ArxivCliError::from(anyhow::anyhow!("2 of 3 queries failed"));
+        assert!(matches!(error, ArxivCliError::Other(_)));
+        assert_eq!(error.to_string(), "2 of 3 queries failed");
+    }
+}
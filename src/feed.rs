@@ -0,0 +1,226 @@
+use arxiv::ArxivQuery;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::download::SerDesArxiv;
+use crate::error::ArxivCliError;
+use crate::http::client;
+
+/// Papers of one page of results of the arXiv API, read from its Atom feed
+/// rather than through the arxiv crate, which drops fields such as the
+/// journal reference and the DOI
+#[derive(Default)]
+pub struct Feed {
+    /// Number of results of the query across all pages, if reported
+    pub total_results: Option<usize>,
+    pub papers: Vec<SerDesArxiv>,
+}
+
+/// Run the query against the arXiv API
+pub async fn fetch_feed(query: &ArxivQuery) -> anyhow::Result<Feed> {
+    let body = client()
+        .get(query.to_url())
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    parse_feed(&body)
+}
+
+/// Parse the Atom feed returned by the arXiv API
+pub fn parse_feed(xml: &str) -> anyhow::Result<Feed> {
+    let mut feed = Feed::default();
+    let mut paper: Option<SerDesArxiv> = None;
+    let mut text = String::new();
+    for event in EventReader::from_str(xml) {
+        match event.map_err(|e| ArxivCliError::Parse(format!("invalid arXiv feed: {}", e)))? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                text.clear();
+                let attribute = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|attribute| attribute.name.local_name == key)
+                        .map(|attribute| attribute.value.clone())
+                };
+                match (name.local_name.as_str(), paper.as_mut()) {
+                    ("entry", _) => paper = Some(SerDesArxiv::default()),
+                    ("link", Some(paper)) if attribute("title").as_deref() == Some("pdf") => {
+                        paper.pdf_url = https_url(&attribute("href").unwrap_or_default());
+                        paper.html_url = paper.pdf_url.replacen("/pdf/", "/html/", 1);
+                    }
+                    ("link", Some(paper)) if attribute("rel").as_deref() == Some("license") => {
+                        paper.license = attribute("href");
+                    }
+                    ("primary_category", Some(paper)) => {
+                        paper.primary_category = attribute("term").unwrap_or_default();
+                    }
+                    ("category", Some(paper)) => {
+                        paper.categories.extend(attribute("term"));
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::Characters(chunk) | XmlEvent::CData(chunk) => text.push_str(&chunk),
+            XmlEvent::EndElement { name } => {
+                let value = text.trim().to_string();
+                match (name.local_name.as_str(), paper.as_mut()) {
+                    ("entry", Some(_)) => feed.papers.extend(paper.take()),
+                    ("id", Some(paper)) => paper.id = value,
+                    ("updated", Some(paper)) => paper.updated = value,
+                    ("published", Some(paper)) => paper.published = value,
+                    ("title", Some(paper)) => paper.title = value,
+                    ("summary", Some(paper)) => paper.summary = value,
+                    ("name", Some(paper)) => paper.authors.push(value),
+                    ("comment", Some(paper)) => paper.comment = Some(value),
+                    ("journal_ref", Some(paper)) => paper.journal_ref = Some(value),
+                    ("doi", Some(paper)) => paper.doi = Some(value),
+                    ("totalResults", None) => feed.total_results = value.parse().ok(),
+                    _ => {}
+                }
+                text.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(feed)
+}
+
+/// The feed links to http:// URLs, which arxiv.org redirects to https://. A
+/// doubled `httpss://` scheme, as the arxiv crate used to produce, is made
+/// https:// too; only the scheme is touched, not the rest of the URL.
+fn https_url(url: &str) -> String {
+    match url
+        .strip_prefix("http://")
+        .or_else(|| url.strip_prefix("httpss://"))
+    {
+        Some(rest) => format!("https://{}", rest),
+        None => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title type="html">ArXiv Query: search_query=ti:attention</title>
+  <id>http://arxiv.org/api/cHxbiOdZaP56ODnBPIenZhzg5f8</id>
+  <updated>2024-01-16T00:00:00-05:00</updated>
+  <opensearch:totalResults xmlns:opensearch="http://a9.com/-/spec/opensearch/1.1/">1234</opensearch:totalResults>
+  <entry>
+    <id>http://arxiv.org/abs/1706.03762v7</id>
+    <updated>2023-08-02T00:41:18Z</updated>
+    <published>2017-06-12T17:57:34Z</published>
+    <title>Attention Is All
+  You Need</title>
+    <summary>  The dominant sequence transduction models are based on complex recurrent &amp; convolutional networks.
+</summary>
+    <author>
+      <name>Ashish Vaswani</name>
+    </author>
+    <author>
+      <name>Noam Shazeer</name>
+    </author>
+    <arxiv:comment xmlns:arxiv="http://arxiv.org/schemas/atom">15 pages, 5 figures</arxiv:comment>
+    <arxiv:journal_ref xmlns:arxiv="http://arxiv.org/schemas/atom">Advances in Neural Information Processing Systems 30 (2017)</arxiv:journal_ref>
+    <arxiv:doi xmlns:arxiv="http://arxiv.org/schemas/atom">10.48550/arXiv.1706.03762</arxiv:doi>
+    <link title="doi" href="http://dx.doi.org/10.48550/arXiv.1706.03762" rel="related"/>
+    <link href="http://arxiv.org/abs/1706.03762v7" rel="alternate" type="text/html"/>
+    <link title="pdf" href="http://arxiv.org/pdf/1706.03762v7" rel="related" type="application/pdf"/>
+    <link href="http://creativecommons.org/licenses/by/4.0/" rel="license"/>
+    <arxiv:primary_category xmlns:arxiv="http://arxiv.org/schemas/atom" term="cs.CL" scheme="http://arxiv.org/schemas/atom"/>
+    <category term="cs.CL" scheme="http://arxiv.org/schemas/atom"/>
+    <category term="cs.LG" scheme="http://arxiv.org/schemas/atom"/>
+  </entry>
+  <entry>
+    <id>http://arxiv.org/abs/cs/0112017v1</id>
+    <updated>2001-12-14T00:00:00Z</updated>
+    <published>2001-12-14T00:00:00Z</published>
+    <title>An old-style paper</title>
+    <summary>Abstract.</summary>
+    <author>
+      <name>Jane Doe</name>
+    </author>
+    <link title="pdf" href="https://arxiv.org/pdf/cs/0112017v1" rel="related" type="application/pdf"/>
+    <arxiv:primary_category xmlns:arxiv="http://arxiv.org/schemas/atom" term="cs.AI" scheme="http://arxiv.org/schemas/atom"/>
+    <category term="cs.AI" scheme="http://arxiv.org/schemas/atom"/>
+  </entry>
+</feed>
+"#;
+
+    #[test]
+    fn test_parse_feed() {
+        let feed = parse_feed(FEED).expect("Should parse feed");
+        assert_eq!(feed.total_results, Some(1234));
+        assert_eq!(feed.papers.len(), 2);
+        let paper = &feed.papers[0];
+        assert_eq!(paper.id, "http://arxiv.org/abs/1706.03762v7");
+        assert_eq!(paper.updated, "2023-08-02T00:41:18Z");
+        assert_eq!(paper.published, "2017-06-12T17:57:34Z");
+        assert_eq!(paper.title, "Attention Is All\n  You Need");
+        assert_eq!(
+            paper.summary,
+            "The dominant sequence transduction models are based on complex recurrent & convolutional networks."
+        );
+        assert_eq!(paper.authors, vec!["Ashish Vaswani", "Noam Shazeer"]);
+        assert_eq!(paper.primary_category, "cs.CL");
+        assert_eq!(paper.categories, vec!["cs.CL", "cs.LG"]);
+        assert_eq!(paper.pdf_url, "https://arxiv.org/pdf/1706.03762v7");
+        assert_eq!(paper.html_url, "https://arxiv.org/html/1706.03762v7");
+        assert_eq!(paper.comment.as_deref(), Some("15 pages, 5 figures"));
+        assert_eq!(
+            paper.journal_ref.as_deref(),
+            Some("Advances in Neural Information Processing Systems 30 (2017)")
+        );
+        assert_eq!(paper.doi.as_deref(), Some("10.48550/arXiv.1706.03762"));
+        assert_eq!(
+            paper.license.as_deref(),
+            Some("http://creativecommons.org/licenses/by/4.0/")
+        );
+        let paper = &feed.papers[1];
+        assert_eq!(paper.id, "http://arxiv.org/abs/cs/0112017v1");
+        assert_eq!(paper.pdf_url, "https://arxiv.org/pdf/cs/0112017v1");
+        assert_eq!(paper.comment, None);
+        assert_eq!(paper.journal_ref, None);
+        assert_eq!(paper.doi, None);
+        assert_eq!(paper.license, None);
+    }
+
+    #[test]
+    fn test_parse_invalid_feed() {
+        let err = parse_feed("<feed><entry></feed>")
+            .err()
+            .expect("Should reject feed");
+        assert!(err.to_string().contains("invalid arXiv feed"));
+        let feed = parse_feed("<feed></feed>").expect("Should parse empty feed");
+        assert!(feed.papers.is_empty());
+        assert_eq!(feed.total_results, None);
+    }
+
+    #[test]
+    fn test_https_url() {
+        assert_eq!(
+            https_url("http://arxiv.org/pdf/2401.12345v1"),
+            "https://arxiv.org/pdf/2401.12345v1"
+        );
+        assert_eq!(
+            https_url("https://arxiv.org/pdf/2401.12345v1"),
+            "https://arxiv.org/pdf/2401.12345v1"
+        );
+        assert_eq!(
+            https_url("httpss://arxiv.org/pdf/2401.12345v1"),
+            "https://arxiv.org/pdf/2401.12345v1"
+        );
+        assert_eq!(
+            https_url("https://example.org/httpss/paper"),
+            "https://example.org/httpss/paper"
+        );
+        assert_eq!(
+            https_url("http://example.org/httpss/paper"),
+            "https://example.org/httpss/paper"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::{fmt, str::FromStr};
+
+use crate::download::{SerDesArxiv, sanitize_filename};
+
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{title}";
+const PLACEHOLDERS: [&str; 6] = [
+    "id",
+    "title",
+    "first_author",
+    "published",
+    "year",
+    "primary_category",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(&'static str),
+}
+
+/// Template for the names of the PDF and text files saved for a paper, with
+/// placeholders such as `{id}` or `{title}` resolved from the paper metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilenameTemplate {
+    template: String,
+    segments: Vec<Segment>,
+}
+
+impl FilenameTemplate {
+    /// File name, without extension, for `paper`, truncated to `max_length`
+    /// characters
+    pub fn render(&self, paper: &SerDesArxiv, max_length: usize) -> String {
+        let mut name = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => name.push_str(text),
+                Segment::Placeholder(placeholder) => {
+                    name.push_str(&placeholder_value(placeholder, paper))
+                }
+            }
+        }
+        sanitize_filename(&name, max_length)
+    }
+}
+
+fn placeholder_value(placeholder: &str, paper: &SerDesArxiv) -> String {
+    match placeholder {
+        "id" => paper
+            .id
+            .split_once("/abs/")
+            .map_or(paper.id.as_str(), |(_, id)| id)
+            .to_string(),
+        "title" => paper.title.clone(),
+        "first_author" => paper
+            .authors
+            .first()
+            .cloned()
+            .unwrap_or_else(|| "unknown".to_string()),
+        // Only the date part of e.g. 2024-01-15T18:00:00Z
+        "published" => paper.published.chars().take(10).collect(),
+        "year" => paper.published.chars().take(4).collect(),
+        "primary_category" => paper.primary_category.clone(),
+        _ => unreachable!("placeholders are validated when parsing the template"),
+    }
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        DEFAULT_FILENAME_TEMPLATE
+            .parse()
+            .expect("default template is valid")
+    }
+}
+
+impl fmt::Display for FilenameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+impl FromStr for FilenameTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = vec![];
+        let mut rest = s;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                segments.push(Segment::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..].find('}').ok_or_else(|| {
+                anyhow::anyhow!("invalid filename template '{}': unclosed '{{'", s)
+            })? + open;
+            let name = &rest[open + 1..close];
+            let placeholder = PLACEHOLDERS
+                .iter()
+                .find(|placeholder| **placeholder == name)
+                .ok_or_else(|| {
+                    let expected: Vec<String> = PLACEHOLDERS
+                        .iter()
+                        .map(|placeholder| format!("{{{}}}", placeholder))
+                        .collect();
+                    anyhow::anyhow!(
+                        "invalid filename template '{}': unknown placeholder '{{{}}}', expected one of {}",
+                        s,
+                        name,
+                        expected.join(", ")
+                    )
+                })?;
+            segments.push(Segment::Placeholder(placeholder));
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+        if !segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Placeholder(_)))
+        {
+            anyhow::bail!(
+                "invalid filename template '{}': expected at least one placeholder, e.g. {{id}} or {{title}}",
+                s
+            );
+        }
+        Ok(Self {
+            template: s.to_string(),
+            segments,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::download::DEFAULT_MAX_FILENAME_LENGTH;
+
+    fn paper() -> SerDesArxiv {
+        SerDesArxiv {
+            id: "http://arxiv.org/abs/1706.03762v7".to_string(),
+            updated: "2023-08-02T00:41:18Z".to_string(),
+            published: "2017-06-12T17:57:34Z".to_string(),
+            title: "Attention Is All You Need".to_string(),
+            authors: vec!["Ashish Vaswani".to_string(), "Noam Shazeer".to_string()],
+            primary_category: "cs.CL".to_string(),
+            categories: vec!["cs.CL".to_string(), "cs.LG".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_default_template() {
+        assert_eq!(
+            FilenameTemplate::default().render(&paper(), DEFAULT_MAX_FILENAME_LENGTH),
+            "Attention Is All You Need"
+        );
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let template: FilenameTemplate = "{id}_{title}".parse().expect("Should parse template");
+        assert_eq!(
+            template.render(&paper(), DEFAULT_MAX_FILENAME_LENGTH),
+            "1706.03762v7_Attention Is All You Need"
+        );
+        let template: FilenameTemplate = "{published}-{first_author} [{primary_category}]"
+            .parse()
+            .expect("Should parse template");
+        assert_eq!(
+            template.render(&paper(), DEFAULT_MAX_FILENAME_LENGTH),
+            "2017-06-12-Ashish Vaswani [cs.CL]"
+        );
+        let template: FilenameTemplate = "{id}_{year}_{first_author}_{title}"
+            .parse()
+            .expect("Should parse template");
+        assert_eq!(
+            template.render(&paper(), DEFAULT_MAX_FILENAME_LENGTH),
+            "1706.03762v7_2017_Ashish Vaswani_Attention Is All You Need"
+        );
+    }
+
+    #[test]
+    fn test_render_long_title() {
+        let mut paper = paper();
+        paper.title = "Attention ".repeat(100);
+        let template: FilenameTemplate = "{year}_{title}".parse().expect("Should parse template");
+        let name = template.render(&paper, 64);
+        assert!(name.chars().count() <= 64);
+        assert!(name.starts_with("2017_Attention Attention"));
+    }
+
+    #[test]
+    fn test_render_sanitizes() {
+        let mut paper = paper();
+        paper.id = "http://arxiv.org/abs/cs/0112017v1".to_string();
+        paper.authors = vec![];
+        let template: FilenameTemplate = "{id}: {first_author}"
+            .parse()
+            .expect("Should parse template");
+        assert_eq!(
+            template.render(&paper, DEFAULT_MAX_FILENAME_LENGTH),
+            "cs_0112017v1_ unknown"
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_templates() {
+        let err = "{id}_{month}"
+            .parse::<FilenameTemplate>()
+            .expect_err("Should reject unknown placeholder");
+        assert!(err.to_string().contains("unknown placeholder '{month}'"));
+        assert!(
+            err.to_string()
+                .contains("{published}, {year}, {primary_category}")
+        );
+        let err = "{id"
+            .parse::<FilenameTemplate>()
+            .expect_err("Should reject unclosed placeholder");
+        assert!(err.to_string().contains("unclosed"));
+        assert!("paper".parse::<FilenameTemplate>().is_err());
+        assert!("".parse::<FilenameTemplate>().is_err());
+    }
+}
@@ -0,0 +1,538 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate};
+use log::warn;
+use regex::Regex;
+
+use crate::bibtex::normalize_whitespace;
+use crate::download::SerDesArxiv;
+use crate::license::LicenseFamily;
+
+/// Client-side filters applied to the fetched papers before anything is saved
+#[derive(Debug, Clone, Default)]
+pub struct PaperFilters {
+    /// Categories whose papers are dropped
+    pub exclude_categories: Vec<String>,
+    /// Also drop papers that are only cross-listed in an excluded category
+    pub exclude_cross_listed: bool,
+    /// Keep only papers whose primary category is one of these
+    pub primary_categories: Vec<String>,
+    /// Drop papers whose title or summary contains one of these phrases, ignoring case
+    pub exclude_keywords: Vec<String>,
+    /// Keep only papers whose comment contains one of these texts, ignoring case
+    pub comment_contains: Vec<String>,
+    /// Keep only papers whose title matches this pattern
+    pub title_regex: Option<Regex>,
+    /// Keep only papers whose summary, with whitespace collapsed, matches this pattern
+    pub abstract_regex: Option<Regex>,
+    /// Keep only papers with at least this many authors
+    pub min_authors: Option<usize>,
+    /// Keep only papers with at most this many authors
+    pub max_authors: Option<usize>,
+    /// Keep only papers last updated on or after this date
+    pub updated_after: Option<NaiveDate>,
+    /// Keep only papers with a journal reference
+    pub with_journal_ref: bool,
+    /// Keep only papers with a DOI
+    pub with_doi: bool,
+    /// Keep only papers under one of these licenses
+    pub licenses: Vec<LicenseFamily>,
+    /// Also drop the papers whose license is unknown, instead of keeping them
+    pub strict_license: bool,
+}
+
+impl PaperFilters {
+    /// Whether any filter is set, in which case more papers than requested are fetched
+    pub fn is_active(&self) -> bool {
+        !self.exclude_categories.is_empty()
+            || !self.primary_categories.is_empty()
+            || !self.exclude_keywords.is_empty()
+            || !self.comment_contains.is_empty()
+            || self.title_regex.is_some()
+            || self.abstract_regex.is_some()
+            || self.min_authors.is_some()
+            || self.max_authors.is_some()
+            || self.updated_after.is_some()
+            || self.with_journal_ref
+            || self.with_doi
+            || !self.licenses.is_empty()
+    }
+
+    /// Name of the first filter rejecting the paper, if any
+    pub fn rejection(&self, paper: &SerDesArxiv) -> Option<&'static str> {
+        if self.is_excluded_category(paper) {
+            return Some("excluded category");
+        }
+        if self.has_excluded_keyword(paper) {
+            return Some("keyword filters");
+        }
+        if !self.primary_categories.is_empty()
+            && !self
+                .primary_categories
+                .iter()
+                .any(|category| paper.primary_category.eq_ignore_ascii_case(category))
+        {
+            return Some("primary category");
+        }
+        if !self.comment_contains.is_empty() && !self.has_matching_comment(paper) {
+            return Some("comment");
+        }
+        if let Some(title_regex) = &self.title_regex
+            && !title_regex.is_match(&paper.title)
+        {
+            return Some("title pattern");
+        }
+        // Summaries are wrapped over several lines, which would break phrases
+        if let Some(abstract_regex) = &self.abstract_regex
+            && !abstract_regex.is_match(&normalize_whitespace(&paper.summary))
+        {
+            return Some("abstract pattern");
+        }
+        // Kept apart in the report, to tell large collaborations from short
+        // author lists
+        let authors = paper.authors.len();
+        if self.min_authors.is_some_and(|min| authors < min) {
+            return Some("min authors");
+        }
+        if self.max_authors.is_some_and(|max| authors > max) {
+            return Some("max authors");
+        }
+        if self.with_journal_ref
+            && paper
+                .journal_ref
+                .as_deref()
+                .is_none_or(|journal_ref| journal_ref.trim().is_empty())
+        {
+            return Some("journal reference");
+        }
+        if self.with_doi && paper.doi.as_deref().is_none_or(|doi| doi.trim().is_empty()) {
+            return Some("DOI");
+        }
+        if !self.licenses.is_empty() {
+            match paper.license.as_deref().and_then(LicenseFamily::from_url) {
+                Some(family) if self.licenses.contains(&family) => {}
+                Some(_) => return Some("license"),
+                None if self.strict_license => return Some("license"),
+                None => warn!(
+                    "Keeping {}: unknown license {}",
+                    paper.id,
+                    paper.license.as_deref().unwrap_or("(none)")
+                ),
+            }
+        }
+        if let Some(updated_after) = self.updated_after {
+            match updated_date(&paper.updated) {
+                Some(updated) if updated < updated_after => return Some("update date"),
+                Some(_) => {}
+                None => warn!(
+                    "Keeping {}: cannot read its update date '{}'",
+                    paper.id, paper.updated
+                ),
+            }
+        }
+        None
+    }
+
+    fn is_excluded_category(&self, paper: &SerDesArxiv) -> bool {
+        self.exclude_categories.iter().any(|excluded| {
+            paper.primary_category.eq_ignore_ascii_case(excluded)
+                || (self.exclude_cross_listed
+                    && paper
+                        .categories
+                        .iter()
+                        .any(|category| category.eq_ignore_ascii_case(excluded)))
+        })
+    }
+
+    fn has_excluded_keyword(&self, paper: &SerDesArxiv) -> bool {
+        if self.exclude_keywords.is_empty() {
+            return false;
+        }
+        let title = normalize_whitespace(&paper.title).to_lowercase();
+        let summary = normalize_whitespace(&paper.summary).to_lowercase();
+        self.exclude_keywords.iter().any(|keyword| {
+            let keyword = normalize_whitespace(keyword).to_lowercase();
+            !keyword.is_empty() && (title.contains(&keyword) || summary.contains(&keyword))
+        })
+    }
+
+    fn has_matching_comment(&self, paper: &SerDesArxiv) -> bool {
+        let Some(comment) = &paper.comment else {
+            return false;
+        };
+        let comment = comment.to_lowercase();
+        self.comment_contains
+            .iter()
+            .any(|text| comment.contains(&text.to_lowercase()))
+    }
+
+    /// Drop the rejected papers, returning how many were dropped by each filter
+    pub fn apply(&self, papers: &mut Vec<SerDesArxiv>) -> BTreeMap<&'static str, usize> {
+        let mut dropped = BTreeMap::new();
+        papers.retain(|paper| match self.rejection(paper) {
+            Some(reason) => {
+                *dropped.entry(reason).or_insert(0) += 1;
+                false
+            }
+            None => true,
+        });
+        dropped
+    }
+}
+
+/// Date part of the timestamps returned by arXiv (e.g. 2024-01-15T18:00:00Z),
+/// also accepting timestamps with an offset or a bare date
+fn updated_date(updated: &str) -> Option<NaiveDate> {
+    let updated = updated.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(updated) {
+        return Some(timestamp.date_naive());
+    }
+    NaiveDate::parse_from_str(updated.get(..10)?, "%Y-%m-%d").ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn paper(title: &str, primary_category: &str, categories: &[&str]) -> SerDesArxiv {
+        SerDesArxiv {
+            title: title.to_string(),
+            primary_category: primary_category.to_string(),
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn titles(papers: &[SerDesArxiv]) -> Vec<&str> {
+        papers.iter().map(|paper| paper.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_no_filters() {
+        let filters = PaperFilters::default();
+        assert!(!filters.is_active());
+        let mut papers = vec![paper("a", "cs.CL", &["cs.CL"])];
+        assert!(filters.apply(&mut papers).is_empty());
+        assert_eq!(papers.len(), 1);
+    }
+
+    #[test]
+    fn test_exclude_primary_category() {
+        let filters = PaperFilters {
+            exclude_categories: vec!["eess.AS".to_string(), "cs.CV".to_string()],
+            ..Default::default()
+        };
+        assert!(filters.is_active());
+        let mut papers = vec![
+            paper("speech", "eess.AS", &["eess.AS", "cs.CL"]),
+            paper("nlp", "cs.CL", &["cs.CL"]),
+            paper("vision", "cs.CV", &["cs.CV", "cs.CL"]),
+            paper("cross-listed", "cs.CL", &["cs.CL", "cs.CV"]),
+        ];
+        let dropped = filters.apply(&mut papers);
+        assert_eq!(titles(&papers), vec!["nlp", "cross-listed"]);
+        assert_eq!(dropped.get("excluded category"), Some(&2));
+    }
+
+    #[test]
+    fn test_exclude_cross_listed() {
+        let filters = PaperFilters {
+            exclude_categories: vec!["cs.CV".to_string()],
+            exclude_cross_listed: true,
+            ..Default::default()
+        };
+        let mut papers = vec![
+            paper("nlp", "cs.CL", &["cs.CL"]),
+            paper("cross-listed", "cs.CL", &["cs.CL", "cs.CV"]),
+            paper("vision", "cs.CV", &["cs.CV"]),
+        ];
+        let dropped = filters.apply(&mut papers);
+        assert_eq!(titles(&papers), vec!["nlp"]);
+        assert_eq!(dropped.get("excluded category"), Some(&2));
+    }
+
+    #[test]
+    fn test_comment_contains() {
+        let with_comment = |title: &str, comment: Option<&str>| {
+            let mut paper = paper(title, "cs.CL", &["cs.CL"]);
+            paper.comment = comment.map(|comment| comment.to_string());
+            paper
+        };
+        let filters = PaperFilters {
+            comment_contains: vec!["accepted at".to_string(), "to appear".to_string()],
+            ..Default::default()
+        };
+        assert!(filters.is_active());
+        let mut papers = vec![
+            with_comment("no comment", None),
+            with_comment("accepted", Some("Accepted at ACL 2024")),
+            with_comment("upper case", Some("ACCEPTED AT EMNLP")),
+            with_comment("to appear", Some("12 pages, to appear in NeurIPS")),
+            with_comment("pages only", Some("12 pages, 3 figures")),
+        ];
+        let dropped = filters.apply(&mut papers);
+        assert_eq!(titles(&papers), vec!["accepted", "upper case", "to appear"]);
+        assert_eq!(dropped.get("comment"), Some(&2));
+    }
+
+    #[test]
+    fn test_primary_only() {
+        let filters = PaperFilters {
+            primary_categories: vec!["cs.CL".to_string(), "stat.ML".to_string()],
+            ..Default::default()
+        };
+        assert!(filters.is_active());
+        let mut papers = vec![
+            paper("nlp", "cs.CL", &["cs.CL"]),
+            paper("cross-listed", "cs.LG", &["cs.LG", "cs.CL"]),
+            paper("statistics", "stat.ML", &["stat.ML", "cs.CL"]),
+            paper("lower case", "cs.cl", &["cs.cl"]),
+            paper("vision", "cs.CV", &["cs.CV", "stat.ML"]),
+        ];
+        let dropped = filters.apply(&mut papers);
+        assert_eq!(titles(&papers), vec!["nlp", "statistics", "lower case"]);
+        assert_eq!(dropped.get("primary category"), Some(&2));
+    }
+
+    #[test]
+    fn test_author_count() {
+        let with_authors = |title: &str, count: usize| {
+            let mut paper = paper(title, "cs.CL", &["cs.CL"]);
+            paper.authors = (0..count).map(|i| format!("Author {}", i)).collect();
+            paper
+        };
+        let papers = vec![
+            with_authors("none", 0),
+            with_authors("single", 1),
+            with_authors("pair", 2),
+            with_authors("trio", 3),
+        ];
+        let filters = PaperFilters {
+            max_authors: Some(2),
+            ..Default::default()
+        };
+        assert!(filters.is_active());
+        let mut kept = papers.clone();
+        let dropped = filters.apply(&mut kept);
+        assert_eq!(titles(&kept), vec!["none", "single", "pair"]);
+        assert_eq!(dropped.get("max authors"), Some(&1));
+        let filters = PaperFilters {
+            min_authors: Some(1),
+            max_authors: Some(1),
+            ..Default::default()
+        };
+        let mut kept = papers.clone();
+        let dropped = filters.apply(&mut kept);
+        assert_eq!(titles(&kept), vec!["single"]);
+        assert_eq!(dropped.get("min authors"), Some(&1));
+        assert_eq!(dropped.get("max authors"), Some(&2));
+        let filters = PaperFilters {
+            min_authors: Some(3),
+            ..Default::default()
+        };
+        let mut kept = papers;
+        let dropped = filters.apply(&mut kept);
+        assert_eq!(titles(&kept), vec!["trio"]);
+        assert_eq!(dropped.get("min authors"), Some(&3));
+    }
+
+    #[test]
+    fn test_title_regex() {
+        let papers = vec![
+            paper("A Benchmark for Reasoning", "cs.CL", &["cs.CL"]),
+            paper("Scaling Laws Revisited", "cs.CL", &["cs.CL"]),
+            paper("dataset: a new corpus", "cs.CL", &["cs.CL"]),
+            paper("Towards a better DATASET", "cs.CL", &["cs.CL"]),
+        ];
+        let filters_for = |pattern: &str| PaperFilters {
+            title_regex: Some(Regex::new(pattern).expect("Should compile pattern")),
+            ..Default::default()
+        };
+        let filters = filters_for("(?i)benchmark|dataset");
+        assert!(filters.is_active());
+        let mut kept = papers.clone();
+        let dropped = filters.apply(&mut kept);
+        assert_eq!(
+            titles(&kept),
+            vec![
+                "A Benchmark for Reasoning",
+                "dataset: a new corpus",
+                "Towards a better DATASET"
+            ]
+        );
+        assert_eq!(dropped.get("title pattern"), Some(&1));
+        let mut kept = papers.clone();
+        filters_for("^(?i)dataset").apply(&mut kept);
+        assert_eq!(titles(&kept), vec!["dataset: a new corpus"]);
+        let mut kept = papers.clone();
+        filters_for("DATASET$").apply(&mut kept);
+        assert_eq!(titles(&kept), vec!["Towards a better DATASET"]);
+        let mut kept = papers;
+        let dropped = filters_for("^quantum").apply(&mut kept);
+        assert!(kept.is_empty());
+        assert_eq!(dropped.get("title pattern"), Some(&4));
+    }
+
+    #[test]
+    fn test_abstract_regex() {
+        let with_summary = |title: &str, summary: &str| {
+            let mut paper = paper(title, "cs.LG", &["cs.LG"]);
+            paper.summary = summary.to_string();
+            paper
+        };
+        let mut papers = vec![
+            with_summary(
+                "wrapped",
+                "We train a latent diffusion\n  model on images.\nResults are strong.",
+            ),
+            with_summary("plural", "Diffusion models are studied here."),
+            with_summary("prefix", "A new multi-diffusion modeling approach."),
+            with_summary("unrelated", "We study\ngraph neural networks."),
+        ];
+        let filters = PaperFilters {
+            abstract_regex: Some(
+                Regex::new(r"(?i)\bdiffusion models?\b").expect("Should compile pattern"),
+            ),
+            ..Default::default()
+        };
+        assert!(filters.is_active());
+        let dropped = filters.apply(&mut papers);
+        assert_eq!(titles(&papers), vec!["wrapped", "plural"]);
+        assert_eq!(dropped.get("abstract pattern"), Some(&2));
+    }
+
+    #[test]
+    fn test_exclude_keywords() {
+        let with_summary = |title: &str, summary: &str| {
+            let mut paper = paper(title, "cs.CL", &["cs.CL"]);
+            paper.summary = summary.to_string();
+            paper
+        };
+        let filters = PaperFilters {
+            exclude_keywords: vec!["survey".to_string(), "literature  review".to_string()],
+            ..Default::default()
+        };
+        assert!(filters.is_active());
+        let mut papers = vec![
+            with_summary("A Survey of LLMs", "We cover many models."),
+            with_summary("Scaling study", "This literature\n review covers scaling."),
+            with_summary("New method", "We propose a new method."),
+            with_summary(
+                "Another method",
+                "Unlike prior SURVEYS, we run experiments.",
+            ),
+        ];
+        let dropped = filters.apply(&mut papers);
+        assert_eq!(titles(&papers), vec!["New method"]);
+        assert_eq!(dropped.get("keyword filters"), Some(&3));
+    }
+
+    #[test]
+    fn test_updated_after() {
+        let filters = PaperFilters {
+            updated_after: NaiveDate::from_ymd_opt(2024, 3, 1),
+            ..Default::default()
+        };
+        assert!(filters.is_active());
+        let updated = |title: &str, updated: &str| {
+            let mut paper = paper(title, "cs.CL", &["cs.CL"]);
+            paper.updated = updated.to_string();
+            paper
+        };
+        let mut papers = vec![
+            updated("before", "2024-02-29T23:59:59Z"),
+            updated("on", "2024-03-01T00:00:00Z"),
+            updated("after", "2024-05-10T12:30:00Z"),
+            updated("offset", "2024-03-01T01:00:00+02:00"),
+            updated("date only", "2024-03-02"),
+            updated("malformed", "last tuesday"),
+            updated("missing", ""),
+        ];
+        let dropped = filters.apply(&mut papers);
+        assert_eq!(
+            titles(&papers),
+            vec!["on", "after", "offset", "date only", "malformed", "missing"]
+        );
+        assert_eq!(dropped.get("update date"), Some(&1));
+    }
+
+    #[test]
+    fn test_licenses() {
+        let licensed = |title: &str, license: Option<&str>| {
+            let mut paper = paper(title, "cs.CL", &["cs.CL"]);
+            paper.license = license.map(str::to_string);
+            paper
+        };
+        let papers = vec![
+            licensed("cc-by", Some("http://creativecommons.org/licenses/by/4.0/")),
+            licensed(
+                "cc0",
+                Some("http://creativecommons.org/publicdomain/zero/1.0/"),
+            ),
+            licensed(
+                "non-commercial",
+                Some("http://creativecommons.org/licenses/by-nc-sa/4.0/"),
+            ),
+            licensed(
+                "arxiv",
+                Some("http://arxiv.org/licenses/nonexclusive-distrib/1.0/"),
+            ),
+            licensed("missing", None),
+            licensed("unknown", Some("https://example.org/license")),
+        ];
+        let filters = PaperFilters {
+            licenses: vec![LicenseFamily::CcBy, LicenseFamily::Cc0],
+            ..Default::default()
+        };
+        assert!(filters.is_active());
+        let mut kept = papers.clone();
+        let dropped = filters.apply(&mut kept);
+        assert_eq!(titles(&kept), vec!["cc-by", "cc0", "missing", "unknown"]);
+        assert_eq!(dropped.get("license"), Some(&2));
+        let strict = PaperFilters {
+            strict_license: true,
+            ..filters
+        };
+        let mut kept = papers;
+        let dropped = strict.apply(&mut kept);
+        assert_eq!(titles(&kept), vec!["cc-by", "cc0"]);
+        assert_eq!(dropped.get("license"), Some(&4));
+    }
+
+    #[test]
+    fn test_with_doi() {
+        let filters = PaperFilters {
+            with_doi: true,
+            ..Default::default()
+        };
+        assert!(filters.is_active());
+        let mut published = paper("published", "cs.CL", &["cs.CL"]);
+        published.doi = Some("10.1103/PhysRevD.76.013009".to_string());
+        let mut papers = vec![published, paper("preprint", "cs.CL", &["cs.CL"])];
+        let dropped = filters.apply(&mut papers);
+        assert_eq!(titles(&papers), vec!["published"]);
+        assert_eq!(dropped.get("DOI"), Some(&1));
+    }
+
+    #[test]
+    fn test_with_journal_ref() {
+        let filters = PaperFilters {
+            with_journal_ref: true,
+            ..Default::default()
+        };
+        assert!(filters.is_active());
+        let with_journal_ref = |title: &str, journal_ref: Option<&str>| {
+            let mut paper = paper(title, "cs.CL", &["cs.CL"]);
+            paper.journal_ref = journal_ref.map(str::to_string);
+            paper
+        };
+        let mut papers = vec![
+            with_journal_ref("published", Some("Phys. Rev. D 76, 013009 (2007)")),
+            with_journal_ref("preprint", None),
+            with_journal_ref("blank", Some(" ")),
+        ];
+        let dropped = filters.apply(&mut papers);
+        assert_eq!(titles(&papers), vec!["published"]);
+        assert_eq!(dropped.get("journal reference"), Some(&2));
+    }
+}
@@ -0,0 +1,217 @@
+use chrono::NaiveDate;
+
+use crate::bibtex::normalize_whitespace;
+use crate::download::SerDesArxiv;
+
+/// Inline stylesheet, so that the page needs no external asset
+const STYLESHEET: &str = "body{font-family:system-ui,sans-serif;max-width:50em;margin:2em auto;padding:0 1em;line-height:1.5;color:#222}\
+h1{font-size:1.6em}\
+.meta{color:#555}\
+article{border-top:1px solid #ddd;padding:1em 0}\
+article h2{font-size:1.15em;margin:0 0 .3em}\
+a{color:#1a0dab}\
+.authors{margin:.2em 0}\
+.badge{display:inline-block;background:#eef;border:1px solid #ccd;border-radius:3px;padding:0 .4em;margin-right:.3em;font-size:.85em}\
+details{margin-top:.5em}\
+summary{cursor:pointer}";
+
+/// Escape the characters that HTML would read as markup, for use in text as
+/// well as in quoted attribute values
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encode the characters of a relative path that are not allowed in
+/// a URL, keeping the `/` separators
+fn encode_href(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Render the papers as a self-contained HTML page, in their order: a header
+/// with the queries and the date, then one entry per paper with its linked
+/// title, authors, category badges, local PDF if any and collapsible abstract.
+/// `pdf_links` holds the path of the downloaded PDF of each paper, relative to
+/// the HTML file.
+pub fn render_html(
+    papers: &[SerDesArxiv],
+    pdf_links: &[Option<String>],
+    queries: &[String],
+    date: NaiveDate,
+) -> String {
+    let mut out = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
+    out.push_str("<meta charset=\"utf-8\">\n");
+    out.push_str("<title>arXiv papers</title>\n");
+    out.push_str(&format!("<style>{}</style>\n", STYLESHEET));
+    out.push_str("</head>\n<body>\n<h1>arXiv papers</h1>\n");
+    let queries: Vec<String> = queries
+        .iter()
+        .filter(|query| !query.is_empty())
+        .map(|query| format!("<code>{}</code>", escape_html(query)))
+        .collect();
+    let label = if queries.len() > 1 {
+        "Queries"
+    } else {
+        "Query"
+    };
+    if !queries.is_empty() {
+        out.push_str(&format!(
+            "<p class=\"meta\">{}: {}</p>\n",
+            label,
+            queries.join(", ")
+        ));
+    }
+    out.push_str(&format!(
+        "<p class=\"meta\">Generated on {} with {} paper(s).</p>\n",
+        date.format("%Y-%m-%d"),
+        papers.len()
+    ));
+    for (index, paper) in papers.iter().enumerate() {
+        let id = paper
+            .id
+            .split_once("/abs/")
+            .map_or(paper.id.as_str(), |(_, id)| id);
+        out.push_str("<article>\n");
+        out.push_str(&format!(
+            "<h2><a href=\"https://arxiv.org/abs/{}\">{}</a></h2>\n",
+            escape_html(id),
+            escape_html(&normalize_whitespace(&paper.title))
+        ));
+        let authors: Vec<String> = paper
+            .authors
+            .iter()
+            .map(|author| escape_html(author))
+            .collect();
+        out.push_str(&format!(
+            "<p class=\"authors\">{}</p>\n",
+            authors.join(", ")
+        ));
+        let badges: Vec<String> = paper
+            .categories
+            .iter()
+            .map(|category| format!("<span class=\"badge\">{}</span>", escape_html(category)))
+            .collect();
+        out.push_str(&format!(
+            "<p class=\"meta\">Published {} {}</p>\n",
+            escape_html(paper.published.get(..10).unwrap_or(&paper.published)),
+            badges.join("")
+        ));
+        if let Some(Some(link)) = pdf_links.get(index) {
+            let name = link.rsplit('/').next().unwrap_or(link);
+            out.push_str(&format!(
+                "<p>PDF: <a href=\"{}\">{}</a></p>\n",
+                escape_html(&encode_href(link)),
+                escape_html(name)
+            ));
+        }
+        let summary = normalize_whitespace(&paper.summary);
+        if !summary.is_empty() {
+            out.push_str(&format!(
+                "<details>\n<summary>Abstract</summary>\n<p>{}</p>\n</details>\n",
+                escape_html(&summary)
+            ));
+        }
+        out.push_str("</article>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn paper() -> SerDesArxiv {
+        SerDesArxiv {
+            id: "http://arxiv.org/abs/2401.00001v1".to_string(),
+            updated: "2024-01-02T00:00:00Z".to_string(),
+            published: "2024-01-01T00:00:00Z".to_string(),
+            title: "Bounds for <b> & \"quoted\"\n  terms".to_string(),
+            summary: "We show that a < b && c > d.".to_string(),
+            authors: vec!["Ada O'Brien".to_string(), "Alan Turing".to_string()],
+            primary_category: "cs.LG".to_string(),
+            categories: vec!["cs.LG".to_string(), "stat.ML".to_string()],
+            pdf_url: "https://arxiv.org/pdf/2401.00001v1".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("<script>alert('x') & \"y\"</script>"),
+            "&lt;script&gt;alert(&#39;x&#39;) &amp; &quot;y&quot;&lt;/script&gt;"
+        );
+        assert_eq!(escape_html("Plain title"), "Plain title");
+    }
+
+    #[test]
+    fn test_encode_href() {
+        assert_eq!(encode_href("pdfs/A B#1.pdf"), "pdfs/A%20B%231.pdf");
+        assert_eq!(
+            encode_href("pdfs/Schrödinger.pdf"),
+            "pdfs/Schr%C3%B6dinger.pdf"
+        );
+    }
+
+    #[test]
+    fn test_render_html() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).expect("valid date");
+        let html = render_html(
+            &[paper()],
+            &[Some("pdfs/Bounds for b.pdf".to_string())],
+            &["ti:\"bounds\" AND cat:cs.LG".to_string()],
+            date,
+        );
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.ends_with("</html>\n"));
+        // No external stylesheet, script or image
+        assert!(!html.contains("<link") && !html.contains("<script") && !html.contains("<img"));
+        assert!(html.contains(
+            "<p class=\"meta\">Query: <code>ti:&quot;bounds&quot; AND cat:cs.LG</code></p>\n"
+        ));
+        assert!(html.contains("Generated on 2024-01-15 with 1 paper(s)."));
+        assert!(html.contains(
+            "<h2><a href=\"https://arxiv.org/abs/2401.00001v1\">Bounds for &lt;b&gt; &amp; &quot;quoted&quot; terms</a></h2>\n"
+        ));
+        assert!(html.contains("<p class=\"authors\">Ada O&#39;Brien, Alan Turing</p>\n"));
+        assert!(html.contains(
+            "Published 2024-01-01 <span class=\"badge\">cs.LG</span><span class=\"badge\">stat.ML</span>"
+        ));
+        assert!(
+            html.contains(
+                "<p>PDF: <a href=\"pdfs/Bounds%20for%20b.pdf\">Bounds for b.pdf</a></p>\n"
+            )
+        );
+        assert!(html.contains("<p>We show that a &lt; b &amp;&amp; c &gt; d.</p>"));
+    }
+
+    #[test]
+    fn test_render_html_without_pdf() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).expect("valid date");
+        let mut paper = paper();
+        paper.summary = "".to_string();
+        let html = render_html(&[paper], &[None], &[], date);
+        assert!(!html.contains("PDF:"));
+        assert!(!html.contains("<details>"));
+        assert!(!html.contains("Query"));
+    }
+}
@@ -0,0 +1,88 @@
+use std::{sync::OnceLock, time::Duration};
+
+use reqwest::{Client, Proxy};
+
+use crate::error::ArxivCliError;
+
+/// User-Agent sent with every request unless another one is configured, as
+/// arXiv asks clients to identify themselves
+pub const DEFAULT_USER_AGENT: &str = concat!(
+    "arxiv-cli/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://github.com/AstraBert/arxiv-cli)"
+);
+
+/// Time after which a request that receives nothing fails, unless configured
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Client shared by every request, so that connections are reused
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Settings of the HTTP client shared by every request
+#[derive(Debug, Clone, Default)]
+pub struct HttpSettings {
+    /// User-Agent header, [`DEFAULT_USER_AGENT`] if unset
+    pub user_agent: Option<String>,
+    /// Proxy for every request (e.g. http://proxy.example.com:8080), taking
+    /// precedence over the HTTP_PROXY and HTTPS_PROXY environment variables
+    pub proxy: Option<String>,
+    /// Time after which connecting, or waiting for more of a response, fails,
+    /// [`DEFAULT_TIMEOUT`] if unset. A slow download that keeps receiving data
+    /// does not time out, a stalled one does.
+    pub timeout: Option<Duration>,
+}
+
+/// Build a client with the given settings. The HTTP_PROXY, HTTPS_PROXY,
+/// ALL_PROXY and NO_PROXY environment variables are honored.
+pub fn build_client(settings: &HttpSettings) -> Result<Client, ArxivCliError> {
+    let mut builder =
+        Client::builder().user_agent(settings.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT));
+    let timeout = settings.timeout.unwrap_or(DEFAULT_TIMEOUT);
+    builder = builder.connect_timeout(timeout).read_timeout(timeout);
+    if let Some(proxy) = &settings.proxy {
+        builder = builder.proxy(Proxy::all(proxy)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Set up the shared client with the given settings, which must be done
+/// before the first request; later calls fail
+pub fn init_client(settings: &HttpSettings) -> Result<(), ArxivCliError> {
+    CLIENT
+        .set(build_client(settings)?)
+        .map_err(|_| ArxivCliError::Other(anyhow::anyhow!("the HTTP client is already set up")))
+}
+
+/// Shared client, with the default settings unless [`init_client`] was called
+/// before
+pub fn client() -> &'static Client {
+    CLIENT.get_or_init(|| {
+        build_client(&HttpSettings::default()).expect("the default HTTP client should build")
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_client() {
+        assert!(
+            DEFAULT_USER_AGENT.starts_with(&format!("arxiv-cli/{} ", env!("CARGO_PKG_VERSION")))
+        );
+        let settings = HttpSettings {
+            user_agent: Some("my-lab-crawler/1.0".to_string()),
+            proxy: Some("http://proxy.example.com:8080".to_string()),
+            timeout: Some(Duration::from_secs(5)),
+        };
+        assert!(build_client(&settings).is_ok());
+        let settings = HttpSettings {
+            proxy: Some("not a proxy url".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            build_client(&settings),
+            Err(ArxivCliError::Http(_))
+        ));
+    }
+}
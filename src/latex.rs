@@ -0,0 +1,373 @@
+/// Commands replaced by a symbol, e.g. the Greek letters and common math operators
+const SYMBOLS: [(&str, &str); 76] = [
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ε"),
+    ("varepsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("vartheta", "ϑ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("rho", "ρ"),
+    ("sigma", "σ"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "φ"),
+    ("varphi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Xi", "Ξ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Upsilon", "Υ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+    ("infty", "∞"),
+    ("le", "≤"),
+    ("leq", "≤"),
+    ("ge", "≥"),
+    ("geq", "≥"),
+    ("neq", "≠"),
+    ("ne", "≠"),
+    ("pm", "±"),
+    ("mp", "∓"),
+    ("times", "×"),
+    ("cdot", "·"),
+    ("approx", "≈"),
+    ("sim", "∼"),
+    ("simeq", "≃"),
+    ("equiv", "≡"),
+    ("propto", "∝"),
+    ("to", "→"),
+    ("rightarrow", "→"),
+    ("leftarrow", "←"),
+    ("Rightarrow", "⇒"),
+    ("leftrightarrow", "↔"),
+    ("mapsto", "↦"),
+    ("in", "∈"),
+    ("notin", "∉"),
+    ("subset", "⊂"),
+    ("subseteq", "⊆"),
+    ("cup", "∪"),
+    ("cap", "∩"),
+    ("forall", "∀"),
+    ("exists", "∃"),
+    ("partial", "∂"),
+    ("nabla", "∇"),
+    ("sum", "∑"),
+    ("prod", "∏"),
+    ("int", "∫"),
+    ("sqrt", "√"),
+    ("ell", "ℓ"),
+    ("ldots", "…"),
+    ("dots", "…"),
+];
+
+/// Commands whose argument is kept as plain text, e.g. \emph{word}
+const FORMATTING_COMMANDS: [&str; 20] = [
+    "emph",
+    "textbf",
+    "textit",
+    "texttt",
+    "textrm",
+    "textsf",
+    "textsc",
+    "textup",
+    "text",
+    "mathrm",
+    "mathbf",
+    "mathit",
+    "mathsf",
+    "mathtt",
+    "mathcal",
+    "mathfrak",
+    "boldsymbol",
+    "operatorname",
+    "underline",
+    "mbox",
+];
+
+/// Convert the LaTeX found in arXiv titles to plain Unicode text: math
+/// delimiters are dropped, common symbols and accents are replaced by their
+/// Unicode characters (`$\alpha$` becomes `α`, `\'e` becomes `é`), formatting
+/// commands such as `\emph{}` and `\textbf{}` are stripped and unknown commands
+/// lose their backslash
+pub fn latex_to_unicode(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    convert(&chars)
+}
+
+fn convert(chars: &[char]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '$' => i += 1,
+            '~' => {
+                out.push(' ');
+                i += 1;
+            }
+            '{' => {
+                let end = closing_brace(chars, i);
+                out.push_str(&convert(&chars[i + 1..end]));
+                i = end + 1;
+            }
+            '}' => i += 1,
+            mark @ ('^' | '_') => {
+                let (argument, next) = argument(chars, i + 1);
+                let converted = convert(argument);
+                match script(&converted, mark) {
+                    Some(script) => out.push_str(&script),
+                    None => {
+                        out.push(mark);
+                        out.push_str(&converted);
+                    }
+                }
+                i = next;
+            }
+            '\\' => i = command(chars, i + 1, &mut out),
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Convert the command starting after the backslash at `start`, returning the
+/// index following the command and its argument, if any
+fn command(chars: &[char], start: usize, out: &mut String) -> usize {
+    let Some(&first) = chars.get(start) else {
+        return start;
+    };
+    if !first.is_ascii_alphabetic() {
+        return match first {
+            '\'' | '`' | '"' | '^' | '~' | '=' | '.' => {
+                let (argument, next) = argument(chars, start + 1);
+                out.push_str(&accented(first, &convert(argument)));
+                next
+            }
+            // Spacing commands
+            ',' | ';' | ':' | ' ' | '\\' => {
+                out.push(' ');
+                start + 1
+            }
+            '!' => start + 1,
+            // Escaped characters such as \& or \%
+            c => {
+                out.push(c);
+                start + 1
+            }
+        };
+    }
+    let end = chars[start..]
+        .iter()
+        .position(|c| !c.is_ascii_alphabetic())
+        .map_or(chars.len(), |offset| start + offset);
+    let name: String = chars[start..end].iter().collect();
+    if FORMATTING_COMMANDS.contains(&name.as_str()) {
+        let (argument, next) = argument(chars, end);
+        out.push_str(&convert(argument));
+        return next;
+    }
+    match name.as_str() {
+        "mathbb" => {
+            let (argument, next) = argument(chars, end);
+            out.extend(convert(argument).chars().map(double_struck));
+            next
+        }
+        "c" | "v" | "u" | "H" => {
+            let (argument, next) = argument(chars, end);
+            let mark = match name.as_str() {
+                "c" => '¸',
+                "v" => 'ˇ',
+                "u" => '˘',
+                _ => '˝',
+            };
+            out.push_str(&accented(mark, &convert(argument)));
+            next
+        }
+        _ => {
+            match SYMBOLS.iter().find(|(command, _)| *command == name) {
+                Some((_, symbol)) => out.push_str(symbol),
+                None => out.push_str(&name),
+            }
+            end
+        }
+    }
+}
+
+/// Argument of a command or script starting at `start`: a braced group, a
+/// command or a single character. Returns it along with the index following it.
+fn argument(chars: &[char], start: usize) -> (&[char], usize) {
+    let start = chars[start.min(chars.len())..]
+        .iter()
+        .position(|c| *c != ' ')
+        .map_or(chars.len(), |offset| start + offset);
+    match chars.get(start) {
+        None => (&[], start),
+        Some('{') => {
+            let end = closing_brace(chars, start);
+            (&chars[start + 1..end], (end + 1).min(chars.len()))
+        }
+        Some('\\') => {
+            let end = chars[start + 1..]
+                .iter()
+                .position(|c| !c.is_ascii_alphabetic())
+                .map_or(chars.len(), |offset| start + 1 + offset)
+                .max(start + 2)
+                .min(chars.len());
+            (&chars[start..end], end)
+        }
+        Some(_) => (&chars[start..start + 1], start + 1),
+    }
+}
+
+/// Index of the brace closing the one at `open`, or the end of the text if it
+/// is never closed
+fn closing_brace(chars: &[char], open: usize) -> usize {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (index, c) in chars.iter().enumerate().skip(open) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return index;
+                }
+            }
+            _ => {}
+        }
+    }
+    chars.len()
+}
+
+/// Superscript or subscript version of `text`, if every character has one
+fn script(text: &str, mark: char) -> Option<String> {
+    let (digits, plus, minus) = if mark == '^' {
+        ("⁰¹²³⁴⁵⁶⁷⁸⁹", '⁺', '⁻')
+    } else {
+        ("₀₁₂₃₄₅₆₇₈₉", '₊', '₋')
+    };
+    if text.is_empty() {
+        return None;
+    }
+    text.chars()
+        .map(|c| match c {
+            '0'..='9' => digits.chars().nth(c as usize - '0' as usize),
+            '+' => Some(plus),
+            '-' => Some(minus),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Letter carrying the accent `mark` (as in \'e or \c{c}), or the letter
+/// itself if the combination is not supported
+fn accented(mark: char, letter: &str) -> String {
+    let mut chars = letter.chars();
+    let (Some(base), None) = (chars.next(), chars.next()) else {
+        return letter.to_string();
+    };
+    let (from, to) = match mark {
+        '\'' => ("aeiouyAEIOUYcnszCNSZ", "áéíóúýÁÉÍÓÚÝćńśźĆŃŚŹ"),
+        '`' => ("aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+        '"' => ("aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+        '^' => ("aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+        '~' => ("anoANO", "ãñõÃÑÕ"),
+        '=' => ("aeiouAEIOU", "āēīōūĀĒĪŌŪ"),
+        '.' => ("zZ", "żŻ"),
+        '¸' => ("cCsS", "çÇşŞ"),
+        'ˇ' => ("cszrnCSZRN", "čšžřňČŠŽŘŇ"),
+        '˘' => ("agAG", "ăğĂĞ"),
+        '˝' => ("ouOU", "őűŐŰ"),
+        _ => ("", ""),
+    };
+    match from.chars().position(|c| c == base) {
+        Some(index) => to.chars().nth(index).unwrap_or(base).to_string(),
+        None => base.to_string(),
+    }
+}
+
+/// Double-struck version of the letters used for number sets, as in \mathbb{R}
+fn double_struck(c: char) -> char {
+    match c {
+        'N' => 'ℕ',
+        'Z' => 'ℤ',
+        'Q' => 'ℚ',
+        'R' => 'ℝ',
+        'C' => 'ℂ',
+        'P' => 'ℙ',
+        'E' => '𝔼',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_latex_to_unicode_math() {
+        let cases = [
+            (
+                "$\\alpha$-Divergence Minimization",
+                "α-Divergence Minimization",
+            ),
+            ("Learning in $O(n^2)$ time", "Learning in O(n²) time"),
+            ("$\\ell_1$ and $\\ell_{\\infty}$ norms", "ℓ₁ and ℓ_∞ norms"),
+            ("$x_{ij}$ and $e^{i\\pi}$", "x_ij and e^iπ"),
+            (
+                "Optimal $\\mathcal{O}(\\sqrt{T})$ regret",
+                "Optimal O(√T) regret",
+            ),
+            ("Embeddings in $\\mathbb{R}^d$", "Embeddings in ℝ^d"),
+            ("$\\epsilon \\leq 10^{-3}$", "ε ≤ 10⁻³"),
+            ("$H_2O$ and $\\Delta$G", "H₂O and ΔG"),
+        ];
+        for (title, expected) in cases {
+            assert_eq!(latex_to_unicode(title), expected, "{}", title);
+        }
+    }
+
+    #[test]
+    fn test_latex_to_unicode_text() {
+        let cases = [
+            (
+                "\\emph{Attention} is \\textbf{all} you need",
+                "Attention is all you need",
+            ),
+            ("Q\\&A over 100\\% of {BERT}", "Q&A over 100% of BERT"),
+            ("Schr\\\"odinger bridges", "Schrödinger bridges"),
+            ("Erd\\H{o}s and Poincar\\'{e}", "Erdős and Poincaré"),
+            ("Fran\\c{c}ais and \\v{C}ech", "Français and Čech"),
+            ("A \\LaTeX{} parser", "A LaTeX parser"),
+            ("Fig.~1 and a\\,b", "Fig. 1 and a b"),
+            ("Plain title", "Plain title"),
+            ("Unbalanced {brace", "Unbalanced brace"),
+            ("Trailing backslash \\", "Trailing backslash "),
+        ];
+        for (title, expected) in cases {
+            assert_eq!(latex_to_unicode(title), expected, "{}", title);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Search arXiv and download the matching papers.
+//!
+//! The `arxiv-cli` binary is built on top of this library, which can also be
+//! used to fetch paper metadata in memory:
+//!
+//! ```no_run
+//! use arxiv_cli::download::{DownloadOptions, fetch_papers};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let papers = fetch_papers("cat:cs.CL AND ti:retrieval", 10, &DownloadOptions::default()).await?;
+//! for paper in papers {
+//!     println!("{} ({})", paper.title, paper.id);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod bibtex;
+pub mod category;
+pub mod csv_export;
+pub mod download;
+pub mod error;
+pub mod feed;
+pub mod filename;
+pub mod filter;
+pub mod html;
+pub mod http;
+pub mod latex;
+pub mod license;
+pub mod manifest;
+pub mod markdown;
+pub mod query;
+pub mod storage;
+pub mod table;
@@ -0,0 +1,226 @@
+use std::{fmt, str::FromStr, sync::LazyLock};
+
+use regex::Regex;
+
+use crate::download::{SerDesArxiv, paper_id_without_version};
+use crate::http::client;
+
+/// Link to the license of a paper on its abstract page
+static LICENSE_LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"href="(https?://(?:www\.)?(?:creativecommons\.org|arxiv\.org)/(?:licenses|publicdomain)/[^"]+)""#)
+        .expect("valid regex")
+});
+
+/// Family of the licenses under which arXiv distributes papers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LicenseFamily {
+    CcBy,
+    CcBySa,
+    CcByNc,
+    CcByNcSa,
+    CcByNd,
+    CcByNcNd,
+    Cc0,
+    /// arXiv's perpetual, non-exclusive license to distribute
+    Arxiv,
+}
+
+impl LicenseFamily {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LicenseFamily::CcBy => "cc-by",
+            LicenseFamily::CcBySa => "cc-by-sa",
+            LicenseFamily::CcByNc => "cc-by-nc",
+            LicenseFamily::CcByNcSa => "cc-by-nc-sa",
+            LicenseFamily::CcByNd => "cc-by-nd",
+            LicenseFamily::CcByNcNd => "cc-by-nc-nd",
+            LicenseFamily::Cc0 => "cc0",
+            LicenseFamily::Arxiv => "arxiv",
+        }
+    }
+
+    /// Family of a license URL, e.g. cc-by for
+    /// http://creativecommons.org/licenses/by/4.0/, whatever its version
+    pub fn from_url(url: &str) -> Option<Self> {
+        let url = url.trim().to_lowercase();
+        let url = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))
+            .unwrap_or(&url);
+        let url = url.strip_prefix("www.").unwrap_or(url);
+        if let Some(code) = url.strip_prefix("creativecommons.org/licenses/") {
+            return match code.split('/').next() {
+                Some("by") => Some(LicenseFamily::CcBy),
+                Some("by-sa") => Some(LicenseFamily::CcBySa),
+                Some("by-nc") => Some(LicenseFamily::CcByNc),
+                Some("by-nc-sa") => Some(LicenseFamily::CcByNcSa),
+                Some("by-nd") => Some(LicenseFamily::CcByNd),
+                Some("by-nc-nd") => Some(LicenseFamily::CcByNcNd),
+                _ => None,
+            };
+        }
+        if url.starts_with("creativecommons.org/publicdomain/zero/") {
+            return Some(LicenseFamily::Cc0);
+        }
+        // Papers submitted before 2004 are under an assumed version of it
+        if url.starts_with("arxiv.org/licenses/nonexclusive-distrib/")
+            || url.starts_with("arxiv.org/licenses/assumed-1991-2003")
+        {
+            return Some(LicenseFamily::Arxiv);
+        }
+        None
+    }
+}
+
+impl fmt::Display for LicenseFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LicenseFamily {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cc-by" => Ok(LicenseFamily::CcBy),
+            "cc-by-sa" => Ok(LicenseFamily::CcBySa),
+            "cc-by-nc" => Ok(LicenseFamily::CcByNc),
+            "cc-by-nc-sa" => Ok(LicenseFamily::CcByNcSa),
+            "cc-by-nd" => Ok(LicenseFamily::CcByNd),
+            "cc-by-nc-nd" => Ok(LicenseFamily::CcByNcNd),
+            "cc0" => Ok(LicenseFamily::Cc0),
+            "arxiv" => Ok(LicenseFamily::Arxiv),
+            _ => Err(anyhow::anyhow!(
+                "invalid license '{}': expected one of cc-by, cc-by-sa, cc-by-nc, cc-by-nc-sa, cc-by-nd, cc-by-nc-nd, cc0, arxiv",
+                s
+            )),
+        }
+    }
+}
+
+/// URL of the license linked from an abstract page
+fn license_url(html: &str) -> Option<String> {
+    LICENSE_LINK
+        .captures(html)
+        .map(|captures| captures[1].to_string())
+}
+
+/// Read the license of the paper from its abstract page, as the API does not
+/// report it
+pub async fn fetch_license(paper: &SerDesArxiv) -> anyhow::Result<Option<String>> {
+    let url = format!(
+        "https://arxiv.org/abs/{}",
+        paper_id_without_version(&paper.id)
+    );
+    let html = client()
+        .get(&url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(license_url(&html))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_license_family_from_url() {
+        let cases = [
+            (
+                "http://creativecommons.org/licenses/by/4.0/",
+                Some(LicenseFamily::CcBy),
+            ),
+            (
+                "https://creativecommons.org/licenses/by/3.0/",
+                Some(LicenseFamily::CcBy),
+            ),
+            (
+                "http://creativecommons.org/licenses/by-sa/4.0/",
+                Some(LicenseFamily::CcBySa),
+            ),
+            (
+                "http://creativecommons.org/licenses/by-nc/4.0/",
+                Some(LicenseFamily::CcByNc),
+            ),
+            (
+                "http://creativecommons.org/licenses/by-nc-sa/4.0/",
+                Some(LicenseFamily::CcByNcSa),
+            ),
+            (
+                "http://creativecommons.org/licenses/by-nd/4.0/",
+                Some(LicenseFamily::CcByNd),
+            ),
+            (
+                "http://creativecommons.org/licenses/by-nc-nd/4.0/",
+                Some(LicenseFamily::CcByNcNd),
+            ),
+            (
+                "http://creativecommons.org/publicdomain/zero/1.0/",
+                Some(LicenseFamily::Cc0),
+            ),
+            (
+                "http://arxiv.org/licenses/nonexclusive-distrib/1.0/",
+                Some(LicenseFamily::Arxiv),
+            ),
+            (
+                "http://arxiv.org/licenses/assumed-1991-2003/",
+                Some(LicenseFamily::Arxiv),
+            ),
+            (
+                "https://www.creativecommons.org/licenses/BY/4.0",
+                Some(LicenseFamily::CcBy),
+            ),
+            ("http://creativecommons.org/licenses/sampling/1.0/", None),
+            ("https://example.org/licenses/by/4.0/", None),
+            ("", None),
+        ];
+        for (url, family) in cases {
+            assert_eq!(LicenseFamily::from_url(url), family, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_parse_license_family() {
+        for family in [
+            "cc-by",
+            "cc-by-sa",
+            "cc-by-nc",
+            "cc-by-nc-sa",
+            "cc-by-nd",
+            "cc-by-nc-nd",
+            "cc0",
+            "arxiv",
+        ] {
+            let parsed: LicenseFamily = family.parse().expect("Should parse license family");
+            assert_eq!(parsed.as_str(), family);
+        }
+        assert_eq!(
+            "CC-BY".parse::<LicenseFamily>().ok(),
+            Some(LicenseFamily::CcBy)
+        );
+        let err = "mit"
+            .parse::<LicenseFamily>()
+            .expect_err("Should reject unknown license");
+        assert!(err.to_string().contains("invalid license 'mit'"));
+    }
+
+    #[test]
+    fn test_license_url() {
+        let html = r#"<div class="abs-license"><a href="http://creativecommons.org/licenses/by-nc-sa/4.0/" title="Rights to this article" class="has_license">
+            <img alt="license icon" src="https://arxiv.org/icons/licenses/by-nc-sa-4.0.png"/></a></div>"#;
+        assert_eq!(
+            license_url(html).as_deref(),
+            Some("http://creativecommons.org/licenses/by-nc-sa/4.0/")
+        );
+        let html = r#"<a href="http://arxiv.org/licenses/nonexclusive-distrib/1.0/" title="Rights to this article">view license</a>"#;
+        assert_eq!(
+            license_url(html).as_deref(),
+            Some("http://arxiv.org/licenses/nonexclusive-distrib/1.0/")
+        );
+        assert_eq!(license_url("<p>No license here</p>"), None);
+    }
+}
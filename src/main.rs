@@ -1,46 +1,1728 @@
-mod download;
+mod config;
 
-use crate::download::download_arxiv_papers;
-use clap::Parser;
+use crate::config::Config;
+use arxiv_cli::category::validate_category;
+use arxiv_cli::download::{
+    BIBTEX_FILE, DEFAULT_CONCURRENCY, DEFAULT_MAX_FILENAME_LENGTH, DEFAULT_MAX_TOTAL,
+    DEFAULT_RETRIES, DownloadOptions, MIN_MAX_FILENAME_LENGTH, MetadataFormat, PDF_DIRECTORY,
+    SortBy, SortOrder, TEXT_DIRECTORY, count_papers, dedup_ids, download_arxiv_papers,
+    download_batch, parse_arxiv_id, read_id_file, read_query_file,
+};
+use arxiv_cli::error::ArxivCliError;
+use arxiv_cli::filename::{DEFAULT_FILENAME_TEMPLATE, FilenameTemplate};
+use arxiv_cli::filter::PaperFilters;
+use arxiv_cli::http::{HttpSettings, init_client};
+use arxiv_cli::license::LicenseFamily;
+use arxiv_cli::markdown::READING_LIST_FILE;
+use arxiv_cli::query::{Field, Query};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
+use clap::{
+    ArgAction, ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand,
+    builder::RangedU64ValueParser, error::ErrorKind,
+};
+use log::LevelFilter;
+use regex::Regex;
+use std::{
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 /// Download papers from arXiv by category or search query.
 #[derive(Parser, Debug)]
 #[command(version = "1.0.0")]
 #[command(name = "arxiv-cli")]
 #[command(about, long_about = None)]
+#[command(subcommand_negates_reqs = true)]
+#[command(group(
+    ArgGroup::new("search")
+        .required(true)
+        .multiple(true)
+        .args(["query", "query_file", "category", "title", "abstract_query", "author", "raw_query", "ids", "id_file"])
+))]
+#[command(group(ArgGroup::new("exhaustive").multiple(true).args(["all", "count_only"])))]
 struct Args {
-    /// Search query (e.g., "graphrag", "machine learning")
+    /// Search query (e.g., "graphrag", "machine learning"), or - to read it from stdin
     #[arg(short, long)]
-    query: String,
+    query: Option<String>,
+
+    /// arXiv category to search in (e.g., "cs.CL", "stat.ML"), can be repeated or comma-separated to search in any of them
+    #[arg(short, long, value_delimiter = ',')]
+    category: Vec<String>,
+
+    /// Title, or fragment of a title, to search for (e.g., "attention is all you need")
+    #[arg(short, long, visible_alias = "title-query")]
+    title: Option<String>,
+
+    /// Phrase to search for in the abstracts only (e.g., "contrastive learning")
+    #[arg(
+        long = "abstract",
+        value_name = "ABSTRACT",
+        visible_alias = "abstract-query"
+    )]
+    abstract_query: Option<String>,
+
+    /// Author to search for (e.g., "Yann LeCun")
+    #[arg(short, long)]
+    author: Option<String>,
+
+    /// Term that papers must not contain in any field (e.g., "survey"), can be repeated
+    #[arg(long = "not", value_name = "TERM")]
+    not: Vec<String>,
+
+    /// File listing one search query per line, each run like --query and combined with the other search flags, blank lines and lines starting with '#' are ignored
+    #[arg(long, conflicts_with_all = ["query", "ids", "id_file"])]
+    query_file: Option<PathBuf>,
+
+    /// Full arXiv search query passed verbatim to the API (e.g., "cat:cs.CL ANDNOT au:smith"), or - to read it from stdin
+    #[arg(long, conflicts_with_all = ["query", "query_file", "category", "title", "abstract_query", "author", "not", "since", "from", "to"])]
+    raw_query: Option<String>,
+
+    /// arXiv id of a paper to fetch (e.g., "2401.12345", "cs/0112017"), can be repeated
+    #[arg(long = "id", value_name = "ID", value_parser = parse_arxiv_id)]
+    ids: Vec<String>,
+
+    /// File listing one arXiv id per line, blank lines and lines starting with '#' are ignored
+    #[arg(long)]
+    id_file: Option<PathBuf>,
+
+    /// Version of the papers given with --id, --id-file or get to download (e.g., 1 for v1), instead of the latest one
+    #[arg(long, value_parser = RangedU64ValueParser::<u32>::new().range(1..))]
+    paper_version: Option<u32>,
+
+    /// Drop papers whose primary category is this one (e.g., "cs.CV"), can be repeated
+    #[arg(long, value_name = "CATEGORY")]
+    exclude_category: Vec<String>,
+
+    /// Do not check --category and --exclude-category against the known arXiv categories, e.g. for newly added ones
+    #[arg(long, default_value_t = false)]
+    no_validate_category: bool,
+
+    /// Also drop papers cross-listed in a category given with --exclude-category
+    #[arg(long, default_value_t = false, requires = "exclude_category")]
+    exclude_cross_listed: bool,
+
+    /// Drop papers whose title or abstract contains this phrase, ignoring case (e.g., "survey"), can be repeated
+    #[arg(long = "exclude", value_name = "PHRASE")]
+    exclude_keywords: Vec<String>,
+
+    /// Keep only papers whose primary category is one of the --category ones, dropping cross-listed papers
+    #[arg(long, default_value_t = false, requires = "category")]
+    primary_only: bool,
+
+    /// Keep only papers whose comment contains this text, ignoring case (e.g., "accepted at"), can be repeated to keep papers matching any of them
+    #[arg(long, value_name = "TEXT")]
+    comment_contains: Vec<String>,
+
+    /// Keep only papers whose title matches this regular expression (e.g., "(?i)benchmark|dataset")
+    #[arg(long, value_name = "PATTERN", value_parser = Regex::new)]
+    title_regex: Option<Regex>,
+
+    /// Keep only papers whose abstract matches this regular expression (e.g., "\bdiffusion model\b"), line breaks are matched as spaces
+    #[arg(long, value_name = "PATTERN", value_parser = Regex::new)]
+    abstract_regex: Option<Regex>,
+
+    /// Keep only papers with at least this many authors
+    #[arg(long)]
+    min_authors: Option<usize>,
+
+    /// Keep only papers with at most this many authors
+    #[arg(long)]
+    max_authors: Option<usize>,
+
+    /// Only fetch papers submitted within this duration before now (e.g., 24h, 7d, 2w, 1m)
+    #[arg(long, value_parser = parse_since, conflicts_with_all = ["from", "to"])]
+    since: Option<TimeDelta>,
+
+    /// Only fetch papers submitted on or after this date (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date)]
+    from: Option<NaiveDate>,
+
+    /// Only fetch papers submitted on or before this date (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date)]
+    to: Option<NaiveDate>,
+
+    /// Keep only papers with a journal reference, i.e. with a published version
+    #[arg(long, default_value_t = false)]
+    with_journal_ref: bool,
+
+    /// Keep only papers with a DOI, usually given once they are published
+    #[arg(long, default_value_t = false)]
+    with_doi: bool,
+
+    /// Keep only papers under this license (cc-by, cc-by-sa, cc-by-nc, cc-by-nc-sa, cc-by-nd, cc-by-nc-nd, cc0, arxiv), can be repeated
+    #[arg(long, value_name = "LICENSE")]
+    license: Vec<LicenseFamily>,
+
+    /// Also drop papers whose license is unknown when filtering with --license
+    #[arg(long, default_value_t = false, requires = "license")]
+    strict_license: bool,
+
+    /// Read the license of each paper from its abstract page and save it in the metadata
+    #[arg(long, default_value_t = false)]
+    resolve_license: bool,
+
+    /// Keep only papers last updated on or after this date (YYYY-MM-DD), e.g. to track revisions
+    #[arg(long, value_parser = parse_date)]
+    updated_after: Option<NaiveDate>,
 
     /// The maximum number of papers to fetch
-    #[arg(short, long, default_value_t = 5)]
+    #[arg(short, long, default_value_t = 5, value_parser = clap::value_parser!(i32).range(1..))]
     limit: i32,
 
+    /// Fetch every result of the query, page by page, instead of stopping at --limit
+    #[arg(long, default_value_t = false, conflicts_with = "limit")]
+    all: bool,
+
+    /// Maximum number of papers fetched with --all, or with --count-only and filters
+    #[arg(long, default_value_t = DEFAULT_MAX_TOTAL, requires = "exhaustive", value_parser = clap::value_parser!(i32).range(1..))]
+    max_total: i32,
+
+    /// Keep this many papers picked at random among the --limit fetched ones, after filtering (e.g., --sample 5 --limit 200)
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    sample: Option<usize>,
+
+    /// Seed making --sample pick the same papers from the same results
+    #[arg(long, requires = "sample")]
+    seed: Option<u64>,
+
+    /// Index of the first result to fetch: combined with --limit, fetches results start..start+limit
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(i32).range(0..))]
+    start: i32,
+
+    /// Minimum number of seconds between two requests to the arXiv API (e.g., 3, 0.5)
+    #[arg(long, value_name = "SECONDS", default_value = "3", value_parser = parse_request_delay)]
+    request_delay: Duration,
+
+    /// Proxy for every request (e.g., "http://proxy.example.com:8080"), taking precedence over HTTP_PROXY and HTTPS_PROXY
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// Seconds after which connecting to arXiv, or a download that stopped receiving data, fails and can be retried
+    #[arg(long, value_name = "SECONDS", default_value = "60", value_parser = parse_timeout)]
+    timeout: Duration,
+
+    /// User-Agent sent with every request, instead of one identifying arxiv-cli and its version
+    #[arg(long, value_name = "USER_AGENT")]
+    user_agent: Option<String>,
+
     /// Whether or not to fetch and save the PDF paper
     #[arg(short, long, default_value_t = false)]
     pdf: bool,
 
+    /// Whether or not to fetch the source of the paper (usually a gzipped LaTeX tarball) into sources/
+    #[arg(long, default_value_t = false)]
+    source: bool,
+
+    /// Maximum number of PDFs to download at the same time
+    #[arg(short = 'j', long, default_value_t = DEFAULT_CONCURRENCY, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+
+    /// Number of times a failed PDF download is retried, with exponential backoff
+    #[arg(long, default_value_t = DEFAULT_RETRIES)]
+    retries: u32,
+
+    /// Check that each downloaded PDF is complete (%PDF header, reasonable size, %%EOF marker), retrying the ones that are not
+    #[arg(long, default_value_t = false)]
+    verify: bool,
+
+    /// Record the SHA-256 digest of each downloaded PDF in SHA256SUMS in the PDF directory, which `sha256sum -c SHA256SUMS` checks
+    #[arg(long, default_value_t = false)]
+    checksums: bool,
+
+    /// Re-download PDFs even if they already exist in the output directory
+    #[arg(long, default_value_t = false)]
+    force: bool,
+
     /// Whether or not to save the summary of the papers txt files
     #[arg(short, long, default_value_t = false)]
     summary: bool,
 
+    /// Whether or not to extract the full text of the papers from their HTML version into txt files
+    #[arg(long, default_value_t = false)]
+    fulltext: bool,
+
+    /// Convert the LaTeX in titles (e.g., $\alpha$, \emph{...}) to plain Unicode text before using them in file names and metadata
+    #[arg(long, default_value_t = false, overrides_with = "latex")]
+    no_latex: bool,
+
+    /// Keep the LaTeX in titles as written by the authors (default), overriding --no-latex
+    #[arg(long, default_value_t = false, overrides_with = "no_latex")]
+    latex: bool,
+
+    /// Name of the PDF and text files, with placeholders {id}, {title}, {first_author}, {published}, {year} and {primary_category}
+    #[arg(long, default_value = DEFAULT_FILENAME_TEMPLATE)]
+    filename_template: FilenameTemplate,
+
+    /// Maximum number of characters of the names of the PDF and text files, extension included (e.g., 140 on eCryptfs)
+    #[arg(long, default_value_t = DEFAULT_MAX_FILENAME_LENGTH, value_parser = RangedU64ValueParser::<usize>::new().range(MIN_MAX_FILENAME_LENGTH as u64..))]
+    max_filename_length: usize,
+
     /// Whether or not to disable fetching and saving the metadata of the paper to a JSONL file
     #[arg(long, default_value_t = false)]
     no_metadata: bool,
+
+    /// Format of the metadata file: jsonl (metadata.jsonl), json (a JSON array in metadata.json), csv (metadata.csv) or yaml (a YAML sequence in metadata.yaml)
+    #[arg(long, default_value = "jsonl")]
+    format: MetadataFormat,
+
+    /// Write the metadata as a single JSON array to metadata.json, same as --format json
+    #[arg(long, default_value_t = false, conflicts_with = "format")]
+    json_array: bool,
+
+    /// Path of the metadata file, relative to the output directory unless absolute (default: metadata.jsonl, metadata.json, metadata.csv or metadata.yaml depending on --format)
+    #[arg(long, value_name = "PATH", conflicts_with = "no_metadata")]
+    metadata_file: Option<PathBuf>,
+
+    /// Write the metadata to a file named after the time of the run (e.g., metadata-2024-06-01T12-30-00.jsonl) instead of the shared one; --metadata-file takes precedence
+    #[arg(long, default_value_t = false, conflicts_with = "no_metadata")]
+    timestamped_metadata: bool,
+
+    /// Print the metadata to standard output, in the --format format, instead of writing the metadata file, e.g. to pipe it into jq
+    #[arg(long, default_value_t = false, conflicts_with_all = ["no_metadata", "metadata_file", "timestamped_metadata", "append", "validate", "dry_run"])]
+    stdout: bool,
+
+    /// Also print each paper to standard output as indented JSON; metadata.jsonl keeps one paper per line, and JSON arrays are always indented
+    #[arg(long, default_value_t = false, conflicts_with = "stdout")]
+    pretty: bool,
+
+    /// Whether or not to include the summary (abstract) of the papers in the metadata file
+    #[arg(long, visible_alias = "include-abstract", default_value_t = false)]
+    include_summary_in_metadata: bool,
+
+    /// Add the papers to the end of the jsonl or csv metadata file instead of replacing it, skipping the ids already listed there
+    #[arg(long, default_value_t = false, conflicts_with = "no_metadata")]
+    append: bool,
+
+    /// Read the metadata file back once written, reporting the lines that do not parse
+    #[arg(long, default_value_t = false, conflicts_with = "no_metadata")]
+    validate: bool,
+
+    /// Whether or not to also save the metadata of the papers to metadata.csv
+    #[arg(long, default_value_t = false)]
+    csv: bool,
+
+    /// Also save the id, title and abstract of each paper to abstracts.jsonl, one paper per line
+    #[arg(long, default_value_t = false)]
+    with_abstracts: bool,
+
+    /// Append a BibTeX entry for each paper to this file, relative to the output directory unless absolute (default: references.bib)
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = BIBTEX_FILE)]
+    bibtex: Option<PathBuf>,
+
+    /// Save the papers to this SQLite database too, relative to the output directory unless absolute, updating the ones already stored there
+    #[arg(long, value_name = "PATH")]
+    sqlite: Option<PathBuf>,
+
+    /// Write a Markdown reading list of the papers to this file (default: reading_list.md), relative to the output directory unless absolute, linking the downloaded PDFs when combined with --pdf
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = READING_LIST_FILE)]
+    markdown: Option<PathBuf>,
+
+    /// Write a self-contained HTML page listing the papers to this file, relative to the output directory unless absolute, linking the downloaded PDFs when combined with --pdf
+    #[arg(long, value_name = "PATH")]
+    html: Option<PathBuf>,
+
+    /// Field used to sort the results (relevance, lastUpdatedDate, submittedDate)
+    #[arg(long, default_value = "submittedDate")]
+    sort_by: SortBy,
+
+    /// Order in which the results are sorted (ascending, descending)
+    #[arg(long, default_value = "descending")]
+    sort_order: SortOrder,
+
+    /// Log what is being fetched and written: -v for each query and file, -vv for each paper, -vvv for everything
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Whether or not to hide the progress bars
+    #[arg(long, default_value_t = false)]
+    quiet: bool,
+
+    /// Open the HTML version of the first paper (or its abstract page) in the default browser
+    #[arg(long, default_value_t = false)]
+    open: bool,
+
+    /// Only print the id, title and primary category of the matching papers, without writing anything
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// Pick in a list which of the matching papers get their PDF, summary, full text or source downloaded; the metadata still lists them all
+    #[arg(short, long, default_value_t = false, conflicts_with_all = ["dry_run", "table"])]
+    interactive: bool,
+
+    /// Only print a table of the matching papers (index, id, title, primary category, published date) fitting the terminal, without writing anything
+    #[arg(long, default_value_t = false, conflicts_with_all = ["dry_run", "stdout"])]
+    table: bool,
+
+    /// Exit with an error when no paper matches the query and filters, instead of only reporting it
+    #[arg(long, default_value_t = false)]
+    fail_on_empty: bool,
+
+    /// Only print how many papers match the query and filters, fetching every page but writing nothing
+    #[arg(long, default_value_t = false, conflicts_with_all = ["limit", "query_file", "dry_run", "sample"])]
+    count_only: bool,
+
+    /// Print the --count-only result as JSON (e.g., {"count": 42})
+    #[arg(long, default_value_t = false, requires = "count_only")]
+    json: bool,
+
+    /// Directory under which the metadata, PDFs and summaries are saved
+    #[arg(short, long, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Directory of the PDFs, relative to the output directory unless absolute (e.g., ~/papers/raw)
+    #[arg(long, default_value = PDF_DIRECTORY)]
+    pdf_dir: PathBuf,
+
+    /// Directory of the summaries and full texts, relative to the output directory unless absolute
+    #[arg(long, default_value = TEXT_DIRECTORY)]
+    text_dir: PathBuf,
+
+    /// TOML file providing defaults for the options (default: ~/.config/arxiv-cli/config.toml if it exists)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Fetch papers by their arXiv ids instead of searching, using the options given before get
+    Get {
+        /// arXiv ids of the papers (e.g., "2301.12345", "cs/0112017v2")
+        #[arg(required = true, value_parser = parse_arxiv_id)]
+        ids: Vec<String>,
+    },
+}
+
+/// Day before which arXiv has no submissions, used for open-ended date ranges
+const ARXIV_FIRST_DAY: NaiveDate = NaiveDate::from_ymd_opt(1991, 1, 1).expect("valid date");
+
+/// Parse a relative duration such as 24h, 7d, 2w or 1m (30 days)
+fn parse_since(value: &str) -> anyhow::Result<TimeDelta> {
+    let value = value.trim();
+    let split = value.len() - value.chars().last().map_or(0, char::len_utf8);
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| {
+        anyhow::anyhow!(
+            "invalid duration '{}': expected a number followed by a unit, e.g. 24h, 7d, 2w, 1m",
+            value
+        )
+    })?;
+    if amount <= 0 {
+        anyhow::bail!("invalid duration '{}': must be positive", value);
+    }
+    let duration = match unit {
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        "w" => TimeDelta::try_weeks(amount),
+        "m" => amount.checked_mul(30).and_then(TimeDelta::try_days),
+        _ => anyhow::bail!(
+            "invalid duration unit '{}' in '{}': expected one of h, d, w, m",
+            unit,
+            value
+        ),
+    };
+    duration.ok_or_else(|| anyhow::anyhow!("invalid duration '{}': too large", value))
+}
+
+/// Parse a calendar date given as YYYY-MM-DD
+fn parse_date(value: &str) -> anyhow::Result<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|e| anyhow::anyhow!("invalid date '{}': expected YYYY-MM-DD ({})", value, e))
+}
+
+/// Parse a delay given as a number of seconds, possibly fractional
+fn parse_request_delay(value: &str) -> anyhow::Result<Duration> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "invalid delay '{}': expected a non-negative number of seconds, e.g. 3 or 0.5",
+                value
+            )
+        })
+}
+
+/// Parse a positive number of seconds, e.g. "60" or "2.5"
+fn parse_timeout(value: &str) -> anyhow::Result<Duration> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .filter(|timeout| !timeout.is_zero())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "invalid timeout '{}': expected a positive number of seconds, e.g. 60 or 2.5",
+                value
+            )
+        })
+}
+
+/// Replace a leading `~` with the home directory, as a shell would for an
+/// unquoted path; `~user` paths are left as they are
+fn expand_tilde(path: PathBuf) -> PathBuf {
+    let Some(rest) = path.to_str().and_then(|path| path.strip_prefix('~')) else {
+        return path;
+    };
+    let rest = match rest.strip_prefix(['/', std::path::MAIN_SEPARATOR]) {
+        Some(rest) => rest,
+        None if rest.is_empty() => rest,
+        None => return path,
+    };
+    match dirs::home_dir() {
+        Some(home) => home.join(rest),
+        None => path,
+    }
+}
+
+/// submittedDate range between two instants
+fn date_range(from: DateTime<Utc>, to: DateTime<Utc>) -> Query {
+    Query::SubmittedDate { from, to }
+}
+
+/// submittedDate range covering the `since` duration before `now`
+fn since_range(since: TimeDelta, now: DateTime<Utc>) -> Query {
+    date_range(now - since, now)
+}
+
+/// submittedDate range covering whole days from `from` to `to`, both included,
+/// open-ended bounds going back to arXiv's first papers or up to `now`
+fn from_to_range(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    now: DateTime<Utc>,
+) -> anyhow::Result<Query> {
+    if let (Some(from), Some(to)) = (from, to)
+        && from > to
+    {
+        anyhow::bail!("--from {} is after --to {}", from, to);
+    }
+    let from = from
+        .unwrap_or(ARXIV_FIRST_DAY)
+        .and_time(NaiveTime::MIN)
+        .and_utc();
+    let to = match to {
+        Some(to) => to
+            .and_hms_opt(23, 59, 0)
+            .expect("23:59 is a valid time")
+            .and_utc(),
+        None => now,
+    };
+    Ok(date_range(from, to))
+}
+
+/// Reject author-count bounds that no paper can satisfy
+fn check_author_bounds(min: Option<usize>, max: Option<usize>) -> anyhow::Result<()> {
+    if let (Some(min), Some(max)) = (min, max)
+        && min > max
+    {
+        anyhow::bail!(
+            "--min-authors {} is greater than --max-authors {}",
+            min,
+            max
+        );
+    }
+    Ok(())
+}
+
+/// Value used for a flag that reads from stdin when given as `-`
+const STDIN_SENTINEL: &str = "-";
+
+/// Read a query from `reader`, without its surrounding whitespace and newlines
+fn read_query(mut reader: impl Read, flag: &str) -> anyhow::Result<String> {
+    let mut query = String::new();
+    reader
+        .read_to_string(&mut query)
+        .map_err(|e| anyhow::anyhow!("failed to read {} from stdin: {}", flag, e))?;
+    let query = query.trim();
+    if query.is_empty() {
+        anyhow::bail!("{} - expects a query on stdin, but stdin was empty", flag);
+    }
+    Ok(query.to_string())
+}
+
+/// Replace a `-` query with the query read from `reader`
+fn resolve_stdin_query(
+    query: &mut Option<String>,
+    reader: impl Read,
+    flag: &str,
+) -> anyhow::Result<()> {
+    if query.as_deref() == Some(STDIN_SENTINEL) {
+        *query = Some(read_query(reader, flag)?);
+    }
+    Ok(())
+}
+
+/// Whether any of the flags of the "search" group is set
+fn has_search_input(args: &Args) -> bool {
+    args.query.is_some()
+        || args.query_file.is_some()
+        || !args.category.is_empty()
+        || args.title.is_some()
+        || args.abstract_query.is_some()
+        || args.author.is_some()
+        || args.raw_query.is_some()
+        || !args.ids.is_empty()
+        || args.id_file.is_some()
+        || args.command.is_some()
+}
+
+/// Check the categories given with --category and --exclude-category, or in
+/// the config file, against the arXiv taxonomy
+fn validate_categories(args: &Args) -> Result<(), String> {
+    if args.no_validate_category {
+        return Ok(());
+    }
+    args.category
+        .iter()
+        .chain(&args.exclude_category)
+        .map(|category| category.trim())
+        .filter(|category| !category.is_empty())
+        .try_for_each(validate_category)
+}
+
+/// Check the combinations of flags that clap cannot, as the config file is
+/// only read once the command line is parsed: the flags that conflict with, or
+/// require, one set in the config file, and --append with a JSON or YAML format
+fn validate_combinations(args: &Args) -> Result<(), String> {
+    let conflicts = [
+        ("append", args.append, "stdout", args.stdout),
+        ("no-metadata", args.no_metadata, "append", args.append),
+        ("no-metadata", args.no_metadata, "validate", args.validate),
+        ("no-metadata", args.no_metadata, "stdout", args.stdout),
+        (
+            "no-metadata",
+            args.no_metadata,
+            "timestamped-metadata",
+            args.timestamped_metadata,
+        ),
+        (
+            "no-metadata",
+            args.no_metadata,
+            "metadata-file",
+            args.metadata_file.is_some(),
+        ),
+    ];
+    for (flag, set, other, other_set) in conflicts {
+        if set && other_set {
+            return Err(format!(
+                "--{} cannot be used with --{}, whether given on the command line or in the config file",
+                flag, other
+            ));
+        }
+    }
+    if args.primary_only
+        && args
+            .category
+            .iter()
+            .all(|category| category.trim().is_empty())
+    {
+        return Err(
+            "--primary-only requires at least one category, given with --category or in the config file"
+                .to_string(),
+        );
+    }
+    if args.append
+        && (args.json_array || matches!(args.format, MetadataFormat::Json | MetadataFormat::Yaml))
+    {
+        return Err("--append only supports the jsonl and csv metadata formats".to_string());
+    }
+    Ok(())
+}
+
+/// Parse the command line, filling the options it does not set from the config
+/// file. The "search" group is only checked afterwards, as the categories can
+/// come from the config file.
+fn parse_args() -> anyhow::Result<Args> {
+    let mut command = Args::command().mut_group("search", |group| group.required(false));
+    let matches = command.get_matches_mut();
+    let mut args = Args::from_arg_matches(&matches)?;
+    if let Some(config) = Config::load(args.config.as_deref())? {
+        config.apply(&mut args, &matches)?;
+    }
+    if !has_search_input(&args) {
+        command
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "at least one of --query, --query-file, --category, --title, --abstract, --author, --raw-query, --id or --id-file must be provided, or category in the config file",
+            )
+            .exit();
+    }
+    if let Err(message) = validate_categories(&args) {
+        command.error(ErrorKind::InvalidValue, message).exit();
+    }
+    if let Err(message) = validate_combinations(&args) {
+        command.error(ErrorKind::ArgumentConflict, message).exit();
+    }
+    Ok(args)
+}
+
+/// Log level of this crate for the number of times --verbose is given
+fn log_level(verbose: u8) -> LevelFilter {
+    match verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Combine the search-related arguments into an arXiv search query
+fn build_search_query(args: &Args) -> anyhow::Result<String> {
+    if let Some(raw_query) = &args.raw_query {
+        return Ok(raw_query.clone());
+    }
+    let mut terms: Vec<Query> = vec![];
+    let mut categories: Vec<&str> = vec![];
+    for category in args.category.iter().map(|category| category.trim()) {
+        if !category.is_empty() && !categories.contains(&category) {
+            categories.push(category);
+        }
+    }
+    if !categories.is_empty() {
+        terms.push(Query::or(
+            categories
+                .into_iter()
+                .map(|category| Query::term(Field::Category, category))
+                .collect(),
+        ));
+    }
+    if let Some(query) = &args.query {
+        terms.push(Query::text(query));
+    }
+    if let Some(title) = &args.title {
+        terms.push(Query::term(Field::Title, title));
+    }
+    if let Some(abstract_query) = &args.abstract_query {
+        terms.push(Query::term(Field::Abstract, abstract_query));
+    }
+    if let Some(author) = &args.author {
+        terms.push(Query::term(Field::Author, author));
+    }
+    // The "search" group guarantees that ids are given when there are no terms
+    if terms.is_empty() && !args.not.is_empty() {
+        anyhow::bail!("--not needs a search term to exclude papers from");
+    }
+    if let Some(since) = args.since {
+        terms.push(since_range(since, Utc::now()));
+    }
+    if args.from.is_some() || args.to.is_some() {
+        terms.push(from_to_range(args.from, args.to, Utc::now())?);
+    }
+    if terms.is_empty() {
+        return Ok(String::new());
+    }
+    let mut query = Query::and(terms);
+    if !args.not.is_empty() {
+        let excluded = args
+            .not
+            .iter()
+            .map(|term| Query::term(Field::All, term))
+            .collect();
+        query = query.and_not(Query::or(excluded));
+    }
+    Ok(query.to_string())
+}
+
+/// Search query for each line of the query file, used as --query
+fn batch_queries(args: &mut Args, path: &Path) -> anyhow::Result<Vec<String>> {
+    let mut search_queries = vec![];
+    for query in read_query_file(path)? {
+        args.query = Some(query);
+        search_queries.push(build_search_query(args)?);
+    }
+    args.query = None;
+    Ok(search_queries)
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
-
-    download_arxiv_papers(
-        args.query,
-        args.limit,
-        !args.no_metadata,
-        args.pdf,
-        args.summary,
-    )
-    .await?;
+    let mut args = parse_args()?;
+    // Only this crate's logs are raised, RUST_LOG can still override them
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Warn)
+        .filter_module(module_path!(), log_level(args.verbose))
+        .parse_default_env()
+        .init();
+    if let Some(Command::Get { ids }) = args.command.take() {
+        if args.query_file.is_some() {
+            anyhow::bail!("get cannot be combined with --query-file");
+        }
+        args.ids.extend(ids);
+    }
+    // --query and --raw-query conflict, so stdin is read at most once
+    resolve_stdin_query(&mut args.query, io::stdin().lock(), "--query")?;
+    resolve_stdin_query(&mut args.raw_query, io::stdin().lock(), "--raw-query")?;
+    let search_queries = match args.query_file.clone() {
+        Some(path) => batch_queries(&mut args, &path)?,
+        None => vec![build_search_query(&args)?],
+    };
+    for search_query in &search_queries {
+        log::info!("Search query: {}", search_query);
+    }
+    let is_batch = args.query_file.is_some();
+    check_author_bounds(args.min_authors, args.max_authors)?;
+    let mut ids = args.ids;
+    let mut malformed = vec![];
+    if let Some(id_file) = &args.id_file {
+        let id_file = read_id_file(id_file)?;
+        ids.extend(id_file.ids);
+        malformed = id_file.malformed;
+    }
+    dedup_ids(&mut ids);
+    init_client(&HttpSettings {
+        user_agent: args.user_agent.clone(),
+        proxy: args.proxy.clone(),
+        timeout: Some(args.timeout),
+    })?;
+    if args.paper_version.is_some() && ids.is_empty() {
+        anyhow::bail!("--paper-version needs ids given with --id, --id-file or get");
+    }
+    let format = if args.json_array {
+        MetadataFormat::Json
+    } else {
+        args.format
+    };
+    // An explicit --metadata-file wins over --timestamped-metadata
+    let metadata_file = match args.metadata_file {
+        None if args.timestamped_metadata => {
+            let time = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+            Some(PathBuf::from(format.timestamped_file_name(&time)))
+        }
+        metadata_file => metadata_file,
+    };
+    let options = DownloadOptions {
+        sort_by: args.sort_by,
+        sort_order: args.sort_order,
+        save_metadata: !args.no_metadata,
+        stdout: args.stdout,
+        pretty: args.pretty,
+        save_pdfs: args.pdf,
+        save_sources: args.source,
+        save_summaries: args.summary,
+        start: args.start,
+        request_delay: args.request_delay,
+        resolve_license: args.resolve_license,
+        id_list: ids,
+        all: args.all || args.count_only,
+        filters: PaperFilters {
+            exclude_categories: args.exclude_category,
+            exclude_cross_listed: args.exclude_cross_listed,
+            primary_categories: if args.primary_only {
+                args.category
+                    .iter()
+                    .map(|category| category.trim().to_string())
+                    .collect()
+            } else {
+                vec![]
+            },
+            exclude_keywords: args.exclude_keywords,
+            comment_contains: args.comment_contains,
+            title_regex: args.title_regex,
+            abstract_regex: args.abstract_regex,
+            min_authors: args.min_authors,
+            max_authors: args.max_authors,
+            updated_after: args.updated_after,
+            with_journal_ref: args.with_journal_ref,
+            with_doi: args.with_doi,
+            licenses: args.license,
+            strict_license: args.strict_license,
+        },
+        sample: args.sample,
+        seed: args.seed,
+        normalize_latex: args.no_latex,
+        filename_template: args.filename_template,
+        max_filename_length: args.max_filename_length,
+        format,
+        metadata_file,
+        paper_version: args.paper_version,
+        include_summary: args.include_summary_in_metadata,
+        append: args.append,
+        validate: args.validate,
+        csv: args.csv,
+        with_abstracts: args.with_abstracts,
+        bibtex: args.bibtex,
+        sqlite: args.sqlite,
+        markdown: args.markdown,
+        html: args.html,
+        fulltext: args.fulltext,
+        concurrency: args.concurrency,
+        retries: args.retries,
+        verify_pdfs: args.verify,
+        checksums: args.checksums,
+        force: args.force,
+        quiet: args.quiet,
+        dry_run: args.dry_run,
+        interactive: args.interactive,
+        table: args.table,
+        open: args.open,
+        fail_on_empty: args.fail_on_empty,
+        output_dir: args.output_dir,
+        pdf_dir: expand_tilde(args.pdf_dir),
+        text_dir: expand_tilde(args.text_dir),
+    };
+
+    let num_results = if args.all || args.count_only {
+        args.max_total
+    } else {
+        args.limit
+    };
+    if args.count_only {
+        let search_query = search_queries.into_iter().next().unwrap_or_default();
+        let count = count_papers(&search_query, num_results, &options).await?;
+        if args.json {
+            println!("{}", count.to_json());
+        } else {
+            println!("{}", count);
+        }
+        if args.fail_on_empty && count.count() == 0 {
+            return Err(ArxivCliError::EmptyQuery.into());
+        }
+    } else if is_batch {
+        download_batch(&search_queries, num_results, &options).await?;
+    } else {
+        let search_query = search_queries.into_iter().next().unwrap_or_default();
+        download_arxiv_papers(search_query, num_results, &options).await?;
+    }
+    if args.timestamped_metadata
+        && let Some(metadata_file) = &options.metadata_file
+    {
+        let path = options.output_dir.join(metadata_file);
+        if path.exists() {
+            eprintln!("Metadata written to {}", path.display());
+        }
+    }
+    if !malformed.is_empty() {
+        eprintln!("{} malformed line(s) in the id file:", malformed.len());
+        for (line_number, line) in &malformed {
+            eprintln!("  - line {}: {}", line_number, line);
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn search_query_from(args: &[&str]) -> anyhow::Result<String> {
+        let args = Args::parse_from(std::iter::once("arxiv-cli").chain(args.iter().copied()));
+        build_search_query(&args)
+    }
+
+    #[test]
+    fn test_build_search_query_author_only() {
+        assert_eq!(
+            search_query_from(&["--author", "Yann LeCun"]).expect("Should build query"),
+            "au:\"Yann LeCun\""
+        );
+        assert_eq!(
+            search_query_from(&["--author", "LeCun"]).expect("Should build query"),
+            "au:LeCun"
+        );
+    }
+
+    #[test]
+    fn test_build_search_query_author_and_category() {
+        assert_eq!(
+            search_query_from(&["--author", "Yann LeCun", "--category", "cs.LG"])
+                .expect("Should build query"),
+            "cat:cs.LG AND au:\"Yann LeCun\""
+        );
+    }
+
+    #[test]
+    fn test_build_search_query_author_and_query() {
+        assert_eq!(
+            search_query_from(&["--author", "Yann LeCun", "--query", "convolutional"])
+                .expect("Should build query"),
+            "convolutional AND au:\"Yann LeCun\""
+        );
+        assert_eq!(
+            search_query_from(&["-a", "Yann LeCun", "-q", "convolutional", "-c", "cs.LG"])
+                .expect("Should build query"),
+            "cat:cs.LG AND convolutional AND au:\"Yann LeCun\""
+        );
+    }
+
+    #[test]
+    fn test_build_search_query_categories() {
+        assert_eq!(
+            search_query_from(&["-c", "cs.CL"]).expect("Should build query"),
+            "cat:cs.CL"
+        );
+        assert_eq!(
+            search_query_from(&["-c", "cs.CL", "-c", "cs.LG"]).expect("Should build query"),
+            "cat:cs.CL OR cat:cs.LG"
+        );
+        assert_eq!(
+            search_query_from(&["-c", "cs.CL", "-c", "cs.LG", "-c", "stat.ML", "-q", "llm"])
+                .expect("Should build query"),
+            "(cat:cs.CL OR cat:cs.LG OR cat:stat.ML) AND llm"
+        );
+        assert_eq!(
+            search_query_from(&["-c", "cs.AI, cs.CL", "-c", "cs.AI", "-q", "agents"])
+                .expect("Should build query"),
+            "(cat:cs.AI OR cat:cs.CL) AND agents"
+        );
+    }
+
+    #[test]
+    fn test_build_search_query_title() {
+        assert_eq!(
+            search_query_from(&["--title", "attention is all you need"])
+                .expect("Should build query"),
+            "ti:\"attention is all you need\""
+        );
+        assert_eq!(
+            search_query_from(&["-t", "transformers"]).expect("Should build query"),
+            "ti:transformers"
+        );
+    }
+
+    #[test]
+    fn test_build_search_query_field_aliases() {
+        assert_eq!(
+            search_query_from(&[
+                "--title-query",
+                "graph rag",
+                "--abstract-query",
+                "retrieval"
+            ])
+            .expect("Should build query"),
+            search_query_from(&["--title", "graph rag", "--abstract", "retrieval"])
+                .expect("Should build query"),
+        );
+    }
+
+    #[test]
+    fn test_build_search_query_title_and_query() {
+        assert_eq!(
+            search_query_from(&["--title", "graph rag", "--query", "retrieval"])
+                .expect("Should build query"),
+            "retrieval AND ti:\"graph rag\""
+        );
+        assert_eq!(
+            search_query_from(&["-t", "graph rag", "-c", "cs.CL", "-a", "Smith"])
+                .expect("Should build query"),
+            "cat:cs.CL AND ti:\"graph rag\" AND au:Smith"
+        );
+    }
+
+    #[test]
+    fn test_build_search_query_abstract() {
+        assert_eq!(
+            search_query_from(&["--abstract", "contrastive learning"]).expect("Should build query"),
+            "abs:\"contrastive learning\""
+        );
+        assert_eq!(
+            search_query_from(&["--abstract", "contrastive", "--category", "cs.LG"])
+                .expect("Should build query"),
+            "cat:cs.LG AND abs:contrastive"
+        );
+    }
+
+    #[test]
+    fn test_parse_sort_flags() {
+        let args = Args::parse_from([
+            "arxiv-cli",
+            "-q",
+            "graphrag",
+            "--sort-by",
+            "relevance",
+            "--sort-order",
+            "ascending",
+        ]);
+        assert_eq!(args.sort_by, SortBy::Relevance);
+        assert_eq!(args.sort_order, SortOrder::Ascending);
+        let args = Args::parse_from(["arxiv-cli", "-q", "graphrag"]);
+        assert_eq!(args.sort_by, SortBy::SubmittedDate);
+        assert_eq!(args.sort_order, SortOrder::Descending);
+    }
+
+    #[test]
+    fn test_parse_sort_flags_invalid() {
+        let err = Args::try_parse_from(["arxiv-cli", "-q", "graphrag", "--sort-by", "newest"])
+            .expect_err("Should reject unknown sort field");
+        assert!(err.to_string().contains("invalid sort field 'newest'"));
+        let err = Args::try_parse_from(["arxiv-cli", "-q", "graphrag", "--sort-order", "up"])
+            .expect_err("Should reject unknown sort order");
+        assert!(err.to_string().contains("invalid sort order 'up'"));
+    }
+
+    #[test]
+    fn test_build_search_query_ids_only() {
+        assert_eq!(
+            search_query_from(&["--id", "2401.12345v2", "--id", "cs/0112017"])
+                .expect("Should build query"),
+            ""
+        );
+        let args = Args::parse_from(["arxiv-cli", "--id", "2401.12345v2", "--id", "cs/0112017"]);
+        assert_eq!(args.ids, vec!["2401.12345", "cs/0112017"]);
+        assert!(Args::try_parse_from(["arxiv-cli", "--id", "not-an-id"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(
+            parse_since("24h").expect("Should parse"),
+            TimeDelta::hours(24)
+        );
+        assert_eq!(parse_since("7d").expect("Should parse"), TimeDelta::days(7));
+        assert_eq!(
+            parse_since("2w").expect("Should parse"),
+            TimeDelta::weeks(2)
+        );
+        assert_eq!(
+            parse_since("1m").expect("Should parse"),
+            TimeDelta::days(30)
+        );
+        let err = parse_since("3y").expect_err("Should reject unknown unit");
+        assert!(err.to_string().contains("invalid duration unit 'y'"));
+        assert!(parse_since("d").is_err());
+        assert!(parse_since("").is_err());
+        assert!(parse_since("0d").is_err());
+        assert!(parse_since("-1d").is_err());
+        assert!(parse_since("1.5d").is_err());
+    }
+
+    #[test]
+    fn test_since_range() {
+        let now = DateTime::parse_from_rfc3339("2024-06-08T12:30:00Z")
+            .expect("Should parse date")
+            .with_timezone(&Utc);
+        assert_eq!(
+            since_range(TimeDelta::days(7), now).to_string(),
+            "submittedDate:[202406011230 TO 202406081230]"
+        );
+        assert_eq!(
+            since_range(TimeDelta::hours(24), now).to_string(),
+            "submittedDate:[202406071230 TO 202406081230]"
+        );
+    }
+
+    #[test]
+    fn test_build_search_query_since() {
+        let query = search_query_from(&["-c", "cs.CL", "--since", "7d"]).expect("Should build");
+        assert!(query.starts_with("cat:cs.CL AND submittedDate:["));
+        assert!(Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--since", "7x"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(
+            parse_date("2024-03-31").expect("Should parse"),
+            NaiveDate::from_ymd_opt(2024, 3, 31).expect("valid date")
+        );
+        for value in ["2024-02-30", "31/03/2024", "2024-3", "yesterday"] {
+            let err = parse_date(value).expect_err("Should reject malformed date");
+            assert!(err.to_string().contains("expected YYYY-MM-DD"));
+        }
+    }
+
+    #[test]
+    fn test_parse_timestamped_metadata() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--timestamped-metadata"]);
+        assert!(args.timestamped_metadata);
+        // Together with --metadata-file, which then wins
+        assert!(
+            Args::try_parse_from([
+                "arxiv-cli",
+                "-c",
+                "cs.CL",
+                "--timestamped-metadata",
+                "--metadata-file",
+                "papers.jsonl",
+            ])
+            .is_ok()
+        );
+        for flag in ["--stdout", "--no-metadata"] {
+            assert!(
+                Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--timestamped-metadata", flag])
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_proxy_and_user_agent() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL"]);
+        assert_eq!(args.proxy, None);
+        assert_eq!(args.user_agent, None);
+        let args = Args::parse_from([
+            "arxiv-cli",
+            "-c",
+            "cs.CL",
+            "--proxy",
+            "http://proxy.example.com:8080",
+            "--user-agent",
+            "my-lab-crawler/1.0",
+        ]);
+        assert_eq!(args.proxy.as_deref(), Some("http://proxy.example.com:8080"));
+        assert_eq!(args.user_agent.as_deref(), Some("my-lab-crawler/1.0"));
+    }
+
+    #[test]
+    fn test_parse_request_delay() {
+        assert_eq!(
+            parse_request_delay("3").expect("Should parse"),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            parse_request_delay("0.5").expect("Should parse"),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            parse_request_delay("0").expect("Should parse"),
+            Duration::ZERO
+        );
+        for value in ["-1", "3s", "NaN", ""] {
+            let err = parse_request_delay(value).expect_err("Should reject invalid delay");
+            assert!(err.to_string().contains("non-negative number of seconds"));
+        }
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(
+            parse_timeout("60").expect("Should parse"),
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            parse_timeout("2.5").expect("Should parse"),
+            Duration::from_millis(2500)
+        );
+        for value in ["0", "-1", "60s", ""] {
+            let err = parse_timeout(value).expect_err("Should reject invalid timeout");
+            assert!(err.to_string().contains("positive number of seconds"));
+        }
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL"]);
+        assert_eq!(args.timeout, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_from_to_range() {
+        let now = DateTime::parse_from_rfc3339("2024-06-08T12:30:00Z")
+            .expect("Should parse date")
+            .with_timezone(&Utc);
+        let from = NaiveDate::from_ymd_opt(2024, 1, 1);
+        let to = NaiveDate::from_ymd_opt(2024, 3, 31);
+        assert_eq!(
+            from_to_range(from, to, now)
+                .expect("Should build range")
+                .to_string(),
+            "submittedDate:[202401010000 TO 202403312359]"
+        );
+        assert_eq!(
+            from_to_range(from, None, now)
+                .expect("Should build range")
+                .to_string(),
+            "submittedDate:[202401010000 TO 202406081230]"
+        );
+        assert_eq!(
+            from_to_range(None, to, now)
+                .expect("Should build range")
+                .to_string(),
+            "submittedDate:[199101010000 TO 202403312359]"
+        );
+        assert!(from_to_range(to, from, now).is_err());
+    }
+
+    #[test]
+    fn test_build_search_query_from_to() {
+        assert_eq!(
+            search_query_from(&["-c", "cs.CL", "--from", "2024-01-01", "--to", "2024-03-31"])
+                .expect("Should build query"),
+            "cat:cs.CL AND submittedDate:[202401010000 TO 202403312359]"
+        );
+        assert!(
+            Args::try_parse_from([
+                "arxiv-cli",
+                "-c",
+                "cs.CL",
+                "--since",
+                "7d",
+                "--from",
+                "2024-01-01"
+            ])
+            .is_err()
+        );
+        assert!(
+            Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--from", "2024-13-01"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_build_search_query_raw_query() {
+        let raw = "(cat:cs.CL OR cat:cs.LG) ANDNOT au:smith";
+        assert_eq!(
+            search_query_from(&["--raw-query", raw]).expect("Should build query"),
+            raw
+        );
+        for flag in ["--query", "--category", "--author", "--title", "--abstract"] {
+            assert!(
+                Args::try_parse_from(["arxiv-cli", "--raw-query", raw, flag, "x"]).is_err(),
+                "--raw-query should conflict with {}",
+                flag
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_search_query_not() {
+        assert_eq!(
+            search_query_from(&["-c", "cs.CL", "--not", "survey"]).expect("Should build query"),
+            "cat:cs.CL ANDNOT all:survey"
+        );
+        assert_eq!(
+            search_query_from(&[
+                "-c",
+                "cs.CL",
+                "-q",
+                "llm",
+                "--not",
+                "survey",
+                "--not",
+                "literature review"
+            ])
+            .expect("Should build query"),
+            "(cat:cs.CL AND llm) ANDNOT (all:survey OR all:\"literature review\")"
+        );
+        assert_eq!(
+            search_query_from(&["-c", "cs.CL", "-c", "cs.LG", "--not", "survey"])
+                .expect("Should build query"),
+            "(cat:cs.CL OR cat:cs.LG) ANDNOT all:survey"
+        );
+        assert!(search_query_from(&["--id", "2401.12345", "--not", "survey"]).is_err());
+        assert!(Args::try_parse_from(["arxiv-cli", "--raw-query", "llm", "--not", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_limit() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "-l", "20"]);
+        assert_eq!(args.limit, 20);
+        for limit in ["0", "-3", "ten"] {
+            let flag = format!("--limit={}", limit);
+            let err = Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", &flag])
+                .expect_err("Should reject invalid limit");
+            assert_eq!(err.kind(), ErrorKind::ValueValidation);
+        }
+    }
+
+    #[test]
+    fn test_parse_all_flags() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--all"]);
+        assert!(args.all);
+        assert_eq!(args.max_total, DEFAULT_MAX_TOTAL);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--all", "--max-total", "500"]);
+        assert_eq!(args.max_total, 500);
+        assert!(Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--all", "-l", "10"]).is_err());
+        assert!(Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--max-total", "10"]).is_err());
+        assert!(
+            Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--all", "--max-total", "0"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_primary_only() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--primary-only"]);
+        assert!(args.primary_only);
+        assert!(Args::try_parse_from(["arxiv-cli", "-q", "llm", "--primary-only"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_metadata_file() {
+        let args = Args::parse_from([
+            "arxiv-cli",
+            "-c",
+            "cs.CL",
+            "--metadata-file",
+            "runs/attention.jsonl",
+        ]);
+        assert_eq!(
+            args.metadata_file,
+            Some(PathBuf::from("runs/attention.jsonl"))
+        );
+        assert!(
+            Args::try_parse_from([
+                "arxiv-cli",
+                "-c",
+                "cs.CL",
+                "--no-metadata",
+                "--metadata-file",
+                "runs/attention.jsonl",
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_max_filename_length() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL"]);
+        assert_eq!(args.max_filename_length, DEFAULT_MAX_FILENAME_LENGTH);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--max-filename-length", "140"]);
+        assert_eq!(args.max_filename_length, 140);
+        assert!(
+            Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--max-filename-length", "10"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = dirs::home_dir().expect("Should have a home directory");
+        assert_eq!(
+            expand_tilde(PathBuf::from("~/papers/raw")),
+            home.join("papers").join("raw")
+        );
+        assert_eq!(expand_tilde(PathBuf::from("~")), home);
+        assert_eq!(
+            expand_tilde(PathBuf::from("~other/papers")),
+            PathBuf::from("~other/papers")
+        );
+        assert_eq!(
+            expand_tilde(PathBuf::from("papers/~")),
+            PathBuf::from("papers/~")
+        );
+        assert_eq!(expand_tilde(PathBuf::from("pdfs")), PathBuf::from("pdfs"));
+    }
+
+    #[test]
+    fn test_parse_bibtex() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL"]);
+        assert_eq!(args.bibtex, None);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--bibtex"]);
+        assert_eq!(args.bibtex, Some(PathBuf::from(BIBTEX_FILE)));
+        let args = Args::parse_from(["arxiv-cli", "--bibtex", "refs.bib", "-c", "cs.CL"]);
+        assert_eq!(args.bibtex, Some(PathBuf::from("refs.bib")));
+    }
+
+    #[test]
+    fn test_parse_json_array() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--json-array"]);
+        assert!(args.json_array);
+        assert!(
+            Args::try_parse_from([
+                "arxiv-cli",
+                "-c",
+                "cs.CL",
+                "--json-array",
+                "--format",
+                "csv"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_stdout() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL"]);
+        assert!(!args.stdout);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--stdout", "--pdf"]);
+        assert!(args.stdout);
+        for conflicting in ["--no-metadata", "--append", "--validate", "--dry-run"] {
+            let result =
+                Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--stdout", conflicting]);
+            assert!(result.is_err(), "{}", conflicting);
+        }
+    }
+
+    #[test]
+    fn test_parse_pretty() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--pretty"]);
+        assert!(args.pretty);
+        assert!(
+            Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--pretty", "--stdout"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_include_abstract() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL"]);
+        assert!(!args.include_summary_in_metadata);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--include-abstract"]);
+        assert!(args.include_summary_in_metadata);
+    }
+
+    #[test]
+    fn test_parse_interactive() {
+        let args = Args::parse_from(["arxiv-cli", "-q", "graphrag", "--pdf", "-i"]);
+        assert!(args.interactive);
+        assert!(Args::try_parse_from(["arxiv-cli", "-q", "graphrag", "-i", "--dry-run"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let args = Args::parse_from(["arxiv-cli", "-q", "graphrag", "--table"]);
+        assert!(args.table);
+        assert!(
+            Args::try_parse_from(["arxiv-cli", "-q", "graphrag", "--table", "--dry-run"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_open() {
+        let args = Args::parse_from(["arxiv-cli", "-q", "graphrag", "-l", "1", "--open"]);
+        assert!(args.open);
+        let args = Args::parse_from(["arxiv-cli", "-q", "graphrag"]);
+        assert!(!args.open);
+    }
+
+    #[test]
+    fn test_parse_verify() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--pdf"]);
+        assert!(!args.verify);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--pdf", "--verify"]);
+        assert!(args.verify);
+    }
+
+    #[test]
+    fn test_parse_checksums() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--pdf"]);
+        assert!(!args.checksums);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--pdf", "--checksums"]);
+        assert!(args.checksums);
+    }
+
+    #[test]
+    fn test_parse_source() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL"]);
+        assert!(!args.source);
+        let args = Args::try_parse_from(["arxiv-cli", "--source", "get", "1706.03762"])
+            .expect("Should parse");
+        assert!(args.source);
+    }
+
+    #[test]
+    fn test_parse_sqlite() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL"]);
+        assert_eq!(args.sqlite, None);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--sqlite", "papers.db"]);
+        assert_eq!(args.sqlite, Some(PathBuf::from("papers.db")));
+    }
+
+    #[test]
+    fn test_parse_markdown() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL"]);
+        assert_eq!(args.markdown, None);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--markdown", "reading-list.md"]);
+        assert_eq!(args.markdown, Some(PathBuf::from("reading-list.md")));
+        let args = Args::parse_from(["arxiv-cli", "--markdown", "-c", "cs.CL"]);
+        assert_eq!(args.markdown, Some(PathBuf::from("reading_list.md")));
+    }
+
+    #[test]
+    fn test_parse_html() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL"]);
+        assert_eq!(args.html, None);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--html", "index.html"]);
+        assert_eq!(args.html, Some(PathBuf::from("index.html")));
+    }
+
+    #[test]
+    fn test_parse_latex() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL"]);
+        assert!(!args.no_latex);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--no-latex"]);
+        assert!(args.no_latex);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--no-latex", "--latex"]);
+        assert!(!args.no_latex);
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--latex", "--no-latex"]);
+        assert!(args.no_latex);
+    }
+
+    #[test]
+    fn test_validate_categories() {
+        let args = Args::parse_from([
+            "arxiv-cli",
+            "-c",
+            "cs.CL, stat.ML",
+            "--exclude-category",
+            "cs.CV",
+        ]);
+        assert!(validate_categories(&args).is_ok());
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL,cs.clg"]);
+        let message = validate_categories(&args).expect_err("Should reject unknown category");
+        assert!(message.contains("'cs.clg', did you mean cs.CL?"));
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--exclude-category", "cs.XX"]);
+        assert!(validate_categories(&args).is_err());
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.XX", "--no-validate-category"]);
+        assert!(validate_categories(&args).is_ok());
+    }
+
+    #[test]
+    fn test_validate_combinations() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--append", "--primary-only"]);
+        assert!(validate_combinations(&args).is_ok());
+        // Flags set from the config file, which clap does not check
+        let mut args = Args::parse_from(["arxiv-cli", "-q", "llm", "--stdout"]);
+        args.append = true;
+        let message = validate_combinations(&args).expect_err("Should reject --append");
+        assert!(message.contains("--append cannot be used with --stdout"));
+        let mut args = Args::parse_from(["arxiv-cli", "-q", "llm", "--validate"]);
+        args.no_metadata = true;
+        assert!(validate_combinations(&args).is_err());
+        let mut args = Args::parse_from(["arxiv-cli", "-q", "llm"]);
+        args.primary_only = true;
+        assert!(validate_combinations(&args).is_err());
+        args.category = vec!["cs.CL".to_string()];
+        assert!(validate_combinations(&args).is_ok());
+        for format in [
+            &["--format", "json"][..],
+            &["--format", "yaml"],
+            &["--json-array"],
+        ] {
+            let mut args = Args::parse_from(["arxiv-cli", "-q", "llm"].iter().chain(format));
+            args.append = true;
+            assert!(validate_combinations(&args).is_err(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_parse_paper_version() {
+        let args =
+            Args::try_parse_from(["arxiv-cli", "--id", "2401.12345", "--paper-version", "2"])
+                .expect("Should parse");
+        assert_eq!(args.paper_version, Some(2));
+        assert!(
+            Args::try_parse_from(["arxiv-cli", "--id", "2401.12345", "--paper-version", "0"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_get() {
+        let args =
+            Args::try_parse_from(["arxiv-cli", "--pdf", "get", "2301.12345", "cs/0112017v2"])
+                .expect("Should parse");
+        assert!(args.pdf);
+        assert!(has_search_input(&args));
+        match args.command {
+            Some(Command::Get { ids }) => assert_eq!(ids, vec!["2301.12345", "cs/0112017"]),
+            None => panic!("Should parse the get subcommand"),
+        }
+        assert!(Args::try_parse_from(["arxiv-cli", "get"]).is_err());
+        assert!(Args::try_parse_from(["arxiv-cli", "get", "not-an-id"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_count_only() {
+        let args = Args::parse_from(["arxiv-cli", "-c", "cs.CL", "--count-only", "--json"]);
+        assert!(args.count_only && args.json);
+        let args = Args::parse_from([
+            "arxiv-cli",
+            "-c",
+            "cs.CL",
+            "--count-only",
+            "--max-total",
+            "500",
+        ]);
+        assert_eq!(args.max_total, 500);
+        assert!(Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--json"]).is_err());
+        assert!(Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--max-total", "500"]).is_err());
+        assert!(
+            Args::try_parse_from(["arxiv-cli", "-c", "cs.CL", "--count-only", "-l", "5"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_author_bounds() {
+        assert!(check_author_bounds(None, None).is_ok());
+        assert!(check_author_bounds(Some(2), None).is_ok());
+        assert!(check_author_bounds(None, Some(0)).is_ok());
+        assert!(check_author_bounds(Some(2), Some(2)).is_ok());
+        let err = check_author_bounds(Some(3), Some(2)).expect_err("Should reject min > max");
+        assert!(err.to_string().contains("--min-authors 3"));
+        assert!(Args::try_parse_from(["arxiv-cli", "-q", "llm", "--max-authors", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_log_level() {
+        assert_eq!(log_level(0), LevelFilter::Warn);
+        assert_eq!(log_level(1), LevelFilter::Info);
+        assert_eq!(log_level(2), LevelFilter::Debug);
+        assert_eq!(log_level(5), LevelFilter::Trace);
+        let args = Args::parse_from(["arxiv-cli", "-q", "llm", "-vv"]);
+        assert_eq!(args.verbose, 2);
+    }
+
+    #[test]
+    fn test_parse_title_regex() {
+        let args = Args::parse_from(["arxiv-cli", "-q", "llm", "--title-regex", "(?i)benchmark"]);
+        assert!(args.title_regex.is_some());
+        let err = Args::try_parse_from(["arxiv-cli", "-q", "llm", "--title-regex", "(unclosed"])
+            .expect_err("Should reject invalid pattern");
+        assert!(err.to_string().contains("unclosed group"));
+    }
+
+    #[test]
+    fn test_read_query_from_stdin() {
+        assert_eq!(
+            read_query("graph neural networks\n".as_bytes(), "--query").expect("Should read"),
+            "graph neural networks"
+        );
+        assert_eq!(
+            read_query(
+                "  cat:cs.CL ANDNOT au:smith\r\n\n".as_bytes(),
+                "--raw-query"
+            )
+            .expect("Should read"),
+            "cat:cs.CL ANDNOT au:smith"
+        );
+        let err = read_query("\n \n".as_bytes(), "--query").expect_err("Should reject empty");
+        assert!(err.to_string().contains("stdin was empty"));
+    }
+
+    #[test]
+    fn test_resolve_stdin_query() {
+        let mut query = Some("-".to_string());
+        resolve_stdin_query(&mut query, "llm agents\n".as_bytes(), "--query").expect("Should read");
+        assert_eq!(query.as_deref(), Some("llm agents"));
+        let mut query = Some("graphrag".to_string());
+        resolve_stdin_query(&mut query, "ignored".as_bytes(), "--query").expect("Should keep");
+        assert_eq!(query.as_deref(), Some("graphrag"));
+        let mut query = None;
+        resolve_stdin_query(&mut query, "ignored".as_bytes(), "--query").expect("Should keep");
+        assert_eq!(query, None);
+    }
+
+    #[test]
+    fn test_batch_queries() {
+        let path = std::env::temp_dir().join("arxiv_cli_test_batch_queries.txt");
+        std::fs::write(&path, "# standing searches\ngraph neural networks\n\nllm\n")
+            .expect("Should write query file");
+        let mut args = Args::parse_from([
+            "arxiv-cli",
+            "--query-file",
+            &path.to_string_lossy(),
+            "-c",
+            "cs.LG",
+        ]);
+        let queries = batch_queries(&mut args, &path).expect("Should build queries");
+        std::fs::remove_file(&path).expect("Should remove query file");
+        assert_eq!(
+            queries,
+            vec!["cat:cs.LG AND (graph neural networks)", "cat:cs.LG AND llm"]
+        );
+        assert!(Args::try_parse_from(["arxiv-cli", "--query-file", "q.txt", "-q", "llm"]).is_err());
+    }
+
+    #[test]
+    fn test_build_search_query_missing_fields() {
+        for args in [
+            &["arxiv-cli", "--limit", "3"][..],
+            &["arxiv-cli", "--not", "survey"],
+            &["arxiv-cli", "--since", "7d"],
+        ] {
+            let err = Args::try_parse_from(args).expect_err("Should require a search flag");
+            assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::download::{SerDesArxiv, paper_id_without_version};
+
+/// Name of the manifest written to the output directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Kind of file written for a paper
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Artifact {
+    Pdf,
+    Summary,
+    Fulltext,
+    Source,
+}
+
+/// File written for a paper, as it was right after being written
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalFile {
+    /// Path relative to the output directory, with forward slashes, unless
+    /// the file is outside of it
+    pub path: String,
+    pub bytes: u64,
+    pub downloaded_at: String,
+}
+
+/// Files written for a paper, over all the runs
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// arXiv id of the paper, with the version of the files last written
+    pub id: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pdf: Option<LocalFile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<LocalFile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fulltext: Option<LocalFile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<LocalFile>,
+}
+
+impl ManifestEntry {
+    /// Files recorded for the paper, whatever their kind
+    pub fn files(&self) -> impl Iterator<Item = &LocalFile> {
+        [&self.pdf, &self.summary, &self.fulltext, &self.source]
+            .into_iter()
+            .flatten()
+    }
+
+    fn file_mut(&mut self, artifact: Artifact) -> &mut Option<LocalFile> {
+        match artifact {
+            Artifact::Pdf => &mut self.pdf,
+            Artifact::Summary => &mut self.summary,
+            Artifact::Fulltext => &mut self.fulltext,
+            Artifact::Source => &mut self.source,
+        }
+    }
+}
+
+/// Local files of each paper, by arXiv id without version, so that the files
+/// named after sanitized titles can be traced back to their papers
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Manifest {
+    papers: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Read the manifest at `path`, or an empty one if there is none yet. An
+    /// unreadable manifest is an error rather than replaced, so that the
+    /// record of the previous runs is not lost.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("invalid manifest {}: {}", path.display(), e))
+    }
+
+    /// Record the file just written at `path` for the paper, linked as `link`
+    /// from the output directory
+    pub fn record(
+        &mut self,
+        paper: &SerDesArxiv,
+        artifact: Artifact,
+        path: &Path,
+        link: String,
+        downloaded_at: &str,
+    ) -> anyhow::Result<()> {
+        let bytes = fs::metadata(path)?.len();
+        let id = paper
+            .id
+            .split_once("/abs/")
+            .map_or(paper.id.as_str(), |(_, id)| id);
+        let entry = self
+            .papers
+            .entry(paper_id_without_version(id).to_string())
+            .or_default();
+        entry.id = id.to_string();
+        entry.title = paper.title.clone();
+        *entry.file_mut(artifact) = Some(LocalFile {
+            path: link,
+            bytes,
+            downloaded_at: downloaded_at.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Add the files of `other`, which replace the ones of the same kind
+    /// already recorded for the same paper
+    pub fn merge(&mut self, other: Manifest) {
+        for (key, new) in other.papers {
+            let entry = self.papers.entry(key).or_default();
+            entry.id = new.id;
+            entry.title = new.title;
+            for (artifact, file) in [
+                (Artifact::Pdf, new.pdf),
+                (Artifact::Summary, new.summary),
+                (Artifact::Fulltext, new.fulltext),
+                (Artifact::Source, new.source),
+            ] {
+                if file.is_some() {
+                    *entry.file_mut(artifact) = file;
+                }
+            }
+        }
+    }
+
+    /// Merge the files recorded in this run into the manifest at `path`,
+    /// returning the number of papers it lists
+    pub fn save_merged(self, path: &Path) -> anyhow::Result<usize> {
+        let mut manifest = Self::load(path)?;
+        manifest.merge(self);
+        fs::write(path, serde_json::to_string_pretty(&manifest)? + "\n")?;
+        Ok(manifest.len())
+    }
+
+    /// Files recorded for the paper with this id, without version
+    pub fn get(&self, id: &str) -> Option<&ManifestEntry> {
+        self.papers.get(id)
+    }
+
+    /// Papers recorded, by arXiv id without version
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ManifestEntry)> {
+        self.papers.iter().map(|(id, entry)| (id.as_str(), entry))
+    }
+
+    pub fn len(&self) -> usize {
+        self.papers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.papers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn paper(id: &str, title: &str) -> SerDesArxiv {
+        SerDesArxiv {
+            id: format!("http://arxiv.org/abs/{}", id),
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_manifest_merge() {
+        let dir = std::env::temp_dir().join("arxiv_cli_test_manifest_merge");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+        }
+        fs::create_dir_all(dir.join("pdfs")).expect("Should be able to create directory");
+        let path = dir.join(MANIFEST_FILE);
+        let first = paper("2401.00001v1", "First");
+        fs::write(dir.join("pdfs/First.pdf"), "%PDF-1.5").expect("Should write PDF");
+        let mut manifest = Manifest::default();
+        manifest
+            .record(
+                &first,
+                Artifact::Pdf,
+                &dir.join("pdfs/First.pdf"),
+                "pdfs/First.pdf".to_string(),
+                "2024-03-01T12:00:00Z",
+            )
+            .expect("Should record PDF");
+        assert_eq!(manifest.save_merged(&path).expect("Should save"), 1);
+        // The next run writes the summary of the same paper and another paper
+        fs::write(dir.join("First.txt"), "An abstract.").expect("Should write summary");
+        fs::write(dir.join("Second.txt"), "Another.").expect("Should write summary");
+        let mut manifest = Manifest::default();
+        for (paper, file) in [
+            (first, "First.txt"),
+            (paper("2401.00002v1", "Second"), "Second.txt"),
+        ] {
+            manifest
+                .record(
+                    &paper,
+                    Artifact::Summary,
+                    &dir.join(file),
+                    file.to_string(),
+                    "2024-03-02T12:00:00Z",
+                )
+                .expect("Should record summary");
+        }
+        assert_eq!(manifest.save_merged(&path).expect("Should save"), 2);
+        let manifest = Manifest::load(&path).expect("Should load");
+        let entry = manifest.get("2401.00001").expect("Should list first paper");
+        assert_eq!(entry.id, "2401.00001v1");
+        assert_eq!(
+            entry.pdf,
+            Some(LocalFile {
+                path: "pdfs/First.pdf".to_string(),
+                bytes: 8,
+                downloaded_at: "2024-03-01T12:00:00Z".to_string(),
+            })
+        );
+        assert_eq!(entry.summary.as_ref().map(|file| file.bytes), Some(12));
+        let entry = manifest
+            .get("2401.00002")
+            .expect("Should list second paper");
+        assert!(entry.pdf.is_none());
+        assert_eq!(
+            entry.summary.as_ref().map(|file| file.path.as_str()),
+            Some("Second.txt")
+        );
+        // A corrupt manifest is not replaced
+        fs::write(&path, "{").expect("Should write manifest");
+        assert!(Manifest::default().save_merged(&path).is_err());
+        assert_eq!(
+            fs::read_to_string(&path).expect("Should read manifest"),
+            "{"
+        );
+        fs::remove_dir_all(&dir).expect("Should be able to remove directory");
+    }
+}
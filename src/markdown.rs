@@ -0,0 +1,173 @@
+use chrono::NaiveDate;
+
+use crate::bibtex::normalize_whitespace;
+use crate::download::SerDesArxiv;
+
+/// Name of the reading list written by --markdown when no path is given
+pub const READING_LIST_FILE: &str = "reading_list.md";
+
+/// Escape the characters that Markdown would read as formatting or links
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in normalize_whitespace(text).chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Render the papers as a Markdown reading list, in their order: a header
+/// with the queries and the date, then one section per paper with its title,
+/// linked to its HTML version or else its abstract page, authors, date, categories, local PDF if any and abstract.
+/// `pdf_links` holds the path of the downloaded PDF of each paper, relative to
+/// the Markdown file.
+pub fn render_markdown(
+    papers: &[SerDesArxiv],
+    pdf_links: &[Option<String>],
+    queries: &[String],
+    date: NaiveDate,
+) -> String {
+    let mut out = String::from("# arXiv reading list\n\n");
+    let queries: Vec<String> = queries
+        .iter()
+        .filter(|query| !query.is_empty())
+        .map(|query| format!("`{}`", query))
+        .collect();
+    match queries.len() {
+        0 => {}
+        1 => out.push_str(&format!("Query: {}  \n", queries[0])),
+        _ => out.push_str(&format!("Queries: {}  \n", queries.join(", "))),
+    }
+    out.push_str(&format!(
+        "Generated on {} with {} paper(s).\n",
+        date.format("%Y-%m-%d"),
+        papers.len()
+    ));
+    for (index, paper) in papers.iter().enumerate() {
+        out.push_str(&format!(
+            "\n## [{}]({})\n\n",
+            escape_markdown(&paper.title),
+            paper.html_url
+        ));
+        let authors: Vec<String> = paper
+            .authors
+            .iter()
+            .map(|author| escape_markdown(author))
+            .collect();
+        let mut fields = vec![
+            format!("**Authors:** {}", authors.join(", ")),
+            format!(
+                "**Published:** {}",
+                paper.published.get(..10).unwrap_or(&paper.published)
+            ),
+            format!("**Categories:** {}", paper.categories.join(", ")),
+        ];
+        if let Some(Some(link)) = pdf_links.get(index) {
+            let name = link.rsplit('/').next().unwrap_or(link);
+            fields.push(format!("**PDF:** [{}](<{}>)", escape_markdown(name), link));
+        }
+        out.push_str(&fields.join("  \n"));
+        out.push('\n');
+        let summary = normalize_whitespace(&paper.summary);
+        if !summary.is_empty() {
+            out.push_str(&format!("\n> {}\n", summary));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn papers() -> Vec<SerDesArxiv> {
+        let first = SerDesArxiv {
+            id: "http://arxiv.org/abs/1706.03762v7".to_string(),
+            updated: "2023-08-02T00:41:18Z".to_string(),
+            published: "2017-06-12T17:57:34Z".to_string(),
+            title: "Attention Is All\n  You Need".to_string(),
+            summary:
+                "  The dominant sequence transduction models\nare based on recurrent networks.\n"
+                    .to_string(),
+            authors: vec!["Ashish Vaswani".to_string(), "Noam Shazeer".to_string()],
+            primary_category: "cs.CL".to_string(),
+            categories: vec!["cs.CL".to_string(), "cs.LG".to_string()],
+            pdf_url: "https://arxiv.org/pdf/1706.03762v7".to_string(),
+            html_url: "https://arxiv.org/html/1706.03762v7".to_string(),
+            ..Default::default()
+        };
+        let second = SerDesArxiv {
+            id: "http://arxiv.org/abs/cs/0112017v1".to_string(),
+            published: "2001-12-14T00:00:00Z".to_string(),
+            title: "On *bold* claims about [brackets] and snake_case".to_string(),
+            summary: "".to_string(),
+            authors: vec!["Jane Doe".to_string()],
+            primary_category: "cs.AI".to_string(),
+            categories: vec!["cs.AI".to_string()],
+            html_url: "https://arxiv.org/html/cs/0112017v1".to_string(),
+            ..first.clone()
+        };
+        vec![first, second]
+    }
+
+    #[test]
+    fn test_escape_markdown() {
+        assert_eq!(
+            escape_markdown("snake_case *and* [links](url) `code` <b> #1 \\"),
+            "snake\\_case \\*and\\* \\[links\\](url) \\`code\\` \\<b\\> \\#1 \\\\"
+        );
+        assert_eq!(escape_markdown("Plain\n  title"), "Plain title");
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).expect("valid date");
+        let markdown = render_markdown(
+            &papers(),
+            &[Some("pdfs/Attention Is All You Need.pdf".to_string()), None],
+            &["cat:cs.CL AND ti:attention".to_string()],
+            date,
+        );
+        assert_eq!(
+            markdown,
+            concat!(
+                "# arXiv reading list\n",
+                "\n",
+                "Query: `cat:cs.CL AND ti:attention`  \n",
+                "Generated on 2024-01-15 with 2 paper(s).\n",
+                "\n",
+                "## [Attention Is All You Need](https://arxiv.org/html/1706.03762v7)\n",
+                "\n",
+                "**Authors:** Ashish Vaswani, Noam Shazeer  \n",
+                "**Published:** 2017-06-12  \n",
+                "**Categories:** cs.CL, cs.LG  \n",
+                "**PDF:** [Attention Is All You Need.pdf](<pdfs/Attention Is All You Need.pdf>)\n",
+                "\n",
+                "> The dominant sequence transduction models are based on recurrent networks.\n",
+                "\n",
+                "## [On \\*bold\\* claims about \\[brackets\\] and snake\\_case](https://arxiv.org/html/cs/0112017v1)\n",
+                "\n",
+                "**Authors:** Jane Doe  \n",
+                "**Published:** 2001-12-14  \n",
+                "**Categories:** cs.AI\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_header() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).expect("valid date");
+        let markdown = render_markdown(&[], &[], &["ti:a".to_string(), "ti:b".to_string()], date);
+        assert_eq!(
+            markdown,
+            "# arXiv reading list\n\nQueries: `ti:a`, `ti:b`  \nGenerated on 2024-01-15 with 0 paper(s).\n"
+        );
+        let markdown = render_markdown(&[], &[], &[String::new()], date);
+        assert_eq!(
+            markdown,
+            "# arXiv reading list\n\nGenerated on 2024-01-15 with 0 paper(s).\n"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+/// Characters that arXiv's query parser interprets outside of a quoted phrase
+const SPECIAL_CHARS: [char; 3] = ['(', ')', ':'];
+
+/// Field of a paper that a term is searched in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    All,
+    Title,
+    Author,
+    Abstract,
+    Category,
+}
+
+impl Field {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Field::All => "all",
+            Field::Title => "ti",
+            Field::Author => "au",
+            Field::Abstract => "abs",
+            Field::Category => "cat",
+        }
+    }
+}
+
+/// Typed representation of an arXiv search query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// Free text, passed to arXiv as typed
+    Text(String),
+    /// A word or phrase searched in a single field
+    Term {
+        field: Field,
+        value: String,
+    },
+    /// Papers submitted between two instants, bounds included
+    SubmittedDate {
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    },
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    /// Papers matching the first query but not the second one
+    AndNot(Box<Query>, Box<Query>),
+}
+
+impl Query {
+    pub fn text(text: &str) -> Self {
+        Query::Text(text.trim().to_string())
+    }
+
+    pub fn term(field: Field, value: &str) -> Self {
+        Query::Term {
+            field,
+            value: value.to_string(),
+        }
+    }
+
+    /// Conjunction of the queries, or the query itself when there is only one
+    pub fn and(mut queries: Vec<Query>) -> Self {
+        if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            Query::And(queries)
+        }
+    }
+
+    /// Disjunction of the queries, or the query itself when there is only one
+    pub fn or(mut queries: Vec<Query>) -> Self {
+        if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            Query::Or(queries)
+        }
+    }
+
+    pub fn and_not(self, excluded: Query) -> Self {
+        Query::AndNot(Box::new(self), Box::new(excluded))
+    }
+
+    /// Whether the rendered query needs parentheses when nested in another one
+    fn needs_grouping(&self) -> bool {
+        match self {
+            Query::Text(text) => text.contains(char::is_whitespace),
+            Query::Term { .. } | Query::SubmittedDate { .. } => false,
+            Query::And(queries) | Query::Or(queries) => queries.len() > 1,
+            Query::AndNot(..) => true,
+        }
+    }
+
+    fn render_nested(&self) -> String {
+        if self.needs_grouping() {
+            format!("({})", self)
+        } else {
+            self.to_string()
+        }
+    }
+
+    fn render_joined(queries: &[Query], operator: &str) -> String {
+        queries
+            .iter()
+            .map(Query::render_nested)
+            .collect::<Vec<_>>()
+            .join(operator)
+    }
+}
+
+/// Render a fielded term, quoting values that contain whitespace or special characters
+fn render_term(field: Field, value: &str) -> String {
+    // Double quotes would close the phrase early, so they are dropped
+    let value = value.replace('"', "");
+    let value = value.trim();
+    if value.contains(char::is_whitespace) || value.contains(SPECIAL_CHARS) {
+        format!("{}:\"{}\"", field.prefix(), value)
+    } else {
+        format!("{}:{}", field.prefix(), value)
+    }
+}
+
+/// Format a date as expected by arXiv's submittedDate ranges
+fn arxiv_date(date: DateTime<Utc>) -> String {
+    date.format("%Y%m%d%H%M").to_string()
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Text(text) => f.write_str(text),
+            Query::Term { field, value } => f.write_str(&render_term(*field, value)),
+            Query::SubmittedDate { from, to } => write!(
+                f,
+                "submittedDate:[{} TO {}]",
+                arxiv_date(*from),
+                arxiv_date(*to)
+            ),
+            Query::And(queries) => f.write_str(&Query::render_joined(queries, " AND ")),
+            Query::Or(queries) => f.write_str(&Query::render_joined(queries, " OR ")),
+            Query::AndNot(included, excluded) => write!(
+                f,
+                "{} ANDNOT {}",
+                included.render_nested(),
+                excluded.render_nested()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .expect("Should parse date")
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_render_terms() {
+        assert_eq!(
+            Query::term(Field::Category, "cs.CL").to_string(),
+            "cat:cs.CL"
+        );
+        assert_eq!(Query::term(Field::Author, "LeCun").to_string(), "au:LeCun");
+        assert_eq!(
+            Query::term(Field::Author, "Yann LeCun").to_string(),
+            "au:\"Yann LeCun\""
+        );
+        assert_eq!(Query::term(Field::All, "survey").to_string(), "all:survey");
+    }
+
+    #[test]
+    fn test_render_term_quoting() {
+        assert_eq!(
+            Query::term(Field::Abstract, " diffusion ").to_string(),
+            "abs:diffusion"
+        );
+        assert_eq!(
+            Query::term(Field::Abstract, "contrastive\tlearning").to_string(),
+            "abs:\"contrastive\tlearning\""
+        );
+        assert_eq!(
+            Query::term(Field::Title, "the \"best\" model").to_string(),
+            "ti:\"the best model\""
+        );
+        assert_eq!(Query::term(Field::Title, "f(x)").to_string(), "ti:\"f(x)\"");
+        assert_eq!(
+            Query::term(Field::Title, "GPT:4").to_string(),
+            "ti:\"GPT:4\""
+        );
+    }
+
+    #[test]
+    fn test_render_text() {
+        assert_eq!(Query::text(" graphrag ").to_string(), "graphrag");
+        assert_eq!(
+            Query::text("machine learning").to_string(),
+            "machine learning"
+        );
+        assert_eq!(
+            Query::and(vec![
+                Query::term(Field::Category, "cs.LG"),
+                Query::text("machine learning"),
+            ])
+            .to_string(),
+            "cat:cs.LG AND (machine learning)"
+        );
+    }
+
+    #[test]
+    fn test_render_and_or() {
+        assert_eq!(
+            Query::and(vec![Query::term(Field::Category, "cs.CL")]).to_string(),
+            "cat:cs.CL"
+        );
+        assert_eq!(
+            Query::or(vec![
+                Query::term(Field::Category, "cs.CL"),
+                Query::term(Field::Category, "cs.LG"),
+            ])
+            .to_string(),
+            "cat:cs.CL OR cat:cs.LG"
+        );
+        assert_eq!(
+            Query::and(vec![
+                Query::or(vec![
+                    Query::term(Field::Category, "cs.CL"),
+                    Query::term(Field::Category, "cs.LG"),
+                ]),
+                Query::text("llm"),
+                Query::term(Field::Author, "Yann LeCun"),
+            ])
+            .to_string(),
+            "(cat:cs.CL OR cat:cs.LG) AND llm AND au:\"Yann LeCun\""
+        );
+    }
+
+    #[test]
+    fn test_render_nested() {
+        let query = Query::or(vec![
+            Query::and(vec![
+                Query::term(Field::Title, "graph"),
+                Query::term(Field::Abstract, "retrieval augmented"),
+            ]),
+            Query::and(vec![
+                Query::term(Field::Author, "Smith"),
+                Query::or(vec![
+                    Query::term(Field::Category, "cs.IR"),
+                    Query::term(Field::Category, "cs.DB"),
+                ]),
+            ]),
+        ]);
+        assert_eq!(
+            query.to_string(),
+            "(ti:graph AND abs:\"retrieval augmented\") OR (au:Smith AND (cat:cs.IR OR cat:cs.DB))"
+        );
+    }
+
+    #[test]
+    fn test_render_and_not() {
+        let included = Query::term(Field::Category, "cs.CL");
+        assert_eq!(
+            included
+                .clone()
+                .and_not(Query::term(Field::Author, "smith"))
+                .to_string(),
+            "cat:cs.CL ANDNOT au:smith"
+        );
+        let query = Query::and(vec![included, Query::text("llm")]).and_not(Query::or(vec![
+            Query::term(Field::All, "survey"),
+            Query::term(Field::All, "literature review"),
+        ]));
+        assert_eq!(
+            query.to_string(),
+            "(cat:cs.CL AND llm) ANDNOT (all:survey OR all:\"literature review\")"
+        );
+        let nested = Query::and(vec![query, Query::term(Field::Title, "benchmark")]);
+        assert_eq!(
+            nested.to_string(),
+            "((cat:cs.CL AND llm) ANDNOT (all:survey OR all:\"literature review\")) AND ti:benchmark"
+        );
+    }
+
+    #[test]
+    fn test_render_submitted_date() {
+        let range = Query::SubmittedDate {
+            from: date("2024-01-01T00:00:00Z"),
+            to: date("2024-03-31T23:59:00Z"),
+        };
+        assert_eq!(
+            range.to_string(),
+            "submittedDate:[202401010000 TO 202403312359]"
+        );
+        assert_eq!(
+            Query::and(vec![Query::term(Field::Category, "cs.CL"), range]).to_string(),
+            "cat:cs.CL AND submittedDate:[202401010000 TO 202403312359]"
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use std::path::Path;
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::download::{SerDesArxiv, paper_id_without_version};
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS papers (
+    id TEXT PRIMARY KEY,
+    entry_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    summary TEXT NOT NULL,
+    authors TEXT NOT NULL,
+    primary_category TEXT NOT NULL,
+    categories TEXT NOT NULL,
+    published TEXT NOT NULL,
+    updated TEXT NOT NULL,
+    pdf_url TEXT NOT NULL,
+    html_url TEXT NOT NULL,
+    comment TEXT,
+    journal_ref TEXT,
+    doi TEXT,
+    license TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+)";
+
+/// Insert the paper, or update its row when anything changed since it was
+/// stored, e.g. a new version or a journal reference. A row is never replaced
+/// by an older version of the paper, and the journal reference, DOI and
+/// license are kept when the new record lacks them, as the feed has no license.
+const UPSERT: &str = "INSERT INTO papers (
+    id, entry_id, title, summary, authors, primary_category, categories,
+    published, updated, pdf_url, html_url, comment, journal_ref, doi, license,
+    created_at, updated_at
+) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?16)
+ON CONFLICT (id) DO UPDATE SET
+    entry_id = excluded.entry_id,
+    title = excluded.title,
+    summary = excluded.summary,
+    authors = excluded.authors,
+    primary_category = excluded.primary_category,
+    categories = excluded.categories,
+    published = excluded.published,
+    updated = excluded.updated,
+    pdf_url = excluded.pdf_url,
+    html_url = excluded.html_url,
+    comment = excluded.comment,
+    journal_ref = COALESCE(excluded.journal_ref, papers.journal_ref),
+    doi = COALESCE(excluded.doi, papers.doi),
+    license = COALESCE(excluded.license, papers.license),
+    updated_at = excluded.updated_at
+WHERE excluded.updated >= papers.updated
+    AND (papers.entry_id, papers.title, papers.summary, papers.authors,
+         papers.primary_category, papers.categories, papers.updated,
+         papers.comment, papers.journal_ref, papers.doi, papers.license)
+    IS NOT (excluded.entry_id, excluded.title, excluded.summary, excluded.authors,
+            excluded.primary_category, excluded.categories, excluded.updated,
+            excluded.comment, COALESCE(excluded.journal_ref, papers.journal_ref),
+            COALESCE(excluded.doi, papers.doi),
+            COALESCE(excluded.license, papers.license))";
+
+/// Number of papers inserted, updated and left as they were by an upsert
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UpsertCounts {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+/// SQLite database of papers, one row per arXiv id whatever the version,
+/// with the authors and categories stored as JSON arrays
+pub struct PaperStore {
+    connection: Connection,
+}
+
+impl PaperStore {
+    /// Open the database at `path`, creating it and the `papers` table if needed
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute(SCHEMA, [])?;
+        Ok(Self { connection })
+    }
+
+    /// Insert the papers, or update the rows of the ones already stored,
+    /// bumping their `updated_at`
+    pub fn upsert(&mut self, papers: &[SerDesArxiv]) -> anyhow::Result<UpsertCounts> {
+        let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        self.upsert_at(papers, &now)
+    }
+
+    fn upsert_at(&mut self, papers: &[SerDesArxiv], now: &str) -> anyhow::Result<UpsertCounts> {
+        let transaction = self.connection.transaction()?;
+        let mut counts = UpsertCounts::default();
+        {
+            let mut exists = transaction.prepare("SELECT 1 FROM papers WHERE id = ?1")?;
+            let mut upsert = transaction.prepare(UPSERT)?;
+            for paper in papers {
+                let id = paper_id_without_version(&paper.id);
+                let existed = exists.query_row([id], |_| Ok(())).optional()?.is_some();
+                let changed = upsert.execute(params![
+                    id,
+                    paper.id,
+                    paper.title,
+                    paper.summary,
+                    serde_json::to_string(&paper.authors)?,
+                    paper.primary_category,
+                    serde_json::to_string(&paper.categories)?,
+                    paper.published,
+                    paper.updated,
+                    paper.pdf_url,
+                    paper.html_url,
+                    paper.comment,
+                    paper.journal_ref,
+                    paper.doi,
+                    paper.license,
+                    now,
+                ])?;
+                match (existed, changed > 0) {
+                    (false, _) => counts.inserted += 1,
+                    (true, true) => counts.updated += 1,
+                    (true, false) => counts.unchanged += 1,
+                }
+            }
+        }
+        transaction.commit()?;
+        Ok(counts)
+    }
+
+    /// Number of papers in the database
+    pub fn count(&self) -> anyhow::Result<usize> {
+        let count: i64 = self
+            .connection
+            .query_row("SELECT COUNT(*) FROM papers", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn paper(id: &str, title: &str) -> SerDesArxiv {
+        SerDesArxiv {
+            id: format!("http://arxiv.org/abs/{}", id),
+            updated: "2024-01-01T00:00:00Z".to_string(),
+            published: "2024-01-01T00:00:00Z".to_string(),
+            title: title.to_string(),
+            summary: "An abstract.".to_string(),
+            authors: vec!["Jane Doe".to_string(), "John Smith".to_string()],
+            primary_category: "cs.CL".to_string(),
+            categories: vec!["cs.CL".to_string(), "cs.LG".to_string()],
+            pdf_url: format!("http://arxiv.org/pdf/{}", id),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_upsert() {
+        let path = std::env::temp_dir().join("arxiv_cli_test_upsert.db");
+        if path.exists() {
+            std::fs::remove_file(&path).expect("Should be able to remove database");
+        }
+        let mut store = PaperStore::open(&path).expect("Should open database");
+        let first_run = [
+            paper("2401.00001v1", "First"),
+            paper("2401.00002v1", "Second"),
+        ];
+        let counts = store
+            .upsert_at(&first_run, "2024-02-01T00:00:00Z")
+            .expect("Should upsert");
+        assert_eq!(
+            counts,
+            UpsertCounts {
+                inserted: 2,
+                updated: 0,
+                unchanged: 0
+            }
+        );
+        // The second run overlaps the first, with a new version of one paper
+        let mut revised = paper("2401.00002v2", "Second, revised");
+        revised.updated = "2024-02-15T00:00:00Z".to_string();
+        let second_run = [
+            paper("2401.00001v1", "First"),
+            revised,
+            paper("2401.00003v1", "Third"),
+        ];
+        drop(store);
+        let mut store = PaperStore::open(&path).expect("Should reopen database");
+        let counts = store
+            .upsert_at(&second_run, "2024-03-01T00:00:00Z")
+            .expect("Should upsert");
+        assert_eq!(
+            counts,
+            UpsertCounts {
+                inserted: 1,
+                updated: 1,
+                unchanged: 1
+            }
+        );
+        assert_eq!(store.count().expect("Should count"), 3);
+        let row = |id: &str| -> (String, String, String, String) {
+            store
+                .connection
+                .query_row(
+                    "SELECT entry_id, title, created_at, updated_at FROM papers WHERE id = ?1",
+                    [id],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                )
+                .expect("Should find paper")
+        };
+        let (entry_id, title, created_at, updated_at) = row("2401.00002");
+        assert_eq!(entry_id, "http://arxiv.org/abs/2401.00002v2");
+        assert_eq!(title, "Second, revised");
+        assert_eq!(created_at, "2024-02-01T00:00:00Z");
+        assert_eq!(updated_at, "2024-03-01T00:00:00Z");
+        // Unchanged papers keep their timestamp
+        let (_, _, _, updated_at) = row("2401.00001");
+        assert_eq!(updated_at, "2024-02-01T00:00:00Z");
+        let authors: String = store
+            .connection
+            .query_row(
+                "SELECT authors FROM papers WHERE id = '2401.00003'",
+                [],
+                |row| row.get(0),
+            )
+            .expect("Should find paper");
+        assert_eq!(authors, r#"["Jane Doe","John Smith"]"#);
+        drop(store);
+        std::fs::remove_file(&path).expect("Should be able to remove database");
+    }
+
+    #[test]
+    fn test_upsert_keeps_newer_data() {
+        let path = std::env::temp_dir().join("arxiv_cli_test_upsert_keeps_newer_data.db");
+        if path.exists() {
+            std::fs::remove_file(&path).expect("Should be able to remove database");
+        }
+        let mut store = PaperStore::open(&path).expect("Should open database");
+        let mut licensed = paper("2401.00001v2", "First, revised");
+        licensed.updated = "2024-02-01T00:00:00Z".to_string();
+        licensed.doi = Some("10.1000/example".to_string());
+        licensed.license = Some("http://creativecommons.org/licenses/by/4.0/".to_string());
+        store
+            .upsert_at(&[licensed], "2024-02-01T00:00:00Z")
+            .expect("Should upsert");
+        // A later run without --resolve-license, then one with --paper-version 1
+        let mut unlicensed = paper("2401.00001v2", "First, revised");
+        unlicensed.updated = "2024-02-01T00:00:00Z".to_string();
+        let older = paper("2401.00001v1", "First");
+        for (run, now) in [
+            (unlicensed, "2024-03-01T00:00:00Z"),
+            (older, "2024-04-01T00:00:00Z"),
+        ] {
+            let counts = store.upsert_at(&[run], now).expect("Should upsert");
+            assert_eq!(
+                counts,
+                UpsertCounts {
+                    inserted: 0,
+                    updated: 0,
+                    unchanged: 1
+                }
+            );
+        }
+        let row: (String, Option<String>, Option<String>, String) = store
+            .connection
+            .query_row(
+                "SELECT entry_id, doi, license, updated_at FROM papers WHERE id = '2401.00001'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .expect("Should find paper");
+        assert_eq!(
+            row,
+            (
+                "http://arxiv.org/abs/2401.00001v2".to_string(),
+                Some("10.1000/example".to_string()),
+                Some("http://creativecommons.org/licenses/by/4.0/".to_string()),
+                "2024-02-01T00:00:00Z".to_string()
+            )
+        );
+        drop(store);
+        std::fs::remove_file(&path).expect("Should be able to remove database");
+    }
+}
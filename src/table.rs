@@ -0,0 +1,144 @@
+use crate::bibtex::normalize_whitespace;
+use crate::download::SerDesArxiv;
+
+/// Width used when standard output is not a terminal
+pub const DEFAULT_TABLE_WIDTH: usize = 120;
+/// Narrowest title column, below which the table overflows the terminal
+const MIN_TITLE_WIDTH: usize = 20;
+const HEADERS: [&str; 5] = ["#", "ID", "Title", "Category", "Published"];
+
+/// Width of the terminal attached to standard output, if any
+pub fn terminal_width() -> Option<usize> {
+    console::Term::stdout()
+        .size_checked()
+        .map(|(_, columns)| columns as usize)
+}
+
+/// Shorten `text` to at most `width` characters, ending it with an ellipsis
+/// when anything is cut
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Render the papers as a table of their index, id, title, primary category
+/// and publication date, fitting in `width` columns: the title is the column
+/// that shrinks, down to a minimum
+pub fn render_table(papers: &[SerDesArxiv], width: usize) -> String {
+    let rows: Vec<[String; 5]> = papers
+        .iter()
+        .enumerate()
+        .map(|(index, paper)| {
+            let id = paper
+                .id
+                .split_once("/abs/")
+                .map_or(paper.id.as_str(), |(_, id)| id);
+            [
+                (index + 1).to_string(),
+                id.to_string(),
+                normalize_whitespace(&paper.title),
+                paper.primary_category.clone(),
+                paper
+                    .published
+                    .get(..10)
+                    .unwrap_or(&paper.published)
+                    .to_string(),
+            ]
+        })
+        .collect();
+    let mut widths = HEADERS.map(|header| header.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    // The title gets whatever the other columns, and the two spaces between
+    // columns, leave
+    let others: usize = widths.iter().sum::<usize>() - widths[2];
+    let available = width.saturating_sub(others + 2 * (HEADERS.len() - 1));
+    widths[2] = widths[2].min(available.max(MIN_TITLE_WIDTH));
+    let line = |cells: [&str; 5]| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(index, (cell, width))| {
+                let cell = truncate(cell, width);
+                // Right-align the index, left-align the rest
+                if index == 0 {
+                    format!("{:>width$}", cell, width = width)
+                } else {
+                    format!("{:<width$}", cell, width = width)
+                }
+            })
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+    let mut out = line(HEADERS);
+    out.push('\n');
+    let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    out.push_str(&separator.join("  "));
+    out.push('\n');
+    for row in &rows {
+        out.push_str(&line(row.each_ref().map(String::as_str)));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn paper(id: &str, title: &str, category: &str) -> SerDesArxiv {
+        SerDesArxiv {
+            id: format!("http://arxiv.org/abs/{}", id),
+            published: "2024-01-15T10:00:00Z".to_string(),
+            title: title.to_string(),
+            primary_category: category.to_string(),
+            categories: vec![category.to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_table() {
+        let papers = [
+            paper("2401.00001v1", "Attention Is All\n  You Need", "cs.CL"),
+            paper("cs/0112017v1", "Short", "cs.AI"),
+        ];
+        assert_eq!(
+            render_table(&papers, 120),
+            concat!(
+                "#  ID            Title                      Category  Published\n",
+                "-  ------------  -------------------------  --------  ----------\n",
+                "1  2401.00001v1  Attention Is All You Need  cs.CL     2024-01-15\n",
+                "2  cs/0112017v1  Short                      cs.AI     2024-01-15\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_table_narrow() {
+        let title = "A very long title that cannot fit in a narrow terminal at all";
+        let papers = [paper("2401.00001v1", title, "cs.CL")];
+        let table = render_table(&papers, 70);
+        assert!(table.lines().all(|line| line.chars().count() <= 70));
+        let row = table.lines().nth(2).expect("Should have a row");
+        assert!(row.contains("A very long title that cannot …  cs.CL"));
+        // The title column never gets narrower than the minimum
+        let table = render_table(&papers, 10);
+        let row = table.lines().nth(2).expect("Should have a row");
+        assert!(row.contains(&format!("{}…", &title[..MIN_TITLE_WIDTH - 1])));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Short", 10), "Short");
+        assert_eq!(truncate("Schrödinger bridges", 8), "Schrödi…");
+    }
+}
@@ -0,0 +1,36 @@
+use std::process::Command;
+
+use serde_json::Value;
+
+#[test]
+fn integration_test_stdout() {
+    let output_dir = std::env::temp_dir().join("arxiv_cli_integration_test_stdout");
+    if output_dir.exists() {
+        std::fs::remove_dir_all(&output_dir).expect("Should be able to remove directory");
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_arxiv-cli"))
+        .args(["-q", "graphrag", "-l", "3", "--stdout", "--output-dir"])
+        .arg(&output_dir)
+        .output()
+        .expect("Should run arxiv-cli");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).expect("Should print UTF-8");
+    let records: Vec<Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Every line should be a JSON object"))
+        .collect();
+    assert_eq!(records.len(), 3);
+    for record in &records {
+        assert!(
+            record["id"]
+                .as_str()
+                .is_some_and(|id| id.contains("arxiv.org/abs/"))
+        );
+    }
+    // Nothing is written to disk
+    assert!(!output_dir.exists());
+}